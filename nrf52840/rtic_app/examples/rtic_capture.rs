@@ -0,0 +1,303 @@
+// examples/rtic_capture.rs
+//
+// Records raw sample bursts, triggered when the string is plucked, and sends
+// them (with sample rate, gain, resolution and timestamp) over USB serial
+// using the telemetry protocol, preceded by the active ADC profile. On the
+// host:
+//
+// cat /dev/ttyACM0 > capture.bin
+// cargo run -- import capture.bin
+//
+// The ADC profile is selected at build time (`ADC_PROFILE`) and can be
+// changed from the host:
+//
+// cargo run -- profile low-gain --device /dev/ttyACM0
+
+#![no_main]
+#![no_std]
+
+use guitar_tuner::{
+    adc::AdcProfile,
+    capture,
+    protocol::{CaptureHeader, Decoder, Frame, MAX_FRAME},
+};
+use hal::pac;
+use nrf52840_hal::{self as hal, pac::SAADC};
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+use tuner_app::saadc::{configure, PROFILE};
+
+const CAPTURE_SIZE: usize = 2048; // approximately 2 seconds of data at 1kHz
+const FS: f32 = 1000.0; // sample rate
+const TRIGGER: i16 = 1000; // absolute sample value starting a capture
+
+pub struct CaptureBuffer {
+    header: CaptureHeader,
+    profile: AdcProfile,
+    samples: [i16; CAPTURE_SIZE],
+    len: usize,
+    /// Set while the capture is being sent.
+    busy: bool,
+}
+
+#[rtic::app(device = pac, dispatchers = [UARTE1, UARTE0_UART0])]
+mod app {
+    use super::*;
+    use cortex_m::asm;
+
+    use fugit::ExtU32;
+    use hal::{
+        clocks::{Clocks, ExternalOscillator, Internal, LfOscStopped},
+        monotonic::MonotonicTimer,
+        usbd::{UsbPeripheral, Usbd},
+    };
+    use usb_device::{
+        bus::UsbBusAllocator,
+        device::{StringDescriptors, UsbDevice, UsbDeviceBuilder, UsbDeviceState, UsbVidPid},
+        UsbError,
+    };
+    use usbd_serial::{SerialPort, USB_CLASS_CDC};
+
+    use pac::TIMER0;
+
+    const TIMER_HZ: u32 = 16_000_000; // 16 MHz
+
+    type UsbBus = Usbd<UsbPeripheral<'static>>;
+
+    #[monotonic(binds = TIMER0, default = true)]
+    type MyMono = MonotonicTimer<TIMER0, TIMER_HZ>;
+
+    #[shared]
+    struct Shared {
+        usb_dev: UsbDevice<'static, UsbBus>,
+        serial: SerialPort<'static, UsbBus>,
+        capture: CaptureBuffer,
+        /// Profile requested by the host, applied by `sample`.
+        pending: Option<AdcProfile>,
+    }
+
+    #[local]
+    struct Local {
+        saadc: SAADC,
+    }
+
+    #[init(local = [
+        clocks: Option<Clocks<ExternalOscillator, Internal, LfOscStopped>> = None,
+        usb_bus: Option<UsbBusAllocator<UsbBus>> = None,
+    ])]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtt_init_print!();
+        rprintln!("\n--- rtic capture ---\n");
+
+        let mono = MyMono::new(cx.device.TIMER0);
+        let saadc = cx.device.SAADC;
+        configure(&saadc, &PROFILE);
+        rprintln!("adc {}", PROFILE);
+
+        rprintln!("calibrated");
+
+        // USB requires the external high frequency oscillator
+        let clocks = cx
+            .local
+            .clocks
+            .insert(Clocks::new(cx.device.CLOCK).enable_ext_hfosc());
+        let usb_bus = cx
+            .local
+            .usb_bus
+            .insert(Usbd::new(UsbPeripheral::new(cx.device.USBD, clocks)));
+        let serial = SerialPort::new(usb_bus);
+        let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
+            .strings(&[StringDescriptors::default()
+                .manufacturer("LTU")
+                .product("Guitar Tuner Capture")
+                .serial_number("0001")])
+            .unwrap()
+            .device_class(USB_CLASS_CDC)
+            .build();
+
+        let capture = CaptureBuffer {
+            header: CaptureHeader {
+                id: 0,
+                sample_rate: FS,
+                gain: PROFILE.gain.factor(),
+                resolution: PROFILE.resolution.bits(),
+                timestamp: 0,
+                length: CAPTURE_SIZE as u32,
+            },
+            profile: PROFILE,
+            samples: [0; CAPTURE_SIZE],
+            len: 0,
+            busy: false,
+        };
+
+        sample::spawn(monotonics::now()).unwrap();
+        (
+            Shared {
+                usb_dev,
+                serial,
+                capture,
+                pending: None,
+            },
+            Local { saadc },
+            init::Monotonics(mono),
+        )
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        loop {
+            // Put core to sleep until next interrupt
+            asm::wfe();
+        }
+    }
+
+    #[task(binds = USBD, priority = 2, shared = [usb_dev, serial, capture, pending], local = [decoder: Decoder = Decoder::new()])]
+    fn usb(mut cx: usb::Context) {
+        let mut buf = [0u8; 64];
+        let n = (&mut cx.shared.usb_dev, &mut cx.shared.serial).lock(|usb_dev, serial| {
+            usb_dev.poll(&mut [serial]);
+            serial.read(&mut buf).unwrap_or(0)
+        });
+
+        // profile requests from the host
+        let mut requested = None;
+        cx.local.decoder.feed(&buf[..n], |r| match r {
+            Ok(Frame::Profile(p)) => requested = Some(p),
+            Ok(_) => {}
+            Err(e) => rprintln!("serial: {}", e),
+        });
+
+        if let Some(p) = requested {
+            rprintln!("adc {}", p);
+            cx.shared.pending.lock(|pending| *pending = Some(p));
+            cx.shared.capture.lock(|c| {
+                c.profile = p;
+                c.header.gain = p.gain.factor();
+                c.header.resolution = p.resolution.bits();
+                // a partial capture was recorded with the old profile
+                if !c.busy {
+                    c.len = 0;
+                }
+            });
+        }
+    }
+
+    // Drift free periodic task at highest priority
+    #[task(priority = 3, shared = [pending], local = [saadc])]
+    fn sample(mut cx: sample::Context, instant: fugit::TimerInstantU32<TIMER_HZ>) {
+        if let Some(p) = cx.shared.pending.lock(|p| p.take()) {
+            configure(cx.local.saadc, &p);
+        }
+        let s = get_sample(cx.local.saadc);
+        process::spawn(s).unwrap();
+
+        // Spawn a new message with 1ms offset to spawned time
+        let next_instant = instant + 1.millis();
+        sample::spawn_at(next_instant, next_instant).unwrap();
+    }
+
+    #[task(priority = 2, shared = [capture], local = [now: u32 = 0], capacity = 2)]
+    fn process(mut cx: process::Context, sample: i16) {
+        // one sample per ms
+        let now = *cx.local.now;
+        *cx.local.now = now.wrapping_add(1);
+
+        cx.shared.capture.lock(|c| {
+            if c.busy {
+                return;
+            }
+            if c.len == 0 {
+                if sample.unsigned_abs() < TRIGGER as u16 {
+                    return;
+                }
+                c.header.timestamp = now;
+            }
+            c.samples[c.len] = sample;
+            c.len += 1;
+            if c.len == CAPTURE_SIZE {
+                c.busy = true;
+                send::spawn().ok();
+            }
+        });
+    }
+
+    #[task(priority = 1, shared = [usb_dev, serial, capture])]
+    fn send(mut cx: send::Context) {
+        let configured = cx
+            .shared
+            .usb_dev
+            .lock(|usb_dev| usb_dev.state() == UsbDeviceState::Configured);
+
+        let (header, profile) = cx.shared.capture.lock(|c| (c.header, c.profile));
+        if configured {
+            rprintln!("sending capture {}", header.id);
+            let mut buf = [0u8; MAX_FRAME];
+            // the profile and header are always within `MAX_FRAME`
+            let n = Frame::Profile(profile).encode(&mut buf).unwrap();
+            let mut ok = write_all(&mut cx.shared.serial, &buf[..n]);
+            let n = Frame::Capture(header).encode(&mut buf).unwrap();
+            ok = ok && write_all(&mut cx.shared.serial, &buf[..n]);
+
+            let mut seq = 0;
+            while ok && seq < capture::nr_chunks(CAPTURE_SIZE) {
+                // only hold the lock while encoding, so sampling goes on
+                let n = cx.shared.capture.lock(|c| {
+                    capture::chunk(&c.samples, seq)
+                        .and_then(|f| f.encode(&mut buf).ok())
+                        .unwrap_or(0)
+                });
+                ok = write_all(&mut cx.shared.serial, &buf[..n]);
+                seq += 1;
+            }
+        } else {
+            rprintln!("capture {} dropped, usb not configured", header.id);
+        }
+
+        cx.shared.capture.lock(|c| {
+            c.header.id = c.header.id.wrapping_add(1);
+            c.len = 0;
+            c.busy = false;
+        });
+    }
+
+    // Writes all bytes, waiting for the usb task to drain the serial
+    // buffer. Returns false if the host went away.
+    fn write_all(
+        serial: &mut impl rtic::Mutex<T = SerialPort<'static, UsbBus>>,
+        mut bytes: &[u8],
+    ) -> bool {
+        while !bytes.is_empty() {
+            match serial.lock(|s| s.write(bytes)) {
+                Ok(n) => bytes = &bytes[n..],
+                Err(UsbError::WouldBlock) => {}
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+fn get_sample(saadc: &mut SAADC) -> i16 {
+    let mut val: i16 = 0;
+    saadc
+        .result
+        .ptr
+        .write(|w| unsafe { w.ptr().bits(((&mut val) as *mut _) as u32) });
+    saadc.result.maxcnt.write(|w| unsafe { w.maxcnt().bits(1) });
+
+    // Conservative compiler fence to prevent starting the ADC before the
+    // pointer and maxcount have been set.
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    saadc.tasks_start.write(|w| unsafe { w.bits(1) });
+    saadc.tasks_sample.write(|w| unsafe { w.bits(1) });
+
+    while saadc.events_end.read().bits() == 0 {}
+
+    saadc.events_end.reset();
+
+    // Second fence to prevent optimizations creating issues with the EasyDMA-modified `val`.
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    val
+}
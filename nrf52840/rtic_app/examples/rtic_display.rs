@@ -0,0 +1,222 @@
+// examples/rtic_display.rs
+//
+// The `rtic_tuner` detector, with the result shown on a 128x64 SSD1306 OLED
+// connected over I2C (SCL P0.27, SDA P0.26).
+
+#![no_main]
+#![no_std]
+
+use guitar_tuner::{
+    note::A4,
+    protocol::{Reading, State},
+    tuning::Tuning,
+};
+use hal::pac;
+use nrf52840_hal::{self as hal, pac::SAADC};
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+use tuner_app::saadc::{configure, PROFILE};
+
+const BUFFER_SIZE: usize = 1024; // approximately 1second of data at 1kHz
+type Buffer = [i16; BUFFER_SIZE];
+
+const FS: f32 = 1000.0; // sample rate
+const LEVEL_WINDOW: usize = 100; // samples used to compute the level
+const NOISE_GATE: f32 = -50.0; // dBFS
+
+#[rtic::app(device = pac, dispatchers = [UARTE1, UARTE0_UART0, SWI0_EGU0])]
+mod app {
+    use super::*;
+    use cortex_m::asm;
+
+    use fugit::ExtU32;
+    use hal::{
+        gpio::p0::Parts as P0Parts,
+        monotonic::MonotonicTimer,
+        twim::{self, Twim},
+    };
+    use ssd1306::{mode::BufferedGraphicsMode, prelude::*, I2CDisplayInterface, Ssd1306};
+
+    use pac::{TIMER0, TWIM0};
+
+    const TIMER_HZ: u32 = 16_000_000; // 16 MHz
+
+    type Display = Ssd1306<
+        I2CInterface<Twim<TWIM0>>,
+        DisplaySize128x64,
+        BufferedGraphicsMode<DisplaySize128x64>,
+    >;
+
+    #[monotonic(binds = TIMER0, default = true)]
+    type MyMono = MonotonicTimer<TIMER0, TIMER_HZ>;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {
+        saadc: SAADC,
+        display: Display,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtt_init_print!();
+        rprintln!("\n--- rtic display ---\n");
+
+        let mono = MyMono::new(cx.device.TIMER0);
+        let saadc = cx.device.SAADC;
+        // ADC front-end selected at build time by `ADC_PROFILE`
+        configure(&saadc, &PROFILE);
+        rprintln!("adc {}", PROFILE);
+        rprintln!("calibrated");
+
+        // Display
+        let gpios = P0Parts::new(cx.device.P0);
+        let pins = twim::Pins {
+            scl: gpios.p0_27.into_floating_input().degrade(),
+            sda: gpios.p0_26.into_floating_input().degrade(),
+        };
+        let i2c = Twim::new(cx.device.TWIM0, pins, twim::Frequency::K400);
+        let mut display = Ssd1306::new(
+            I2CDisplayInterface::new(i2c),
+            DisplaySize128x64,
+            DisplayRotation::Rotate0,
+        )
+        .into_buffered_graphics_mode();
+        display.init().unwrap();
+        rprintln!("display initialized");
+
+        sample::spawn(monotonics::now()).unwrap();
+        (Shared {}, Local { saadc, display }, init::Monotonics(mono))
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        loop {
+            // Put core to sleep until next interrupt
+            asm::wfe();
+        }
+    }
+
+    // Drift free periodic task at highest priority
+    #[task(priority = 3, local = [saadc])]
+    fn sample(cx: sample::Context, instant: fugit::TimerInstantU32<TIMER_HZ>) {
+        let s = get_sample(cx.local.saadc);
+        process::spawn(s).unwrap();
+
+        // Spawn a new message with 1ms offset to spawned time
+        let next_instant = instant + 1.millis();
+        sample::spawn_at(next_instant, next_instant).unwrap();
+    }
+
+    #[task(priority = 2, local = [cnt: usize = 0, period: usize = 3, ptr: usize = 0, buffer: Buffer
+    = [0; BUFFER_SIZE]], capacity = 2)]
+    fn process(cx: process::Context, sample: i16) {
+        let process::LocalResources {
+            cnt,
+            period,
+            ptr,
+            buffer,
+        } = cx.local;
+        *ptr = (*ptr + 1) % buffer.len();
+        buffer[*ptr] = sample;
+
+        if *cnt == 0 {
+            let (err_low, err_mid, err_high) = estimate_error(*period, *ptr, buffer);
+            if err_mid > err_low.min(err_high) {
+                if err_low < err_high {
+                    *period -= 1;
+                } else {
+                    *period += 1;
+                }
+            }
+            *period = (*period).clamp(2, 13); // set new period
+            *cnt = *period * 5; // set counter
+
+            // The display task drops readings while busy flushing
+            show::spawn(reading(*period, *ptr, buffer)).ok();
+        }
+        *cnt -= 1;
+    }
+
+    #[task(priority = 1, local = [display], capacity = 1)]
+    fn show(cx: show::Context, reading: Reading) {
+        let display = cx.local.display;
+        guitar_tuner::display::draw(display, &reading, A4).ok();
+        if display.flush().is_err() {
+            rprintln!("display flush failed");
+        }
+    }
+}
+
+fn reading(period: usize, ptr: usize, buffer: &Buffer) -> Reading {
+    let level = level(ptr, buffer);
+    let pitch = FS / period as f32;
+    let classified = Tuning::STANDARD.classify(pitch, A4);
+    Reading {
+        pitch,
+        cents: classified.map_or(0.0, |(_, c)| c),
+        string: classified.map(|(s, _)| s as u8),
+        confidence: 1.0,
+        level,
+        state: if level < NOISE_GATE {
+            State::Idle
+        } else {
+            State::Track
+        },
+    }
+}
+
+// RMS level in dBFS of the latest `LEVEL_WINDOW` samples
+fn level(ptr: usize, buffer: &Buffer) -> f32 {
+    let mut sum = 0.0;
+    for i in 0..LEVEL_WINDOW {
+        let s = buffer[(BUFFER_SIZE + ptr - i) % BUFFER_SIZE] as f32 / PROFILE.full_scale();
+        sum += s * s;
+    }
+    let rms = libm::sqrtf(sum / LEVEL_WINDOW as f32);
+    20.0 * libm::log10f(rms.max(1e-6))
+}
+
+fn estimate_error(period: usize, ptr: usize, buffer: &Buffer) -> (i32, i32, i32) {
+    let mut err_low = 0;
+    let mut err_mid = 0;
+    let mut err_high = 0;
+    for i in 0..period {
+        let curr = buffer[(BUFFER_SIZE + ptr - i) % BUFFER_SIZE] as i32;
+        let low = buffer[(BUFFER_SIZE + ptr - i - period - 1) % BUFFER_SIZE] as i32;
+        let mid = buffer[(BUFFER_SIZE + ptr - i - period) % BUFFER_SIZE] as i32;
+        let high = buffer[(BUFFER_SIZE + ptr - i - period + 1) % BUFFER_SIZE] as i32;
+
+        err_low += (curr - low).abs();
+        err_mid += (curr - mid).abs();
+        err_high += (curr - high).abs();
+    }
+    (err_low, err_mid, err_high)
+}
+
+fn get_sample(saadc: &mut SAADC) -> i16 {
+    let mut val: i16 = 0;
+    saadc
+        .result
+        .ptr
+        .write(|w| unsafe { w.ptr().bits(((&mut val) as *mut _) as u32) });
+    saadc.result.maxcnt.write(|w| unsafe { w.maxcnt().bits(1) });
+
+    // Conservative compiler fence to prevent starting the ADC before the
+    // pointer and maxcount have been set.
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    saadc.tasks_start.write(|w| unsafe { w.bits(1) });
+    saadc.tasks_sample.write(|w| unsafe { w.bits(1) });
+
+    while saadc.events_end.read().bits() == 0 {}
+
+    saadc.events_end.reset();
+
+    // Second fence to prevent optimizations creating issues with the EasyDMA-modified `val`.
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    val
+}
@@ -0,0 +1,315 @@
+// examples/rtic_midi.rs
+//
+// The `rtic_tuner` detector, with the device enumerating as a USB MIDI
+// device sending the detected notes and pitch bend to the host, turning the
+// tuner into a simple guitar-to-MIDI converter.
+
+#![no_main]
+#![no_std]
+
+use guitar_tuner::{
+    midi::Converter,
+    note::A4,
+    protocol::{Reading, State},
+    tuning::Tuning,
+};
+use hal::pac;
+use nrf52840_hal::{self as hal, pac::SAADC};
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+use tuner_app::saadc::{configure, PROFILE};
+
+const BUFFER_SIZE: usize = 1024; // approximately 1second of data at 1kHz
+type Buffer = [i16; BUFFER_SIZE];
+
+const FS: f32 = 1000.0; // sample rate
+const LEVEL_WINDOW: usize = 100; // samples used to compute the level
+const NOISE_GATE: f32 = -50.0; // dBFS
+const MIDI_CHANNEL: u8 = 0;
+
+#[rtic::app(device = pac, dispatchers = [UARTE1, UARTE0_UART0])]
+mod app {
+    use super::usb_midi::MidiClass;
+    use super::*;
+    use cortex_m::asm;
+
+    use fugit::ExtU32;
+    use hal::{
+        clocks::{Clocks, ExternalOscillator, Internal, LfOscStopped},
+        monotonic::MonotonicTimer,
+        usbd::{UsbPeripheral, Usbd},
+    };
+    use usb_device::{
+        bus::UsbBusAllocator,
+        device::{StringDescriptors, UsbDevice, UsbDeviceBuilder, UsbVidPid},
+    };
+
+    use pac::TIMER0;
+
+    const TIMER_HZ: u32 = 16_000_000; // 16 MHz
+
+    type UsbBus = Usbd<UsbPeripheral<'static>>;
+
+    #[monotonic(binds = TIMER0, default = true)]
+    type MyMono = MonotonicTimer<TIMER0, TIMER_HZ>;
+
+    #[shared]
+    struct Shared {
+        usb_dev: UsbDevice<'static, UsbBus>,
+        midi: MidiClass<'static, UsbBus>,
+    }
+
+    #[local]
+    struct Local {
+        saadc: SAADC,
+    }
+
+    #[init(local = [
+        clocks: Option<Clocks<ExternalOscillator, Internal, LfOscStopped>> = None,
+        usb_bus: Option<UsbBusAllocator<UsbBus>> = None,
+    ])]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        rtt_init_print!();
+        rprintln!("\n--- rtic midi ---\n");
+
+        let mono = MyMono::new(cx.device.TIMER0);
+        let saadc = cx.device.SAADC;
+        // ADC front-end selected at build time by `ADC_PROFILE`
+        configure(&saadc, &PROFILE);
+        rprintln!("adc {}", PROFILE);
+        rprintln!("calibrated");
+
+        // USB requires the external high frequency oscillator
+        let clocks = cx
+            .local
+            .clocks
+            .insert(Clocks::new(cx.device.CLOCK).enable_ext_hfosc());
+        let usb_bus = cx
+            .local
+            .usb_bus
+            .insert(Usbd::new(UsbPeripheral::new(cx.device.USBD, clocks)));
+        let midi = MidiClass::new(usb_bus);
+        // shared V-USB MIDI VID/PID
+        let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x05e4))
+            .strings(&[StringDescriptors::default()
+                .manufacturer("LTU")
+                .product("Guitar Tuner MIDI")
+                .serial_number("0001")])
+            .unwrap()
+            .build();
+
+        sample::spawn(monotonics::now()).unwrap();
+        (
+            Shared { usb_dev, midi },
+            Local { saadc },
+            init::Monotonics(mono),
+        )
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        loop {
+            // Put core to sleep until next interrupt
+            asm::wfe();
+        }
+    }
+
+    #[task(binds = USBD, priority = 2, shared = [usb_dev, midi])]
+    fn usb(cx: usb::Context) {
+        (cx.shared.usb_dev, cx.shared.midi).lock(|usb_dev, midi| {
+            usb_dev.poll(&mut [midi]);
+        });
+    }
+
+    // Drift free periodic task at highest priority
+    #[task(priority = 3, local = [saadc])]
+    fn sample(cx: sample::Context, instant: fugit::TimerInstantU32<TIMER_HZ>) {
+        let s = get_sample(cx.local.saadc);
+        process::spawn(s).unwrap();
+
+        // Spawn a new message with 1ms offset to spawned time
+        let next_instant = instant + 1.millis();
+        sample::spawn_at(next_instant, next_instant).unwrap();
+    }
+
+    #[task(priority = 2, shared = [midi], local = [cnt: usize = 0, period: usize = 3, ptr: usize = 0, buffer: Buffer
+    = [0; BUFFER_SIZE], converter: Converter = Converter::new(MIDI_CHANNEL)], capacity = 2)]
+    fn process(mut cx: process::Context, sample: i16) {
+        let process::LocalResources {
+            cnt,
+            period,
+            ptr,
+            buffer,
+            converter,
+        } = cx.local;
+        *ptr = (*ptr + 1) % buffer.len();
+        buffer[*ptr] = sample;
+
+        if *cnt == 0 {
+            let (err_low, err_mid, err_high) = estimate_error(*period, *ptr, buffer);
+            if err_mid > err_low.min(err_high) {
+                if err_low < err_high {
+                    *period -= 1;
+                } else {
+                    *period += 1;
+                }
+            }
+            *period = (*period).clamp(2, 13); // set new period
+            *cnt = *period * 5; // set counter
+
+            // At most three messages per reading, sent as a single transfer
+            let mut packets = [0u8; 12];
+            let mut len = 0;
+            converter.update(&reading(*period, *ptr, buffer), |m| {
+                packets[len..len + 4].copy_from_slice(&m.to_usb(0));
+                len += 4;
+            });
+            if len > 0 {
+                cx.shared.midi.lock(|midi| {
+                    if midi.send(&packets[..len]).is_err() {
+                        rprintln!("midi dropped, host not listening");
+                    }
+                });
+            }
+        }
+        *cnt -= 1;
+    }
+}
+
+// Minimal USB MIDI 1.0 class, one embedded OUT jack streaming to the host
+mod usb_midi {
+    use usb_device::{class_prelude::*, Result};
+
+    const AUDIO: u8 = 0x01;
+    const AUDIO_CONTROL: u8 = 0x01;
+    const MIDI_STREAMING: u8 = 0x03;
+    const CS_INTERFACE: u8 = 0x24;
+    const CS_ENDPOINT: u8 = 0x25;
+    const EXTERNAL: u8 = 0x02;
+    const EMBEDDED: u8 = 0x01;
+    const IN_JACK_ID: u8 = 0x01;
+    const OUT_JACK_ID: u8 = 0x02;
+
+    // MS header (7) + IN jack (6) + OUT jack (9) + endpoint (9) + CS endpoint (5)
+    const MS_TOTAL_LENGTH: u16 = 36;
+
+    pub struct MidiClass<'a, B: UsbBus> {
+        audio_if: InterfaceNumber,
+        midi_if: InterfaceNumber,
+        ep_in: EndpointIn<'a, B>,
+    }
+
+    impl<'a, B: UsbBus> MidiClass<'a, B> {
+        pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+            MidiClass {
+                audio_if: alloc.interface(),
+                midi_if: alloc.interface(),
+                ep_in: alloc.bulk(64),
+            }
+        }
+
+        /// Sends USB-MIDI event packets (4 bytes each), fails with
+        /// `WouldBlock` if the host has not yet picked up the previous ones.
+        pub fn send(&mut self, packets: &[u8]) -> Result<usize> {
+            self.ep_in.write(packets)
+        }
+    }
+
+    impl<B: UsbBus> UsbClass<B> for MidiClass<'_, B> {
+        fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> Result<()> {
+            writer.interface(self.audio_if, AUDIO, AUDIO_CONTROL, 0)?;
+            // CS audio control header, bcdADC 1.0, one streaming interface
+            writer.write(
+                CS_INTERFACE,
+                &[0x01, 0x00, 0x01, 0x09, 0x00, 0x01, self.midi_if.into()],
+            )?;
+
+            writer.interface(self.midi_if, AUDIO, MIDI_STREAMING, 0)?;
+            let [lo, hi] = MS_TOTAL_LENGTH.to_le_bytes();
+            writer.write(CS_INTERFACE, &[0x01, 0x00, 0x01, lo, hi])?;
+            // external IN jack (the guitar) feeding the embedded OUT jack
+            writer.write(CS_INTERFACE, &[0x02, EXTERNAL, IN_JACK_ID, 0x00])?;
+            writer.write(
+                CS_INTERFACE,
+                &[0x03, EMBEDDED, OUT_JACK_ID, 0x01, IN_JACK_ID, 0x01, 0x00],
+            )?;
+            // audio class endpoints carry bRefresh and bSynchAddress
+            writer.endpoint_ex(&self.ep_in, |buf| {
+                buf[..2].copy_from_slice(&[0x00, 0x00]);
+                Ok(2)
+            })?;
+            writer.write(CS_ENDPOINT, &[0x01, 0x01, OUT_JACK_ID])
+        }
+    }
+}
+
+fn reading(period: usize, ptr: usize, buffer: &Buffer) -> Reading {
+    let level = level(ptr, buffer);
+    let pitch = FS / period as f32;
+    let classified = Tuning::STANDARD.classify(pitch, A4);
+    Reading {
+        pitch,
+        cents: classified.map_or(0.0, |(_, c)| c),
+        string: classified.map(|(s, _)| s as u8),
+        confidence: 1.0,
+        level,
+        state: if level < NOISE_GATE {
+            State::Idle
+        } else {
+            State::Track
+        },
+    }
+}
+
+// RMS level in dBFS of the latest `LEVEL_WINDOW` samples
+fn level(ptr: usize, buffer: &Buffer) -> f32 {
+    let mut sum = 0.0;
+    for i in 0..LEVEL_WINDOW {
+        let s = buffer[(BUFFER_SIZE + ptr - i) % BUFFER_SIZE] as f32 / PROFILE.full_scale();
+        sum += s * s;
+    }
+    let rms = libm::sqrtf(sum / LEVEL_WINDOW as f32);
+    20.0 * libm::log10f(rms.max(1e-6))
+}
+
+fn estimate_error(period: usize, ptr: usize, buffer: &Buffer) -> (i32, i32, i32) {
+    let mut err_low = 0;
+    let mut err_mid = 0;
+    let mut err_high = 0;
+    for i in 0..period {
+        let curr = buffer[(BUFFER_SIZE + ptr - i) % BUFFER_SIZE] as i32;
+        let low = buffer[(BUFFER_SIZE + ptr - i - period - 1) % BUFFER_SIZE] as i32;
+        let mid = buffer[(BUFFER_SIZE + ptr - i - period) % BUFFER_SIZE] as i32;
+        let high = buffer[(BUFFER_SIZE + ptr - i - period + 1) % BUFFER_SIZE] as i32;
+
+        err_low += (curr - low).abs();
+        err_mid += (curr - mid).abs();
+        err_high += (curr - high).abs();
+    }
+    (err_low, err_mid, err_high)
+}
+
+fn get_sample(saadc: &mut SAADC) -> i16 {
+    let mut val: i16 = 0;
+    saadc
+        .result
+        .ptr
+        .write(|w| unsafe { w.ptr().bits(((&mut val) as *mut _) as u32) });
+    saadc.result.maxcnt.write(|w| unsafe { w.maxcnt().bits(1) });
+
+    // Conservative compiler fence to prevent starting the ADC before the
+    // pointer and maxcount have been set.
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    saadc.tasks_start.write(|w| unsafe { w.bits(1) });
+    saadc.tasks_sample.write(|w| unsafe { w.bits(1) });
+
+    while saadc.events_end.read().bits() == 0 {}
+
+    saadc.events_end.reset();
+
+    // Second fence to prevent optimizations creating issues with the EasyDMA-modified `val`.
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    val
+}
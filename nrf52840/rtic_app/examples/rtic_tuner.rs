@@ -7,6 +7,7 @@ use hal::pac;
 use nrf52840_hal::{self as hal, pac::SAADC};
 use panic_rtt_target as _;
 use rtt_target::{rprintln, rtt_init_print};
+use tuner_app::saadc::{configure, PROFILE};
 
 const BUFFER_SIZE: usize = 1024; // approximately 1second of data at 1kHz
 type Buffer = [i16; BUFFER_SIZE];
@@ -19,10 +20,7 @@ mod app {
     use fugit::ExtU32;
     use hal::monotonic::MonotonicTimer;
 
-    use pac::{
-        saadc::{ch::config::*, oversample::OVERSAMPLE_A, resolution::VAL_A},
-        TIMER0,
-    };
+    use pac::TIMER0;
 
     const TIMER_HZ: u32 = 16_000_000; // 16 MHz
 
@@ -44,30 +42,9 @@ mod app {
 
         let mono = MyMono::new(cx.device.TIMER0);
         let saadc = cx.device.SAADC;
-        saadc.enable.write(|w| w.enable().enabled());
-        saadc.resolution.write(|w| w.val().variant(VAL_A::_14BIT));
-        saadc
-            .oversample
-            .write(|w| w.oversample().variant(OVERSAMPLE_A::BYPASS));
-        saadc.samplerate.write(|w| w.mode().task());
-
-        saadc.ch[0].config.write(|w| {
-            w.refsel().variant(REFSEL_A::INTERNAL);
-            w.gain().variant(GAIN_A::GAIN4);
-            w.tacq().variant(TACQ_A::_20US);
-            w.mode().variant(MODE_A::DIFF);
-            w.resp().variant(RESP_A::BYPASS);
-            w.resn().variant(RESN_A::BYPASS);
-            w.burst().disabled();
-            w
-        });
-        saadc.ch[0].pselp.write(|w| w.pselp().analog_input1());
-        saadc.ch[0].pseln.write(|w| w.pseln().analog_input2());
-
-        // Calibrate
-        saadc.events_calibratedone.reset();
-        saadc.tasks_calibrateoffset.write(|w| unsafe { w.bits(1) });
-        while saadc.events_calibratedone.read().bits() == 0 {}
+        // ADC front-end selected at build time by `ADC_PROFILE`
+        configure(&saadc, &PROFILE);
+        rprintln!("adc {}", PROFILE);
 
         rprintln!("calibrated");
         sample::spawn(monotonics::now()).unwrap();
@@ -0,0 +1,5 @@
+// Code shared by the examples
+
+#![no_std]
+
+pub mod saadc;
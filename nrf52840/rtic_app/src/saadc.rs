@@ -0,0 +1,137 @@
+// saadc, applies an `AdcProfile` to the SAADC
+
+use guitar_tuner::adc::{
+    Acquisition, AdcProfile, Gain, Input, Oversample, Pin, Reference, Resolution,
+};
+use nrf52840_hal::pac::{
+    saadc::{
+        ch::{config::*, pseln::PSELN_A, pselp::PSELP_A},
+        oversample::OVERSAMPLE_A,
+        resolution::VAL_A,
+    },
+    SAADC,
+};
+
+/// Profile selected at build time, e.g.:
+///
+/// ADC_PROFILE=low-gain cargo run --example rtic_tuner
+///
+/// The `default` profile is used if `ADC_PROFILE` is not set.
+pub const PROFILE: AdcProfile = match option_env!("ADC_PROFILE") {
+    Some(name) => match AdcProfile::named(name) {
+        Some(profile) => profile,
+        None => panic!("unknown ADC_PROFILE"),
+    },
+    None => AdcProfile::DEFAULT,
+};
+
+/// Configures channel 0 according to `profile` and calibrates the offset.
+/// Sampling is triggered by the `SAMPLE` task.
+pub fn configure(saadc: &SAADC, profile: &AdcProfile) {
+    saadc.enable.write(|w| w.enable().enabled());
+    saadc.resolution.write(|w| {
+        w.val().variant(match profile.resolution {
+            Resolution::Bits8 => VAL_A::_8BIT,
+            Resolution::Bits10 => VAL_A::_10BIT,
+            Resolution::Bits12 => VAL_A::_12BIT,
+            Resolution::Bits14 => VAL_A::_14BIT,
+        })
+    });
+    saadc.oversample.write(|w| {
+        w.oversample().variant(match profile.oversample {
+            Oversample::Bypass => OVERSAMPLE_A::BYPASS,
+            Oversample::Over2x => OVERSAMPLE_A::OVER2X,
+            Oversample::Over4x => OVERSAMPLE_A::OVER4X,
+            Oversample::Over8x => OVERSAMPLE_A::OVER8X,
+            Oversample::Over16x => OVERSAMPLE_A::OVER16X,
+            Oversample::Over32x => OVERSAMPLE_A::OVER32X,
+            Oversample::Over64x => OVERSAMPLE_A::OVER64X,
+            Oversample::Over128x => OVERSAMPLE_A::OVER128X,
+            Oversample::Over256x => OVERSAMPLE_A::OVER256X,
+        })
+    });
+    saadc.samplerate.write(|w| w.mode().task());
+
+    let (pos, neg) = match profile.input {
+        Input::Single(pos) => (pos, None),
+        Input::Differential(pos, neg) => (pos, Some(neg)),
+    };
+
+    saadc.ch[0].config.write(|w| {
+        w.refsel().variant(match profile.reference {
+            Reference::Internal => REFSEL_A::INTERNAL,
+            Reference::Vdd1_4 => REFSEL_A::VDD1_4,
+        });
+        w.gain().variant(match profile.gain {
+            Gain::Gain1_6 => GAIN_A::GAIN1_6,
+            Gain::Gain1_5 => GAIN_A::GAIN1_5,
+            Gain::Gain1_4 => GAIN_A::GAIN1_4,
+            Gain::Gain1_3 => GAIN_A::GAIN1_3,
+            Gain::Gain1_2 => GAIN_A::GAIN1_2,
+            Gain::Gain1 => GAIN_A::GAIN1,
+            Gain::Gain2 => GAIN_A::GAIN2,
+            Gain::Gain4 => GAIN_A::GAIN4,
+        });
+        w.tacq().variant(match profile.acquisition {
+            Acquisition::Us3 => TACQ_A::_3US,
+            Acquisition::Us5 => TACQ_A::_5US,
+            Acquisition::Us10 => TACQ_A::_10US,
+            Acquisition::Us15 => TACQ_A::_15US,
+            Acquisition::Us20 => TACQ_A::_20US,
+            Acquisition::Us40 => TACQ_A::_40US,
+        });
+        w.mode().variant(match neg {
+            Some(_) => MODE_A::DIFF,
+            None => MODE_A::SE,
+        });
+        w.resp().variant(RESP_A::BYPASS);
+        w.resn().variant(RESN_A::BYPASS);
+        // with burst, a single SAMPLE task produces an oversampled result
+        if profile.oversample == Oversample::Bypass {
+            w.burst().disabled();
+        } else {
+            w.burst().enabled();
+        }
+        w
+    });
+    saadc.ch[0].pselp.write(|w| w.pselp().variant(pselp(pos)));
+    saadc.ch[0].pseln.write(|w| {
+        w.pseln().variant(match neg {
+            Some(neg) => pseln(neg),
+            None => PSELN_A::NC,
+        })
+    });
+
+    // Calibrate
+    saadc.events_calibratedone.reset();
+    saadc.tasks_calibrateoffset.write(|w| unsafe { w.bits(1) });
+    while saadc.events_calibratedone.read().bits() == 0 {}
+}
+
+fn pselp(pin: Pin) -> PSELP_A {
+    match pin {
+        Pin::Ain0 => PSELP_A::ANALOG_INPUT0,
+        Pin::Ain1 => PSELP_A::ANALOG_INPUT1,
+        Pin::Ain2 => PSELP_A::ANALOG_INPUT2,
+        Pin::Ain3 => PSELP_A::ANALOG_INPUT3,
+        Pin::Ain4 => PSELP_A::ANALOG_INPUT4,
+        Pin::Ain5 => PSELP_A::ANALOG_INPUT5,
+        Pin::Ain6 => PSELP_A::ANALOG_INPUT6,
+        Pin::Ain7 => PSELP_A::ANALOG_INPUT7,
+        Pin::Vdd => PSELP_A::VDD,
+    }
+}
+
+fn pseln(pin: Pin) -> PSELN_A {
+    match pin {
+        Pin::Ain0 => PSELN_A::ANALOG_INPUT0,
+        Pin::Ain1 => PSELN_A::ANALOG_INPUT1,
+        Pin::Ain2 => PSELN_A::ANALOG_INPUT2,
+        Pin::Ain3 => PSELN_A::ANALOG_INPUT3,
+        Pin::Ain4 => PSELN_A::ANALOG_INPUT4,
+        Pin::Ain5 => PSELN_A::ANALOG_INPUT5,
+        Pin::Ain6 => PSELN_A::ANALOG_INPUT6,
+        Pin::Ain7 => PSELN_A::ANALOG_INPUT7,
+        Pin::Vdd => PSELN_A::VDD,
+    }
+}
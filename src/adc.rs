@@ -0,0 +1,380 @@
+// adc, nRF52 SAADC front-end profiles
+//
+// The SAADC settings (gain, resolution, oversampling, reference, acquisition
+// time and input pins) as plain data, so a profile can be chosen at build
+// time in the firmware, sent over the serial protocol (see `protocol`) and
+// recorded with captures on the host. Writing a profile to the peripheral is
+// done by the firmware (`nrf52840/rtic_app/src/saadc.rs`).
+
+use core::fmt;
+
+use crate::protocol::Error;
+
+/// Supply voltage assumed for the `Vdd1_4` reference.
+pub const VDD: f32 = 3.0;
+
+// Enums with a fixed on-wire representation.
+macro_rules! wire_enum {
+    ($(#[$m:meta])* $name:ident { $($(#[$vm:meta])* $v:ident = $n:literal,)* }) => {
+        $(#[$m])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(u8)]
+        pub enum $name {
+            $($(#[$vm])* $v = $n,)*
+        }
+
+        impl TryFrom<u8> for $name {
+            type Error = Error;
+
+            fn try_from(v: u8) -> Result<Self, Error> {
+                match v {
+                    $($n => Ok($name::$v),)*
+                    _ => Err(Error::BadValue),
+                }
+            }
+        }
+    };
+}
+
+wire_enum!(
+    /// Channel gain.
+    Gain {
+        Gain1_6 = 0,
+        Gain1_5 = 1,
+        Gain1_4 = 2,
+        Gain1_3 = 3,
+        Gain1_2 = 4,
+        Gain1 = 5,
+        Gain2 = 6,
+        Gain4 = 7,
+    }
+);
+
+wire_enum!(
+    Resolution {
+        Bits8 = 0,
+        Bits10 = 1,
+        Bits12 = 2,
+        Bits14 = 3,
+    }
+);
+
+wire_enum!(
+    /// Number of samples averaged per result, 2^n.
+    Oversample {
+        Bypass = 0,
+        Over2x = 1,
+        Over4x = 2,
+        Over8x = 3,
+        Over16x = 4,
+        Over32x = 5,
+        Over64x = 6,
+        Over128x = 7,
+        Over256x = 8,
+    }
+);
+
+wire_enum!(
+    Reference {
+        /// Internal 0.6 V reference.
+        Internal = 0,
+        /// VDD/4.
+        Vdd1_4 = 1,
+    }
+);
+
+wire_enum!(
+    /// Acquisition time.
+    Acquisition {
+        Us3 = 0,
+        Us5 = 1,
+        Us10 = 2,
+        Us15 = 3,
+        Us20 = 4,
+        Us40 = 5,
+    }
+);
+
+wire_enum!(
+    /// Analog input.
+    Pin {
+        Ain0 = 0,
+        Ain1 = 1,
+        Ain2 = 2,
+        Ain3 = 3,
+        Ain4 = 4,
+        Ain5 = 5,
+        Ain6 = 6,
+        Ain7 = 7,
+        Vdd = 8,
+    }
+);
+
+impl Gain {
+    pub fn factor(self) -> f32 {
+        match self {
+            Gain::Gain1_6 => 1.0 / 6.0,
+            Gain::Gain1_5 => 1.0 / 5.0,
+            Gain::Gain1_4 => 1.0 / 4.0,
+            Gain::Gain1_3 => 1.0 / 3.0,
+            Gain::Gain1_2 => 1.0 / 2.0,
+            Gain::Gain1 => 1.0,
+            Gain::Gain2 => 2.0,
+            Gain::Gain4 => 4.0,
+        }
+    }
+
+    /// The gain closest to `factor`.
+    pub fn from_factor(factor: f32) -> Gain {
+        let all = [
+            Gain::Gain1_6,
+            Gain::Gain1_5,
+            Gain::Gain1_4,
+            Gain::Gain1_3,
+            Gain::Gain1_2,
+            Gain::Gain1,
+            Gain::Gain2,
+            Gain::Gain4,
+        ];
+        let mut best = Gain::Gain1;
+        for g in all {
+            if libm::fabsf(g.factor() - factor) < libm::fabsf(best.factor() - factor) {
+                best = g;
+            }
+        }
+        best
+    }
+}
+
+impl fmt::Display for Gain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gain::Gain1_6 => write!(f, "1/6"),
+            Gain::Gain1_5 => write!(f, "1/5"),
+            Gain::Gain1_4 => write!(f, "1/4"),
+            Gain::Gain1_3 => write!(f, "1/3"),
+            Gain::Gain1_2 => write!(f, "1/2"),
+            Gain::Gain1 => write!(f, "1"),
+            Gain::Gain2 => write!(f, "2"),
+            Gain::Gain4 => write!(f, "4"),
+        }
+    }
+}
+
+impl Resolution {
+    pub fn bits(self) -> u8 {
+        8 + 2 * self as u8
+    }
+
+    pub fn from_bits(bits: u8) -> Option<Resolution> {
+        match bits {
+            8 => Some(Resolution::Bits8),
+            10 => Some(Resolution::Bits10),
+            12 => Some(Resolution::Bits12),
+            14 => Some(Resolution::Bits14),
+            _ => None,
+        }
+    }
+}
+
+impl Oversample {
+    /// Number of samples averaged per result.
+    pub fn samples(self) -> u16 {
+        1 << self as u8
+    }
+
+    pub fn from_samples(samples: u16) -> Option<Oversample> {
+        if !samples.is_power_of_two() || samples > 256 {
+            return None;
+        }
+        Oversample::try_from(samples.trailing_zeros() as u8).ok()
+    }
+}
+
+impl Reference {
+    pub fn volts(self) -> f32 {
+        match self {
+            Reference::Internal => 0.6,
+            Reference::Vdd1_4 => VDD / 4.0,
+        }
+    }
+}
+
+impl Acquisition {
+    pub fn micros(self) -> u8 {
+        match self {
+            Acquisition::Us3 => 3,
+            Acquisition::Us5 => 5,
+            Acquisition::Us10 => 10,
+            Acquisition::Us15 => 15,
+            Acquisition::Us20 => 20,
+            Acquisition::Us40 => 40,
+        }
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pin::Vdd => write!(f, "VDD"),
+            p => write!(f, "AIN{}", *p as u8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// Single ended, measured against ground.
+    Single(Pin),
+    /// Differential, positive and negative input.
+    Differential(Pin, Pin),
+}
+
+/// Configuration of the SAADC channel sampling the guitar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdcProfile {
+    pub gain: Gain,
+    pub resolution: Resolution,
+    pub oversample: Oversample,
+    pub reference: Reference,
+    pub acquisition: Acquisition,
+    pub input: Input,
+}
+
+impl Default for AdcProfile {
+    fn default() -> Self {
+        AdcProfile::DEFAULT
+    }
+}
+
+impl AdcProfile {
+    /// The original `rtic_tuner` setup, differential AIN1/AIN2 with gain 4.
+    pub const DEFAULT: AdcProfile = AdcProfile {
+        gain: Gain::Gain4,
+        resolution: Resolution::Bits14,
+        oversample: Oversample::Bypass,
+        reference: Reference::Internal,
+        acquisition: Acquisition::Us20,
+        input: Input::Differential(Pin::Ain1, Pin::Ain2),
+    };
+
+    /// Named profiles, selectable at build time (`ADC_PROFILE`) and from the
+    /// host.
+    pub const NAMED: [(&'static str, AdcProfile); 4] = [
+        ("default", AdcProfile::DEFAULT),
+        // hot pickups and line level sources
+        (
+            "low-gain",
+            AdcProfile {
+                gain: Gain::Gain1,
+                ..AdcProfile::DEFAULT
+            },
+        ),
+        // averaging 4 samples per result, lower noise floor
+        (
+            "oversampled",
+            AdcProfile {
+                oversample: Oversample::Over4x,
+                acquisition: Acquisition::Us10,
+                ..AdcProfile::DEFAULT
+            },
+        ),
+        // input biased at VDD/2, full range 0..VDD
+        (
+            "single-ended",
+            AdcProfile {
+                gain: Gain::Gain1_4,
+                reference: Reference::Vdd1_4,
+                input: Input::Single(Pin::Ain1),
+                ..AdcProfile::DEFAULT
+            },
+        ),
+    ];
+
+    /// The named profile `name`, usable in const context.
+    pub const fn named(name: &str) -> Option<AdcProfile> {
+        let mut i = 0;
+        while i < Self::NAMED.len() {
+            if str_eq(Self::NAMED[i].0, name) {
+                return Some(Self::NAMED[i].1);
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Name of the profile if it matches a named one.
+    pub fn name(&self) -> Option<&'static str> {
+        Self::NAMED
+            .iter()
+            .find(|(_, p)| p == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Result (code) corresponding to the positive end of the input range.
+    pub fn full_scale(&self) -> f32 {
+        let bits = match self.input {
+            Input::Single(_) => self.resolution.bits(),
+            Input::Differential(..) => self.resolution.bits() - 1,
+        };
+        (1u32 << bits) as f32
+    }
+
+    /// Input voltage for a result `sample`.
+    pub fn volts(&self, sample: i16) -> f32 {
+        sample as f32 * self.reference.volts() / (self.gain.factor() * self.full_scale())
+    }
+}
+
+impl fmt::Display for AdcProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gain {}, {} bit, oversample {}x, reference {:.2} V, acquisition {} us, ",
+            self.gain,
+            self.resolution.bits(),
+            self.oversample.samples(),
+            self.reference.volts(),
+            self.acquisition.micros()
+        )?;
+        match self.input {
+            Input::Single(p) => write!(f, "single ended {}", p),
+            Input::Differential(p, n) => write!(f, "differential {}-{}", p, n),
+        }
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+#[test]
+fn profile_test() {
+    const LOW: Option<AdcProfile> = AdcProfile::named("low-gain");
+    assert_eq!(LOW.unwrap().gain, Gain::Gain1);
+    assert_eq!(LOW.unwrap().name(), Some("low-gain"));
+    assert_eq!(AdcProfile::named("none"), None);
+
+    let p = AdcProfile::DEFAULT;
+    assert_eq!(p.resolution.bits(), 14);
+    assert_eq!(p.full_scale(), 8192.0);
+    // 0.6 V / 4 at full scale
+    assert!((p.volts(8192) - 0.15).abs() < 1e-6);
+
+    assert_eq!(Gain::from_factor(3.0), Gain::Gain2);
+    assert_eq!(Gain::from_factor(0.2), Gain::Gain1_5);
+    assert_eq!(Oversample::from_samples(16), Some(Oversample::Over16x));
+    assert_eq!(Oversample::from_samples(3), None);
+    assert_eq!(Pin::try_from(9), Err(Error::BadValue));
+}
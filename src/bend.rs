@@ -0,0 +1,348 @@
+// bend, analysis of bends and vibrato in a pitch contour
+//
+// The contour is the pitch tracked at a fixed frame rate (e.g., by the
+// `track::Tracker` every few ms), kept in cents. After the attack the pitch
+// settles, taken as the first stretch of `hold` seconds staying within
+// `tolerance` cents of the settled pitch. The bend is the largest deviation
+// from the settled pitch afterwards.
+//
+// Vibrato is found in the contour with the slow movements (bends) removed
+// by subtracting a moving average one vibrato period long, its rate by the
+// autocorrelation and its extent by the RMS (of a sine).
+
+/// Deviations of at least this periodicity (normalized autocorrelation)
+/// are vibrato.
+const PERIODICITY: f32 = 0.5;
+
+/// Smallest vibrato extent in cents, below it is the jitter of the tracker.
+const MIN_EXTENT: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vibrato {
+    /// Rate in Hz.
+    pub rate: f32,
+    /// Extent in cents, up and down from the center.
+    pub extent: f32,
+    /// Periodicity of the deviation, 0.0..=1.0.
+    pub regularity: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    /// Time from the first pitched frame until settled in seconds, `None`
+    /// if the pitch never settles.
+    pub settling: Option<f32>,
+    /// Settled pitch in Hz (the first pitch if not settled).
+    pub pitch: f32,
+    /// Largest deviation from the settled pitch in cents.
+    pub bend: f32,
+    /// Time of the largest deviation from the first pitched frame in
+    /// seconds.
+    pub bend_time: f32,
+    pub vibrato: Option<Vibrato>,
+}
+
+/// Pitch contour of up to `N` frames at `rate` frames per second.
+pub struct Contour<const N: usize> {
+    pub rate: f32,
+    /// Largest deviation (cents) within `hold` seconds considered settled.
+    pub tolerance: f32,
+    pub hold: f32,
+    /// Vibrato rate range in Hz.
+    pub min_rate: f32,
+    pub max_rate: f32,
+    // pitch in cents above 1 Hz, NaN if unpitched
+    cents: [f32; N],
+    scratch: [f32; N],
+    len: usize,
+}
+
+impl<const N: usize> Contour<N> {
+    pub const fn new(rate: f32) -> Self {
+        Contour {
+            rate,
+            tolerance: 5.0,
+            hold: 0.1,
+            min_rate: 3.0,
+            max_rate: 12.0,
+            cents: [f32::NAN; N],
+            scratch: [0.0; N],
+            len: 0,
+        }
+    }
+
+    /// Adds the pitch (in Hz) of the next frame, returns false when full.
+    pub fn push(&mut self, pitch: Option<f32>) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.cents[self.len] = match pitch {
+            Some(f) if f > 0.0 => 1200.0 * libm::log2f(f),
+            _ => f32::NAN,
+        };
+        self.len += 1;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Pitch of frame `i` in Hz, `None` if unpitched.
+    pub fn pitch(&self, i: usize) -> Option<f32> {
+        let c = *self.cents[..self.len].get(i)?;
+        (!c.is_nan()).then(|| libm::exp2f(c / 1200.0))
+    }
+
+    /// Analyzes the contour from the first pitched frame on, `None` if no
+    /// frame is pitched.
+    pub fn analyze(&mut self) -> Option<Report> {
+        let first = self.cents[..self.len].iter().position(|c| !c.is_nan())?;
+        let cents = &self.cents[first..self.len];
+        let hold = ((self.hold * self.rate) as usize).max(1);
+
+        // the first window of `hold` frames within the tolerance, extended as
+        // long as it stays within, the pitch being its median (the attack
+        // may still be decaying at the start and a bend starting at the end)
+        // (half of the frames pitched, not the silence after the note)
+        let within = |w: &[f32]| {
+            let pitched = w.iter().filter(|c| !c.is_nan());
+            if pitched.clone().count() < hold.div_ceil(2) {
+                return false;
+            }
+            let (low, high) = pitched.fold((f32::INFINITY, f32::NEG_INFINITY), |(l, h), c| {
+                (l.min(*c), h.max(*c))
+            });
+            high - low <= 2.0 * self.tolerance
+        };
+        let center = cents.windows(hold).position(within).map(|s| {
+            let end = (s + hold..cents.len())
+                .take_while(|e| within(&cents[s..=*e]))
+                .last()
+                .map_or(s + hold, |e| e + 1);
+            let mut n = 0;
+            for c in cents[s..end].iter().filter(|c| !c.is_nan()) {
+                self.scratch[n] = *c;
+                n += 1;
+            }
+            let sorted = &mut self.scratch[..n];
+            sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+            (end - hold, sorted[n / 2])
+        });
+
+        // settled from the first window of `hold` frames within the
+        // tolerance of the pitch
+        let settled = center.map(|(last, center)| {
+            cents
+                .windows(hold)
+                .position(|w| {
+                    w.iter()
+                        .all(|c| c.is_nan() || (c - center).abs() <= self.tolerance)
+                })
+                .unwrap_or(last)
+        });
+        let (start, center) = match (settled, center) {
+            (Some(s), Some((_, center))) => (s, center),
+            _ => (0, cents[0]),
+        };
+
+        // the largest deviation once settled
+        let (bend_at, bend) = cents[start..]
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.is_nan())
+            .map(|(i, c)| (start + i, c - center))
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+            .unwrap_or((start, 0.0));
+
+        let vibrato = self.vibrato(first + start);
+        Some(Report {
+            settling: settled.map(|s| s as f32 / self.rate),
+            pitch: libm::exp2f(center / 1200.0),
+            bend,
+            bend_time: bend_at as f32 / self.rate,
+            vibrato,
+        })
+    }
+
+    // Vibrato in the frames from `start` on, gaps filled with the previous
+    // pitch. Bends would hide it, so it is searched for in segments of two
+    // slowest periods and the most regular one reported.
+    fn vibrato(&mut self, start: usize) -> Option<Vibrato> {
+        let n = self.len - start;
+        let period = libm::roundf(self.rate / self.min_rate) as usize;
+        let low = (libm::floorf(self.rate / self.max_rate) as usize).max(1);
+        let len = 2 * period + 1;
+        if n < len {
+            return None;
+        }
+
+        // relative to the first frame, keeping the sums small
+        let first = self.cents[start];
+        let mut last = 0.0;
+        for (x, c) in self.scratch[..n]
+            .iter_mut()
+            .zip(&self.cents[start..self.len])
+        {
+            if !c.is_nan() {
+                last = c - first;
+            }
+            *x = last;
+        }
+        let x = &self.scratch[..n];
+
+        // segments overlapping by half
+        (0..=n - len)
+            .step_by(period)
+            .chain(core::iter::once(n - len))
+            .filter_map(|s| segment(&x[s..s + len], low, period, self.rate))
+            .max_by(|a, b| a.regularity.total_cmp(&b.regularity))
+    }
+}
+
+// Vibrato in `x`, periods of `low..=high` frames.
+fn segment(x: &[f32], low: usize, high: usize, rate: f32) -> Option<Vibrato> {
+    let energy: f32 = detrended(x, high / 2).map(|d| d * d).sum();
+    if energy <= 0.0 {
+        return None;
+    }
+    let r = |lag: usize| {
+        detrended(x, high / 2)
+            .zip(detrended(x, high / 2).skip(lag))
+            .map(|(a, b)| a * b)
+            .sum::<f32>()
+            / energy
+    };
+    let (lag, regularity) = (low..=high)
+        .map(|lag| (lag, r(lag)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    if regularity < PERIODICITY || lag == low || lag == high {
+        return None;
+    }
+
+    // parabolic interpolation of the lag
+    let (a, b, c) = (r(lag - 1), regularity, r(lag + 1));
+    let d = a - 2.0 * b + c;
+    let offset = if d < 0.0 {
+        (0.5 * (a - c) / d).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    // the RMS of a sine is its amplitude over sqrt(2)
+    let extent = libm::sqrtf(2.0 * energy / x.len() as f32);
+    if extent < MIN_EXTENT {
+        return None;
+    }
+    Some(Vibrato {
+        rate: rate / (lag as f32 + offset),
+        extent,
+        regularity,
+    })
+}
+
+// `x` minus its centered moving average over `half` frames either side
+// (fewer at the ends).
+fn detrended(x: &[f32], half: usize) -> impl Iterator<Item = f32> + '_ {
+    let mut sum: f32 = x[..half.min(x.len())].iter().sum();
+    let mut count = half.min(x.len());
+    (0..x.len()).map(move |i| {
+        if i + half < x.len() {
+            sum += x[i + half];
+            count += 1;
+        }
+        if i > half {
+            sum -= x[i - half - 1];
+            count -= 1;
+        }
+        x[i] - sum / count as f32
+    })
+}
+
+#[cfg(test)]
+const RATE: f32 = 500.0;
+
+// A pluck settling from 30 cents sharp (in ~0.1 s), bent up a whole tone from
+// 0.5 s to 0.7 s and released at 1.2 s, with `vibrato` (rate, extent) from
+// 1.5 s.
+#[cfg(test)]
+fn contour(vibrato: (f32, f32)) -> Contour<2048> {
+    let mut c = Contour::new(RATE);
+    for i in 0..2000 {
+        let t = i as f32 / RATE;
+        let mut cents = 30.0 * libm::expf(-t / 0.03);
+        cents += 200.0 * ((t - 0.5) / 0.2).clamp(0.0, 1.0);
+        if t > 1.2 {
+            cents = 0.0;
+        }
+        if t >= 1.5 {
+            cents += vibrato.1 * libm::sinf(2.0 * core::f32::consts::PI * vibrato.0 * (t - 1.5));
+        }
+        // unpitched during the attack
+        let pitch = 196.0 * libm::exp2f(cents / 1200.0);
+        assert!(c.push((i > 5).then_some(pitch)));
+    }
+    c
+}
+
+#[test]
+fn bend_test() {
+    let mut c = contour((0.0, 0.0));
+    assert_eq!(c.pitch(0), None);
+    assert!((c.pitch(1499).unwrap() - 196.0).abs() < 0.01);
+
+    let r = c.analyze().unwrap();
+    let settling = r.settling.unwrap();
+    assert!(settling > 0.01 && settling < 0.1, "{:?}", r);
+    // within the tolerance of the settled pitch
+    assert!(
+        crate::note::cents(r.pitch, 196.0).abs() < c.tolerance,
+        "{:?}",
+        r
+    );
+    assert!((r.bend - 200.0).abs() < 2.0, "{:?}", r);
+    assert!(r.bend_time > 0.69 && r.bend_time < 1.2);
+    assert_eq!(r.vibrato, None);
+}
+
+#[test]
+fn vibrato_test() {
+    // found after the bend
+    let v = contour((5.5, 20.0)).analyze().unwrap().vibrato.unwrap();
+    assert!((v.rate - 5.5).abs() < 0.2, "{:?}", v);
+    assert!((v.extent - 20.0).abs() < 3.0, "{:?}", v);
+}
+
+#[test]
+fn steady_test() {
+    // vibrato throughout, then silence
+    let mut c = Contour::<2048>::new(RATE);
+    for i in 0..2000 {
+        let t = i as f32 / RATE;
+        let cents = 15.0 * libm::sinf(2.0 * core::f32::consts::PI * 6.0 * t);
+        c.push((t < 3.0).then(|| 196.0 * libm::exp2f(cents / 1200.0)));
+    }
+    let r = c.analyze().unwrap();
+    let v = r.vibrato.unwrap();
+    assert!((v.rate - 6.0).abs() < 0.2, "{:?}", r);
+    assert!((v.extent - 15.0).abs() < 3.0, "{:?}", r);
+    assert!(r.settling.is_none_or(|s| s < 3.0), "{:?}", r);
+
+    // the jitter of a steady note is no vibrato
+    let mut c = Contour::<2048>::new(RATE);
+    for i in 0..2000 {
+        let t = i as f32 / RATE;
+        let cents = 0.2 * libm::sinf(2.0 * core::f32::consts::PI * 11.8 * t);
+        c.push(Some(82.41 * libm::exp2f(cents / 1200.0)));
+    }
+    let r = c.analyze().unwrap();
+    assert_eq!(r.vibrato, None);
+    assert_eq!(r.settling, Some(0.0));
+}
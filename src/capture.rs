@@ -0,0 +1,378 @@
+// capture, raw sample bursts with metadata
+//
+// The device sends a `CaptureHeader` frame followed by the samples in
+// `Samples` frames (see `protocol`). On the host the frames are assembled
+// into a `Capture`, which can be stored as WAV for offline analysis. Legacy
+// captures (one sample per line as traced by `rtic_saadc_raw`) can be
+// imported as well. The device precedes captures with a `Profile` frame, so
+// the host knows which ADC settings produced them.
+
+use crate::protocol::{CaptureHeader, Frame, Samples, MAX_SAMPLES};
+
+/// Largest capture addressed by the sequence numbers of `Samples` frames.
+pub const MAX_LENGTH: usize = u16::MAX as usize * MAX_SAMPLES;
+
+/// Number of `Samples` frames carrying `length` samples.
+pub fn nr_chunks(length: usize) -> usize {
+    length.div_ceil(MAX_SAMPLES)
+}
+
+/// The `Samples` frame with sequence number `seq`, if within `samples`.
+pub fn chunk(samples: &[i16], seq: usize) -> Option<Frame> {
+    let start = seq * MAX_SAMPLES;
+    let end = (start + MAX_SAMPLES).min(samples.len());
+    if start >= end {
+        return None;
+    }
+    Samples::new(seq as u16, &samples[start..end])
+        .ok()
+        .map(Frame::Samples)
+}
+
+/// Splits a capture into frames, the header followed by the samples.
+pub fn frames(header: &CaptureHeader, samples: &[i16], mut f: impl FnMut(Frame)) {
+    f(Frame::Capture(*header));
+    for seq in 0..nr_chunks(samples.len()) {
+        f(chunk(samples, seq).unwrap());
+    }
+}
+
+#[cfg(feature = "std")]
+pub use host::*;
+
+#[cfg(feature = "std")]
+mod host {
+    use std::{fmt, io, path::Path};
+
+    use super::*;
+    use crate::adc::AdcProfile;
+
+    /// Metadata assumed for legacy captures, `rtic_saadc_raw` sampled at
+    /// approximately 1kHz, 14 bit with gain 4.
+    pub const LEGACY: CaptureHeader = CaptureHeader {
+        id: 0,
+        sample_rate: 1000.0,
+        gain: 4.0,
+        resolution: 14,
+        timestamp: 0,
+        length: 0,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Capture {
+        pub header: CaptureHeader,
+        pub samples: Vec<i16>,
+        /// ADC profile, if reported by the device.
+        pub profile: Option<AdcProfile>,
+        /// Number of samples lost in transfer (set to zero in `samples`).
+        pub dropped: usize,
+    }
+
+    impl Capture {
+        /// Parses a legacy capture, one integer per line (optionally
+        /// followed by a comma), empty lines are ignored.
+        pub fn from_legacy(text: &str, header: CaptureHeader) -> Result<Capture, ParseError> {
+            let samples = text
+                .lines()
+                .enumerate()
+                .map(|(i, l)| (i, l.trim().trim_end_matches(',').trim()))
+                .filter(|(_, l)| !l.is_empty())
+                .map(|(i, l)| l.parse::<i16>().map_err(|_| ParseError { line: i + 1 }))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Capture {
+                header: CaptureHeader {
+                    length: samples.len() as u32,
+                    ..header
+                },
+                samples,
+                profile: None,
+                dropped: 0,
+            })
+        }
+
+        /// Samples scaled to -1.0..1.0 given the ADC profile (or resolution).
+        pub fn normalized(&self) -> Vec<f32> {
+            let full_scale = self.profile.map_or(
+                (1u32 << self.header.resolution.clamp(1, 31)) as f32 / 2.0,
+                |p| p.full_scale(),
+            );
+            self.samples
+                .iter()
+                .map(|&s| s as f32 / full_scale)
+                .collect()
+        }
+
+        /// Writes the samples as 16 bit mono WAV, values are kept as is.
+        pub fn write_wav(&self, path: &Path) -> Result<(), hound::Error> {
+            let spec = hound::WavSpec {
+                channels: 1,
+                sample_rate: self.header.sample_rate.round() as u32,
+                bits_per_sample: 16,
+                sample_format: hound::SampleFormat::Int,
+            };
+            let mut writer = hound::WavWriter::create(path, spec)?;
+            for &s in &self.samples {
+                writer.write_sample(s)?;
+            }
+            writer.finalize()
+        }
+
+        /// Reads a WAV file, only the first channel is kept. Integer samples
+        /// of up to 16 bits are kept as is, wider and float samples are
+        /// scaled to 16 bits. Metadata stored next to the WAV (by
+        /// `write_meta`) is restored.
+        pub fn read_wav(path: &Path) -> Result<Capture, hound::Error> {
+            let mut reader = hound::WavReader::open(path)?;
+            let spec = reader.spec();
+            let channels = spec.channels.max(1) as usize;
+            let samples = match spec.sample_format {
+                hound::SampleFormat::Int => {
+                    let shift = spec.bits_per_sample.saturating_sub(16);
+                    reader
+                        .samples::<i32>()
+                        .step_by(channels)
+                        .map(|s| s.map(|v| (v >> shift) as i16))
+                        .collect::<Result<Vec<_>, _>>()?
+                }
+                hound::SampleFormat::Float => reader
+                    .samples::<f32>()
+                    .step_by(channels)
+                    .map(|s| s.map(|v| (v * i16::MAX as f32).clamp(-32768.0, 32767.0) as i16))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+            let mut capture = Capture {
+                header: CaptureHeader {
+                    id: 0,
+                    sample_rate: spec.sample_rate as f32,
+                    gain: 1.0,
+                    resolution: spec.bits_per_sample.min(16) as u8,
+                    timestamp: 0,
+                    length: samples.len() as u32,
+                },
+                samples,
+                profile: None,
+                dropped: 0,
+            };
+            if let Ok(meta) = std::fs::read_to_string(path.with_extension("meta")) {
+                capture.apply_meta(&meta);
+            }
+            Ok(capture)
+        }
+
+        // Restores the metadata written by `meta`, unknown keys and invalid
+        // values are ignored. Custom profiles are not restored.
+        fn apply_meta(&mut self, meta: &str) {
+            for (key, value) in meta.lines().filter_map(|l| l.split_once('=')) {
+                let value = value.trim();
+                let h = &mut self.header;
+                match key.trim() {
+                    "id" => h.id = value.parse().unwrap_or(h.id),
+                    "sample_rate" => h.sample_rate = value.parse().unwrap_or(h.sample_rate),
+                    "gain" => h.gain = value.parse().unwrap_or(h.gain),
+                    "resolution" => h.resolution = value.parse().unwrap_or(h.resolution),
+                    "timestamp" => h.timestamp = value.parse().unwrap_or(h.timestamp),
+                    "dropped" => self.dropped = value.parse().unwrap_or(self.dropped),
+                    "profile" => self.profile = AdcProfile::named(value),
+                    _ => {}
+                }
+            }
+        }
+
+        /// Writes the metadata as `key = value` lines, stored next to the
+        /// WAV as WAV has no place for it.
+        pub fn write_meta(&self, path: &Path) -> io::Result<()> {
+            std::fs::write(path, self.meta())
+        }
+
+        pub fn meta(&self) -> String {
+            let h = &self.header;
+            let mut meta = format!(
+                "id = {}\nsample_rate = {}\ngain = {}\nresolution = {}\ntimestamp = {}\nlength = {}\ndropped = {}\n",
+                h.id, h.sample_rate, h.gain, h.resolution, h.timestamp, h.length, self.dropped
+            );
+            if let Some(p) = &self.profile {
+                meta += &format!("profile = {}\nadc = {}\n", p.name().unwrap_or("custom"), p);
+            }
+            meta
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ParseError {
+        pub line: usize,
+    }
+
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "invalid sample on line {}", self.line)
+        }
+    }
+
+    impl std::error::Error for ParseError {}
+
+    /// Assembles captures from decoded frames, the last `Profile` frame is
+    /// recorded with the following captures, other frames are ignored.
+    /// Repeated chunks are ignored, captures longer than `MAX_LENGTH` are
+    /// cut.
+    #[derive(Default)]
+    pub struct Assembler {
+        // the capture, the chunks and samples received
+        current: Option<(Capture, Vec<bool>, usize)>,
+        profile: Option<AdcProfile>,
+    }
+
+    impl Assembler {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Returns a capture when complete, or when a new header arrives
+        /// before the previous capture was completed.
+        pub fn push(&mut self, frame: &Frame) -> Option<Capture> {
+            match frame {
+                Frame::Capture(header) => {
+                    let previous = self.finish();
+                    let length = (header.length as usize).min(MAX_LENGTH);
+                    let capture = Capture {
+                        header: *header,
+                        samples: vec![0; length],
+                        profile: self.profile,
+                        dropped: 0,
+                    };
+                    self.current = Some((capture, vec![false; nr_chunks(length)], 0));
+                    previous
+                }
+                Frame::Samples(s) => {
+                    let (capture, chunks, received) = self.current.as_mut()?;
+                    let seen = chunks.get_mut(s.seq as usize)?;
+                    if *seen {
+                        return None;
+                    }
+                    *seen = true;
+                    let offset = s.seq as usize * MAX_SAMPLES;
+                    let end = (offset + s.samples().len()).min(capture.samples.len());
+                    if offset < end {
+                        capture.samples[offset..end].copy_from_slice(&s.samples()[..end - offset]);
+                        *received += end - offset;
+                    }
+                    if *received == capture.samples.len() {
+                        self.finish()
+                    } else {
+                        None
+                    }
+                }
+                Frame::Profile(p) => {
+                    self.profile = Some(*p);
+                    None
+                }
+                _ => None,
+            }
+        }
+
+        /// Returns the capture in progress, if any, with missing samples
+        /// accounted as dropped.
+        pub fn finish(&mut self) -> Option<Capture> {
+            self.current.take().map(|(mut capture, _, received)| {
+                capture.dropped = capture.samples.len() - received;
+                capture
+            })
+        }
+    }
+
+    #[test]
+    fn assembler_test() {
+        let header = CaptureHeader {
+            id: 7,
+            sample_rate: 1000.0,
+            gain: 4.0,
+            resolution: 14,
+            timestamp: 1234,
+            length: 300,
+        };
+        let samples: Vec<i16> = (0..300).collect();
+
+        let mut assembler = Assembler::new();
+        let mut done = vec![];
+        assembler.push(&Frame::Profile(AdcProfile::DEFAULT));
+        frames(&header, &samples, |f| done.extend(assembler.push(&f)));
+        assert_eq!(
+            done,
+            [Capture {
+                header,
+                samples: samples.clone(),
+                profile: Some(AdcProfile::DEFAULT),
+                dropped: 0
+            }]
+        );
+        assert!(done[0].meta().contains("profile = default\n"));
+
+        // round trip through WAV and meta
+        // (a file per process, test runs may overlap)
+        let path = std::env::temp_dir().join(format!(
+            "guitar_tuner_assembler_test_{}.wav",
+            std::process::id()
+        ));
+        done[0].write_wav(&path).unwrap();
+        done[0].write_meta(&path.with_extension("meta")).unwrap();
+        let read = Capture::read_wav(&path);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("meta")).unwrap();
+        assert_eq!(read.unwrap(), done[0]);
+
+        // drop the second chunk, the next header completes the capture
+        let mut seq = 0;
+        let mut done = vec![];
+        frames(&header, &samples, |f| {
+            if let Frame::Samples(_) = f {
+                seq += 1;
+                if seq == 2 {
+                    return;
+                }
+            }
+            done.extend(assembler.push(&f))
+        });
+        assert!(done.is_empty());
+        let capture = assembler.push(&Frame::Capture(header)).unwrap();
+        assert_eq!(capture.dropped, MAX_SAMPLES);
+        assert_eq!(capture.samples[MAX_SAMPLES], 0);
+        assert_eq!(capture.samples[2 * MAX_SAMPLES], 2 * MAX_SAMPLES as i16);
+
+        // repeated and out of range chunks are ignored
+        assembler.finish();
+        let mut done = vec![];
+        frames(&header, &samples, |f| {
+            done.extend(assembler.push(&f));
+            if let Frame::Samples(s) = f {
+                done.extend(assembler.push(&Frame::Samples(s)));
+            }
+        });
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].dropped, 0);
+        assert_eq!(done[0].samples, samples);
+        assembler.push(&Frame::Capture(header));
+        let chunk = Samples::new(9, &samples[..MAX_SAMPLES]).unwrap();
+        for _ in 0..10 {
+            assert_eq!(assembler.push(&Frame::Samples(chunk)), None);
+        }
+        assert_eq!(assembler.finish().unwrap().dropped, 300);
+
+        // the length is not trusted
+        let huge = CaptureHeader {
+            length: u32::MAX,
+            ..header
+        };
+        assembler.push(&Frame::Capture(huge));
+        assert_eq!(assembler.finish().unwrap().samples.len(), MAX_LENGTH);
+    }
+
+    #[test]
+    fn legacy_test() {
+        let c = Capture::from_legacy("\n-36, \n-12, \n\n 7\n", LEGACY).unwrap();
+        assert_eq!(c.samples, [-36, -12, 7]);
+        assert_eq!(c.header.length, 3);
+        assert_eq!(
+            Capture::from_legacy("1,\nx\n", LEGACY),
+            Err(ParseError { line: 2 })
+        );
+    }
+}
@@ -0,0 +1,152 @@
+// Sub commands of the `guitar-tuner` command line tool
+
+use std::{borrow::Cow, path::Path};
+
+use anyhow::Context;
+use guitar_tuner::{
+    capture::{Capture, LEGACY},
+    decimate::{Decimator, MAX_FACTOR, MAX_HALF_BANDS},
+    filter::{Chain, Filter},
+    track::{Tracker, MIN_FREQ},
+    tuning::Tuning,
+};
+
+pub mod analyze;
+mod audio;
+pub mod bend;
+pub mod config;
+pub mod decode;
+pub mod detect;
+pub mod devices;
+pub mod import;
+pub mod intonation;
+pub mod live;
+pub mod pass;
+mod plot;
+pub mod profile;
+pub mod stretch;
+pub mod strum;
+pub mod tone;
+
+/// Loads a capture from a WAV file or a legacy `data*.txt` trace (assumed
+/// to be sampled by the `default` ADC profile at 1kHz).
+pub fn load(path: &Path) -> anyhow::Result<Capture> {
+    if path.extension().is_some_and(|e| e == "txt") {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut capture = Capture::from_legacy(&text, LEGACY)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        capture.profile = Some(Default::default());
+        Ok(capture)
+    } else {
+        Capture::read_wav(path).with_context(|| format!("failed to read {}", path.display()))
+    }
+}
+
+/// The pre-processing `chain` built for `fs`.
+pub fn filter(chain: &Chain, fs: f32) -> anyhow::Result<Filter> {
+    chain.build(fs).with_context(|| {
+        format!(
+            "filter \"{}\" is not below the Nyquist frequency of {} Hz",
+            chain,
+            fs / 2.0
+        )
+    })
+}
+
+/// Parses a positive number, e.g. a time for a `Duration`.
+pub fn parse_positive(s: &str) -> Result<f32, String> {
+    s.parse()
+        .ok()
+        .filter(|v: &f32| v.is_finite() && *v > 0.0)
+        .ok_or_else(|| "expected a positive number".to_string())
+}
+
+/// History of the trackers, enough for 25 Hz at 96 kHz (a few semitones
+/// below the low B of a five string bass).
+pub const HISTORY: usize = 8192;
+
+/// Lowest analysis rate in Hz, keeping the second partial of the highest
+/// pitch tracked (1200 Hz) below the pass band.
+const MIN_ANALYSIS_RATE: u32 = 4000;
+
+/// The decimator from `fs` to the analysis `rate`, if given, else by the
+/// smallest factor bringing `fs` down to `max_fs` (e.g. the highest rate of
+/// a tracker), if any.
+pub fn decimator(fs: f32, rate: Option<u32>, max_fs: f32) -> anyhow::Result<Option<Decimator>> {
+    let Some(rate) = rate else {
+        let factor = (fs / max_fs).ceil() as usize;
+        if factor <= 1 {
+            return Ok(None);
+        }
+        return (factor..=MAX_FACTOR << MAX_HALF_BANDS)
+            .find_map(Decimator::new)
+            .map(Some)
+            .with_context(|| format!("sample rate of {} Hz is too high", fs));
+    };
+    if rate < MIN_ANALYSIS_RATE {
+        anyhow::bail!(
+            "analysis rate {} Hz is below {} Hz",
+            rate,
+            MIN_ANALYSIS_RATE
+        );
+    }
+    let decimator = Decimator::from_rates(fs, rate as f32).with_context(|| {
+        format!(
+            "analysis rate {} Hz is not {} Hz divided by 1..=8 times a power of two up to 16",
+            rate, fs
+        )
+    })?;
+    Ok((decimator.factor() > 1).then_some(decimator))
+}
+
+/// The `samples` at `fs` decimated for the trackers (see `decimator`), with
+/// the factor.
+pub fn decimate(
+    samples: &[f32],
+    fs: f32,
+    rate: Option<u32>,
+) -> anyhow::Result<(Cow<'_, [f32]>, usize)> {
+    let max_fs = Tracker::<HISTORY>::max_fs(MIN_FREQ);
+    match decimator(fs, rate, max_fs)? {
+        Some(mut decimator) => {
+            let mut decimated = Vec::with_capacity(samples.len() / decimator.factor());
+            decimator.process(samples, |x| decimated.push(x));
+            Ok((Cow::Owned(decimated), decimator.factor()))
+        }
+        None => Ok((Cow::Borrowed(samples), 1)),
+    }
+}
+
+/// Lowest pitch detected for `tuning`, a fourth below its lowest string, at
+/// most `MIN_FREQ` (the default of the detectors).
+pub fn min_freq(tuning: &Tuning, a4: f32) -> f32 {
+    tuning.range(a4).map_or(MIN_FREQ, |(low, _)| {
+        (low * 2f32.powf(-5.0 / 12.0)).min(MIN_FREQ)
+    })
+}
+
+/// The capo and transposition of `tuning`, e.g. ", capo 2, transposed +0
+/// semitones -50 c", empty if none.
+pub fn shift(tuning: &Tuning) -> String {
+    let mut shift = String::new();
+    if tuning.capo() > 0 {
+        shift += &format!(", capo {}", tuning.capo());
+    }
+    let (semitones, cents) = tuning.transposition();
+    if semitones != 0 || cents != 0.0 {
+        shift += &format!(", transposed {:+} semitones {:+} c", semitones, cents);
+    }
+    shift
+}
+
+/// Index of the first sample after the attack, 50 ms after the peak (the
+/// pitch settles after the pick leaves the string).
+pub fn after_attack(samples: &[f32], fs: f32) -> usize {
+    let peak = samples
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map_or(0, |(i, _)| i);
+    peak + (0.05 * fs) as usize
+}
@@ -0,0 +1,407 @@
+//! Analyzes a note in a WAV or legacy `data*.txt` capture, replacing the
+//! Octave scripts. `--spectrum` reports the fundamental and its harmonics
+//! (see `guitar_tuner::harmonics`), optionally written as CSV and as an SVG
+//! plot of the spectrum. The waveform, its zero crossings and the pitch over
+//! time are plotted as SVG, reproducing the figures of the README.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Args;
+use guitar_tuner::{
+    fft::Detector,
+    harmonics::{Analysis, Analyzer, MAX_HARMONICS},
+    lerp::zero_crossings,
+    note::{cents, Note, A4},
+    track::{Tracker, MAX_FREQ, MIN_FREQ},
+};
+
+use super::{plot::Plot, HISTORY};
+
+/// FFT size of the spectrum, ~1.5 Hz per bin at 48 kHz.
+const FFT_SIZE: usize = 32768;
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The WAV or legacy `.txt` capture of the note
+    #[arg(value_name = "FILE")]
+    input: PathBuf,
+
+    /// Report the fundamental, harmonics, inharmonicity and decay
+    #[arg(long)]
+    spectrum: bool,
+
+    /// Start of the analysis in seconds, defaults to just after the attack
+    #[arg(long, value_name = "SEC")]
+    start: Option<f32>,
+
+    /// Length of the signal used to measure frequencies in seconds
+    #[arg(long, value_name = "SEC", default_value_t = 1.0)]
+    duration: f32,
+
+    /// Fundamental in Hz, estimated by the FFT detector if omitted
+    #[arg(long, value_name = "HZ")]
+    fundamental: Option<f32>,
+
+    /// Number of harmonics
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u8).range(1..=MAX_HARMONICS as i64))]
+    harmonics: u8,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Write the harmonics as CSV
+    #[arg(long, value_name = "FILE")]
+    csv: Option<PathBuf>,
+
+    /// Write the spectrum as an SVG plot
+    #[arg(long, value_name = "FILE")]
+    svg: Option<PathBuf>,
+
+    /// Plot the waveform with the detected periods marked
+    #[arg(long, value_name = "FILE")]
+    waveform: Option<PathBuf>,
+
+    /// Plot the samples with the (`lerp_zero` interpolated) zero crossings
+    #[arg(long, value_name = "FILE")]
+    zero_crossings: Option<PathBuf>,
+
+    /// Plot the pitch over the whole capture, as tracked
+    #[arg(long, value_name = "FILE")]
+    pitch: Option<PathBuf>,
+
+    /// Number of periods shown by the waveform and zero crossing plots
+    #[arg(long, default_value_t = 4)]
+    periods: usize,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    if !opt.spectrum
+        && opt.waveform.is_none()
+        && opt.zero_crossings.is_none()
+        && opt.pitch.is_none()
+    {
+        anyhow::bail!(
+            "nothing to analyze, use --spectrum, --waveform, --zero-crossings or --pitch"
+        );
+    }
+    let capture = super::load(&opt.input)?;
+    let fs = capture.header.sample_rate;
+    let samples = capture.normalized();
+
+    let start = match opt.start {
+        Some(s) => (s * fs) as usize,
+        None => super::after_attack(&samples, fs),
+    };
+    if start >= samples.len() {
+        anyhow::bail!("the capture ends at {:.2} s", samples.len() as f32 / fs);
+    }
+
+    if opt.spectrum {
+        spectrum(&opt, &samples, fs, start)?;
+    }
+    if opt.waveform.is_some() || opt.zero_crossings.is_some() {
+        let period = period(&samples, fs, start)?
+            .with_context(|| format!("no pitch found at {:.2} s", start as f32 / fs))?;
+        println!(
+            "period {:.3} ms ({:.2} Hz) at {:.2} s",
+            1000.0 * period / fs,
+            fs / period,
+            start as f32 / fs
+        );
+        if let Some(path) = &opt.waveform {
+            waveform(&opt, &samples, fs, start, period).save(path)?;
+        }
+        if let Some(path) = &opt.zero_crossings {
+            crossings(&opt, &samples, fs, start, period).save(path)?;
+        }
+    }
+    if let Some(path) = &opt.pitch {
+        pitch(&opt, &samples, fs)?.save(path)?;
+    }
+    Ok(())
+}
+
+// The harmonics report.
+fn spectrum(opt: &Opt, samples: &[f32], fs: f32, start: usize) -> anyhow::Result<()> {
+    let window = ((opt.duration * fs) as usize).min(FFT_SIZE);
+    if start + window > samples.len() {
+        anyhow::bail!(
+            "{:.2} s from {:.2} s exceeds the capture of {:.2} s",
+            opt.duration,
+            start as f32 / fs,
+            samples.len() as f32 / fs
+        );
+    }
+    let samples = &samples[start..];
+
+    let mut detector = Box::new(Detector::<FFT_SIZE>::new());
+    let estimate = detector.detect(&samples[..window], fs);
+    let f0 = match (opt.fundamental, estimate) {
+        (Some(f), _) => f,
+        (None, Some(e)) => e.pitch,
+        (None, None) => anyhow::bail!("no fundamental found, use --fundamental"),
+    };
+
+    let mut analyzer = Analyzer::new();
+    analyzer.harmonics = opt.harmonics as usize;
+    analyzer.window = window as f32 / fs;
+    let analysis = analyzer.analyze(samples, fs, f0);
+
+    let (note, deviation) = Note::nearest(analysis.fundamental, opt.a4);
+    println!(
+        "{:.2} s .. {:.2} s at {} Hz",
+        start as f32 / fs,
+        (start + window) as f32 / fs,
+        fs
+    );
+    print!(
+        "fundamental {:.2} Hz ({} {:+.1} c)",
+        analysis.fundamental, note, deviation
+    );
+    match analysis.inharmonicity {
+        Some(b) => println!(", inharmonicity B = {:.2e}", b),
+        None => println!(),
+    }
+    println!("harmonic  expected Hz  freq Hz    cents  level dB  rel dB  decay s");
+    for row in rows(&analysis) {
+        println!(
+            "{:>8}  {:>11.2}  {:>7.2}  {:>7}  {:>8.1}  {:>6.1}  {:>7}",
+            row.k,
+            row.expected,
+            row.freq,
+            row.cents.map_or("alias".into(), |c| format!("{:+.1}", c)),
+            row.level,
+            row.relative,
+            match row.decay {
+                d if d.is_nan() => "-".into(),
+                d if d.is_infinite() => "inf".into(),
+                d => format!("{:.2}", d),
+            }
+        );
+    }
+    for warning in aliasing(&analysis, fs, analyzer.floor, 2.0 / analyzer.window) {
+        println!("warning: {}", warning);
+    }
+
+    if let Some(path) = &opt.csv {
+        let mut csv = String::from(
+            "harmonic,expected_hz,freq_hz,cents,level_db,relative_db,decay_s,aliased\n",
+        );
+        for row in rows(&analysis) {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                row.k,
+                row.expected,
+                row.freq,
+                row.cents.map_or(String::new(), |c| c.to_string()),
+                row.level,
+                row.relative,
+                row.decay,
+                row.cents.is_none()
+            );
+        }
+        std::fs::write(path, csv).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+
+    if let Some(path) = &opt.svg {
+        let bin = fs / FFT_SIZE as f32;
+        let spectrum = detector
+            .spectrum()
+            .iter()
+            .enumerate()
+            .map(|(k, m)| (k as f32 * bin, 20.0 * m.log10()))
+            .collect();
+        let title = format!("{} ({} {:+.1} c)", name(&opt.input), note, deviation);
+        let mut plot = Plot::new(&title, "frequency [Hz]", "level [dBFS]");
+        plot.x_range(0.0, fs / 2.0)
+            .y_range(-100.0, 0.0)
+            .line("spectrum", "steelblue", spectrum);
+        plot.points(
+            "harmonics",
+            "green",
+            rows(&analysis).map(|r| (r.freq, r.level)).collect(),
+        );
+        for row in rows(&analysis).filter(|r| r.cents.is_none()) {
+            plot.marker(row.freq, &format!("alias {}", row.k), "red");
+        }
+        plot.save(path)?;
+    }
+    Ok(())
+}
+
+// The period in samples tracked from `start` on, searching for up to half a
+// second.
+fn period(samples: &[f32], fs: f32, start: usize) -> anyhow::Result<Option<f32>> {
+    let (samples, factor) = super::decimate(samples, fs, None)?;
+    let (fs, start) = (fs / factor as f32, start / factor);
+    let mut tracker = Tracker::<HISTORY>::with_range(fs, MIN_FREQ, MAX_FREQ)?;
+    tracker.extend(&samples[start.saturating_sub(HISTORY)..start]);
+    let hop = ((0.01 * fs) as usize).max(1);
+    let end = (start + (0.5 * fs) as usize).min(samples.len());
+    for chunk in samples[start..end].chunks(hop) {
+        tracker.update();
+        if let Some(period) = tracker.period() {
+            return Ok(Some(period * factor as f32));
+        }
+        tracker.extend(chunk);
+    }
+    Ok(None)
+}
+
+// Samples from `start` covering the periods shown, with the times in
+// seconds.
+fn span(opt: &Opt, samples: &[f32], fs: f32, start: usize, period: f32) -> Vec<(f32, f32)> {
+    let len = ((opt.periods + 1) as f32 * period) as usize;
+    samples[start..(start + len).min(samples.len())]
+        .iter()
+        .enumerate()
+        .map(|(i, y)| ((start + i) as f32 / fs, *y))
+        .collect()
+}
+
+// The waveform with a marker per period, starting at the first rising zero
+// crossing.
+fn waveform(opt: &Opt, samples: &[f32], fs: f32, start: usize, period: f32) -> Plot {
+    let span = span(opt, samples, fs, start, period);
+    let ys: Vec<f32> = span.iter().map(|p| p.1).collect();
+    let first = zero_crossings(&ys)
+        .find(|(_, rising)| *rising)
+        .map_or(0.0, |(x, _)| x);
+
+    let title = format!(
+        "{}, period {:.3} ms ({:.2} Hz)",
+        name(&opt.input),
+        1000.0 * period / fs,
+        fs / period
+    );
+    let mut plot = Plot::new(&title, "time [s]", "amplitude");
+    plot.line("signal", "steelblue", span);
+    for i in 0..=opt.periods {
+        let t = (start as f32 + first + i as f32 * period) / fs;
+        plot.marker(t, &format!("{}T", i), "green");
+    }
+    plot
+}
+
+// The samples and their zero crossings, rising and falling.
+fn crossings(opt: &Opt, samples: &[f32], fs: f32, start: usize, period: f32) -> Plot {
+    let span = span(opt, samples, fs, start, period);
+    let ys: Vec<f32> = span.iter().map(|p| p.1).collect();
+    let (mut rising, mut falling) = (vec![], vec![]);
+    for (x, up) in zero_crossings(&ys) {
+        let t = (start as f32 + x) / fs;
+        if up {
+            rising.push((t, 0.0));
+        } else {
+            falling.push((t, 0.0));
+        }
+    }
+    println!(
+        "{:.1} rising zero crossings per period",
+        rising.len() as f32 * period / ys.len() as f32
+    );
+
+    let title = format!("{}, zero crossings", name(&opt.input));
+    let mut plot = Plot::new(&title, "time [s]", "amplitude");
+    plot.line("", "steelblue", span.clone())
+        .points("samples", "steelblue", span)
+        .points("rising", "red", rising)
+        .points("falling", "gray", falling);
+    plot
+}
+
+// The pitch over the whole capture, tracked every 10 ms.
+fn pitch(opt: &Opt, samples: &[f32], fs: f32) -> anyhow::Result<Plot> {
+    let duration = samples.len() as f32 / fs;
+    let (samples, factor) = super::decimate(samples, fs, None)?;
+    let fs = fs / factor as f32;
+    let mut tracker = Tracker::<HISTORY>::with_range(fs, MIN_FREQ, MAX_FREQ)?;
+    let hop = ((0.01 * fs) as usize).max(1);
+    let mut points = vec![];
+    for (i, chunk) in samples.chunks(hop).enumerate() {
+        tracker.extend(chunk);
+        tracker.update();
+        if let Some(pitch) = tracker.pitch() {
+            points.push(((i * hop + chunk.len()) as f32 / fs, pitch));
+        }
+    }
+
+    let title = format!("{}, pitch", name(&opt.input));
+    let mut plot = Plot::new(&title, "time [s]", "pitch [Hz]");
+    plot.x_range(0.0, duration)
+        .points("tracker", "steelblue", points);
+    Ok(plot)
+}
+
+// File name of the capture, for titles.
+fn name(path: &Path) -> String {
+    path.file_name()
+        .map_or(String::new(), |n| n.to_string_lossy().into_owned())
+}
+
+// A harmonic as reported.
+struct Row {
+    k: usize,
+    expected: f32,
+    freq: f32,
+    /// Deviation from k times the fundamental, `None` if aliased.
+    cents: Option<f32>,
+    level: f32,
+    /// Level relative to the fundamental.
+    relative: f32,
+    decay: f32,
+}
+
+fn rows(analysis: &Analysis) -> impl Iterator<Item = Row> + '_ {
+    let fundamental = analysis.harmonics()[0];
+    analysis.harmonics().iter().enumerate().map(move |(i, h)| {
+        let k = i + 1;
+        Row {
+            k,
+            expected: h.expected,
+            freq: h.freq,
+            cents: (!h.aliased).then(|| cents(h.freq, k as f32 * fundamental.freq)),
+            level: h.level,
+            relative: h.level - fundamental.level,
+            decay: h.decay,
+        }
+    })
+}
+
+// Aliased harmonics above `floor`, and those overlapping a harmonic in band
+// (within `resolution` Hz).
+fn aliasing(analysis: &Analysis, fs: f32, floor: f32, resolution: f32) -> Vec<String> {
+    let harmonics = analysis.harmonics();
+    let mut warnings = vec![];
+    for (i, h) in harmonics.iter().enumerate() {
+        if !h.aliased || h.level < floor {
+            continue;
+        }
+        warnings.push(format!(
+            "harmonic {} ({:.1} Hz) is above the Nyquist frequency ({:.0} Hz), aliased to {:.1} Hz at {:.1} dB",
+            i + 1,
+            h.expected,
+            fs / 2.0,
+            h.freq,
+            h.level
+        ));
+        if let Some((j, _)) = harmonics
+            .iter()
+            .enumerate()
+            .find(|(_, o)| !o.aliased && (o.freq - h.freq).abs() < resolution)
+        {
+            warnings.push(format!(
+                "harmonic {} overlaps harmonic {}, its level and frequency are unreliable",
+                i + 1,
+                j + 1
+            ));
+        }
+    }
+    warnings
+}
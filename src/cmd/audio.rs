@@ -0,0 +1,347 @@
+//! Audio devices of the live sub commands (by cpal).
+//!
+//! Streams are negotiated from the configurations a device supports, in
+//! any sample format, and converted to and from `f32` samples.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Context;
+use cpal::{
+    traits::{DeviceTrait, HostTrait},
+    Device, FromSample, Host, Sample, SampleFormat, SampleRate, SizedSample, Stream, StreamError,
+    SupportedStreamConfig, SupportedStreamConfigRange,
+};
+
+/// Sample formats, most preferred first.
+const FORMATS: [SampleFormat; 4] = [
+    SampleFormat::F32,
+    SampleFormat::I32,
+    SampleFormat::I16,
+    SampleFormat::U16,
+];
+
+/// The host named `name` (in any case), or the default host of the
+/// platform for "default".
+pub fn host(name: &str) -> anyhow::Result<Host> {
+    if name == "default" {
+        return Ok(cpal::default_host());
+    }
+    let hosts = cpal::available_hosts();
+    match hosts.iter().find(|id| id.name().eq_ignore_ascii_case(name)) {
+        Some(id) => Ok(cpal::host_from_id(*id)?),
+        None => {
+            let names: Vec<_> = hosts.iter().map(|id| id.name()).collect();
+            anyhow::bail!(
+                "no host \"{}\", the available hosts are: {}",
+                name,
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Direction of a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Input => write!(f, "input"),
+            Direction::Output => write!(f, "output"),
+        }
+    }
+}
+
+/// The devices of `host` in `direction` with their names, in the order
+/// indexed from 1 by `device`.
+pub fn devices(host: &Host, direction: Direction) -> anyhow::Result<Vec<(String, Device)>> {
+    let devices = match direction {
+        Direction::Input => host.input_devices()?,
+        Direction::Output => host.output_devices()?,
+    };
+    Ok(devices
+        .filter_map(|d| d.name().ok().map(|name| (name, d)))
+        .collect())
+}
+
+/// The default device of `host` in `direction`.
+pub fn default_device(host: &Host, direction: Direction) -> Option<Device> {
+    match direction {
+        Direction::Input => host.default_input_device(),
+        Direction::Output => host.default_output_device(),
+    }
+}
+
+/// The ID of a device, qualified by its host, e.g. "alsa:default".
+pub fn id(host: &Host, name: &str) -> String {
+    format!("{}:{}", host.id().name().to_lowercase(), name)
+}
+
+/// The input device selected by `spec`, see `device`.
+pub fn input_device(host: &Host, spec: &str) -> anyhow::Result<Device> {
+    device(host, spec, Direction::Input)
+}
+
+/// The output device selected by `spec`, see `device`.
+pub fn output_device(host: &Host, spec: &str) -> anyhow::Result<Device> {
+    device(host, spec, Direction::Output)
+}
+
+// The device selected by `spec`: "default", an index (from 1, as listed by
+// `devices`), a name, or a unique part of a name in any case, optionally
+// qualified by a host (e.g. "jack:system"), else of `host`.
+fn device(host: &Host, spec: &str, direction: Direction) -> anyhow::Result<Device> {
+    let qualified = spec.split_once(':').and_then(|(name, rest)| {
+        cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name().eq_ignore_ascii_case(name))
+            .map(|id| (id, rest))
+    });
+    let (other, spec) = match qualified {
+        Some((id, rest)) => (Some(cpal::host_from_id(id)?), rest),
+        None => (None, spec),
+    };
+    let host = other.as_ref().unwrap_or(host);
+
+    if spec == "default" {
+        return default_device(host, direction)
+            .with_context(|| format!("no default {} device", direction));
+    }
+    let mut devices = devices(host, direction)?;
+    if let Ok(index) = spec.parse::<usize>() {
+        if (1..=devices.len()).contains(&index) {
+            return Ok(devices.swap_remove(index - 1).1);
+        }
+        anyhow::bail!(
+            "no {} device {}, {}",
+            direction,
+            index,
+            candidates(host, &devices, direction)
+        );
+    }
+    if let Some(i) = devices.iter().position(|(name, _)| name == spec) {
+        return Ok(devices.swap_remove(i).1);
+    }
+    let lower = spec.to_lowercase();
+    let matches: Vec<_> = devices
+        .iter()
+        .enumerate()
+        .filter(|(_, (name, _))| name.to_lowercase().contains(&lower))
+        .map(|(i, _)| i)
+        .collect();
+    match matches[..] {
+        [i] => Ok(devices.swap_remove(i).1),
+        [] => anyhow::bail!(
+            "no {} device \"{}\", {}",
+            direction,
+            spec,
+            candidates(host, &devices, direction)
+        ),
+        _ => {
+            let matching: Vec<_> = matches.iter().map(|i| devices[*i].clone()).collect();
+            anyhow::bail!(
+                "\"{}\" matches several {} devices, {}",
+                spec,
+                direction,
+                candidates(host, &matching, direction)
+            )
+        }
+    }
+}
+
+// The devices to choose from, for an error.
+fn candidates(host: &Host, devices: &[(String, Device)], direction: Direction) -> String {
+    if devices.is_empty() {
+        return format!("there are no {} devices on {}", direction, host.id().name());
+    }
+    let mut text = format!("the {} devices are:", direction);
+    for (i, (name, _)) in devices.iter().enumerate() {
+        text += &format!("\n  {}. \"{}\" ({})", i + 1, name, id(host, name));
+    }
+    text
+}
+
+/// Picks a supported input configuration, see `negotiate`.
+pub fn input_config(
+    device: &Device,
+    rate: Option<u32>,
+    channels: Option<u16>,
+) -> anyhow::Result<SupportedStreamConfig> {
+    let default = device.default_input_config()?;
+    negotiate(
+        device.supported_input_configs()?.collect(),
+        default,
+        rate,
+        channels,
+    )
+    .context("the input device supports no known sample format")
+}
+
+/// Picks a supported output configuration, see `negotiate`.
+pub fn output_config(device: &Device, rate: Option<u32>) -> anyhow::Result<SupportedStreamConfig> {
+    let default = device.default_output_config()?;
+    negotiate(
+        device.supported_output_configs()?.collect(),
+        default,
+        rate,
+        None,
+    )
+    .context("the output device supports no known sample format")
+}
+
+// The configuration with at least (and closest to) `channels` channels (or
+// those of the default), at `rate` (or the default rate) if supported, else
+// the closest, in the most preferred format.
+fn negotiate(
+    ranges: Vec<SupportedStreamConfigRange>,
+    default: SupportedStreamConfig,
+    rate: Option<u32>,
+    channels: Option<u16>,
+) -> Option<SupportedStreamConfig> {
+    let channels = channels.unwrap_or(default.channels());
+    let rate = rate.unwrap_or(default.sample_rate().0);
+    let closest =
+        |r: &SupportedStreamConfigRange| rate.clamp(r.min_sample_rate().0, r.max_sample_rate().0);
+    let range = ranges
+        .into_iter()
+        .filter(|r| FORMATS.contains(&r.sample_format()))
+        .min_by_key(|r| {
+            (
+                r.channels() < channels,
+                r.channels().abs_diff(channels),
+                closest(r).abs_diff(rate),
+                FORMATS.iter().position(|f| *f == r.sample_format()),
+            )
+        })?;
+    let rate = closest(&range);
+    Some(range.with_sample_rate(SampleRate(rate)))
+}
+
+/// Builds an input stream of `config`, calling `data` with the interleaved
+/// samples as `f32`.
+pub fn build_input_stream(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    data: impl FnMut(&[f32], &cpal::InputCallbackInfo) + Send + 'static,
+    error: impl FnMut(StreamError) + Send + 'static,
+) -> anyhow::Result<Stream> {
+    match config.sample_format() {
+        SampleFormat::F32 => input_stream::<f32>(device, config, data, error),
+        SampleFormat::I32 => input_stream::<i32>(device, config, data, error),
+        SampleFormat::I16 => input_stream::<i16>(device, config, data, error),
+        SampleFormat::U16 => input_stream::<u16>(device, config, data, error),
+        format => anyhow::bail!("unsupported sample format {}", format),
+    }
+}
+
+fn input_stream<T>(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    mut data: impl FnMut(&[f32], &cpal::InputCallbackInfo) + Send + 'static,
+    error: impl FnMut(StreamError) + Send + 'static,
+) -> anyhow::Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let mut buf = Vec::new();
+    let stream = device.build_input_stream(
+        &config.config(),
+        move |samples: &[T], info: &cpal::InputCallbackInfo| {
+            buf.clear();
+            buf.extend(samples.iter().map(|s| s.to_sample::<f32>()));
+            data(&buf, info);
+        },
+        error,
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// Builds an output stream of `config`, calling `data` to fill the
+/// interleaved samples as `f32`.
+pub fn build_output_stream(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    data: impl FnMut(&mut [f32], &cpal::OutputCallbackInfo) + Send + 'static,
+    error: impl FnMut(StreamError) + Send + 'static,
+) -> anyhow::Result<Stream> {
+    match config.sample_format() {
+        SampleFormat::F32 => output_stream::<f32>(device, config, data, error),
+        SampleFormat::I32 => output_stream::<i32>(device, config, data, error),
+        SampleFormat::I16 => output_stream::<i16>(device, config, data, error),
+        SampleFormat::U16 => output_stream::<u16>(device, config, data, error),
+        format => anyhow::bail!("unsupported sample format {}", format),
+    }
+}
+
+fn output_stream<T>(
+    device: &Device,
+    config: &SupportedStreamConfig,
+    mut data: impl FnMut(&mut [f32], &cpal::OutputCallbackInfo) + Send + 'static,
+    error: impl FnMut(StreamError) + Send + 'static,
+) -> anyhow::Result<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let mut buf = Vec::new();
+    let stream = device.build_output_stream(
+        &config.config(),
+        move |samples: &mut [T], info: &cpal::OutputCallbackInfo| {
+            buf.resize(samples.len(), 0.0);
+            data(&mut buf, info);
+            for (s, x) in samples.iter_mut().zip(&buf) {
+                *s = x.to_sample::<T>();
+            }
+        },
+        error,
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// A description of a configuration, for the user.
+pub fn describe(config: &SupportedStreamConfig) -> String {
+    format!(
+        "{} Hz, {} channels, {}",
+        config.sample_rate().0,
+        config.channels(),
+        config.sample_format()
+    )
+}
+
+pub fn err_fn(err: StreamError) {
+    eprintln!("an error occurred on stream: {}", err);
+}
+
+/// Set on Ctrl-C (or SIGTERM), once `handle_interrupts` is called.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Handles Ctrl-C by setting `interrupted`, for a clean shut down. A second
+/// Ctrl-C terminates.
+pub fn handle_interrupts() {
+    #[cfg(unix)]
+    {
+        extern "C" fn handler(signal: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::Relaxed);
+            // the default again, the next one terminates
+            unsafe { libc::signal(signal, libc::SIG_DFL) };
+        }
+        let handler = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        unsafe {
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    }
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,139 @@
+//! Analyzes bends and vibrato in a WAV or legacy `data*.txt` capture of a
+//! single note (see `guitar_tuner::bend`), the pitch tracked from the attack
+//! on by the time-domain tracker.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use guitar_tuner::{
+    bend::Contour,
+    note::{Note, A4},
+    track::{Tracker, MAX_FREQ, MIN_FREQ},
+};
+
+use super::{plot::Plot, HISTORY};
+
+/// Frames of the contour, ~20 s at the default hop.
+const FRAMES: usize = 4096;
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The WAV or legacy `.txt` capture to analyze
+    #[arg(value_name = "FILE")]
+    input: PathBuf,
+
+    /// Time between pitch frames in ms
+    #[arg(long, value_name = "MS", default_value_t = 5.0)]
+    hop: f32,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Largest deviation in cents considered settled
+    #[arg(long, value_name = "CENTS", default_value_t = 5.0)]
+    tolerance: f32,
+
+    /// Vibrato rates searched in Hz
+    #[arg(long, value_name = "HZ", default_value_t = 3.0)]
+    min_rate: f32,
+    #[arg(long, value_name = "HZ", default_value_t = 12.0)]
+    max_rate: f32,
+
+    /// Plot the pitch in cents from the settled pitch over time to an SVG file
+    #[arg(long, value_name = "FILE")]
+    svg: Option<PathBuf>,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let capture = super::load(&opt.input)?;
+    let samples = capture.normalized();
+    let (samples, factor) = super::decimate(&samples, capture.header.sample_rate, None)?;
+    let fs = capture.header.sample_rate / factor as f32;
+    let hop = ((opt.hop / 1000.0 * fs) as usize).max(1);
+    let rate = fs / hop as f32;
+    if opt.min_rate <= 0.0 || opt.max_rate <= opt.min_rate || opt.max_rate > rate / 2.0 {
+        anyhow::bail!(
+            "invalid vibrato rates {} Hz .. {} Hz at {:.0} frames per second",
+            opt.min_rate,
+            opt.max_rate,
+            rate
+        );
+    }
+
+    // from the peak of the attack
+    let peak = super::after_attack(&samples, fs).saturating_sub((0.05 * fs) as usize);
+    let samples = &samples[peak.min(samples.len())..];
+
+    let mut tracker = Tracker::<HISTORY>::with_range(fs, MIN_FREQ, MAX_FREQ)?;
+    let mut contour = Box::new(Contour::<FRAMES>::new(rate));
+    contour.tolerance = opt.tolerance;
+    contour.min_rate = opt.min_rate;
+    contour.max_rate = opt.max_rate;
+    for chunk in samples.chunks_exact(hop) {
+        tracker.extend(chunk);
+        tracker.update();
+        if !contour.push(tracker.pitch()) {
+            break;
+        }
+    }
+    let start = peak as f32 / fs;
+    let end = start + contour.len() as f32 / rate;
+
+    let Some(report) = contour.analyze() else {
+        anyhow::bail!("no pitch found in {:.2} s .. {:.2} s", start, end);
+    };
+    println!(
+        "{:.2} s .. {:.2} s, {:.0} frames per second",
+        start, end, rate
+    );
+    let (note, cents) = Note::nearest(report.pitch, opt.a4);
+    match report.settling {
+        Some(settling) => println!(
+            "settled   {:.3} s after the attack, {:.2} Hz ({} {:+.1} c)",
+            settling, report.pitch, note, cents
+        ),
+        None => println!(
+            "not settled within {} c, {:.2} Hz ({} {:+.1} c) at first",
+            opt.tolerance, report.pitch, note, cents
+        ),
+    }
+    // the bend as the nearest number of semitones
+    let semitones = (report.bend / 100.0).round();
+    println!(
+        "bend      {:+.1} c at {:.3} s, {:+} semitones {:+.1} c",
+        report.bend,
+        report.bend_time,
+        semitones,
+        report.bend - 100.0 * semitones
+    );
+    match report.vibrato {
+        Some(v) => println!(
+            "vibrato   {:.2} Hz, +/- {:.1} c, regularity {:.2}",
+            v.rate, v.extent, v.regularity
+        ),
+        None => println!("vibrato   -"),
+    }
+
+    if let Some(path) = &opt.svg {
+        let points = (0..contour.len())
+            .filter_map(|i| {
+                let pitch = contour.pitch(i)?;
+                Some((i as f32 / rate, 1200.0 * (pitch / report.pitch).log2()))
+            })
+            .collect();
+        let title = format!("{}, pitch from {:.2} Hz", opt.input.display(), report.pitch);
+        let mut plot = Plot::new(&title, "time [s]", "cents");
+        plot.x_range(0.0, contour.len() as f32 / rate)
+            .line("pitch", "steelblue", points);
+        // the times reported are from the first pitched frame
+        let first = (0..contour.len()).find_map(|i| contour.pitch(i).map(|_| i as f32 / rate));
+        let first = first.unwrap_or(0.0);
+        if let Some(settling) = report.settling {
+            plot.marker(first + settling, "settled", "gray");
+        }
+        plot.marker(first + report.bend_time, "bend", "firebrick");
+        plot.save(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,481 @@
+//! Persistent settings, the defaults of the options of the sub commands.
+//!
+//! The configuration file (`$XDG_CONFIG_HOME/guitar-tuner/config`, or
+//! `~/.config/guitar-tuner/config`) holds `key = value` lines, `#` starting a
+//! comment. A setting replaces the built-in default of the option of the
+//! same name in every sub command that has one, so options given on the
+//! command line override it. Values are checked as they are set, and edited
+//! in place, keeping comments.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Args, Subcommand, ValueEnum};
+use guitar_tuner::{filter::Chain, temperament::Temperament, tuning::Tuning};
+
+use super::{detect::Detector, live::Display};
+
+/// A setting.
+pub struct Key {
+    pub name: &'static str,
+    /// Sub commands with the option, all with an option of the name if
+    /// empty.
+    pub commands: &'static [&'static str],
+    pub description: &'static str,
+}
+
+/// The settings.
+pub const KEYS: [Key; 17] = [
+    key("host", &[], "audio host, e.g. \"alsa\" or \"jack\""),
+    key("input-device", &[], "input device, see `devices`"),
+    key("output-device", &[], "output device, see `devices`"),
+    key(
+        "tuning",
+        &[],
+        "tuning, e.g. \"drop-d\" or \"D2,A2,D3,G3,B3,E4\"",
+    ),
+    key(
+        "temperament",
+        &[],
+        "temperament, e.g. \"equal\" or \"just:G\"",
+    ),
+    key("capo", &[], "fret of the capo"),
+    key("transpose", &[], "transposition in semitones"),
+    key("detune", &[], "transposition in cents"),
+    key("a4", &[], "reference pitch in Hz"),
+    key("detector", &["detect"], "pitch detector"),
+    key("gate", &[], "noise gate in dBFS"),
+    key(
+        "filter",
+        &[],
+        "pre-processing filters, e.g. \"dc,hp:70,notch:50\"",
+    ),
+    key(
+        "analysis-rate",
+        &["live", "detect"],
+        "sample rate of the detectors in Hz",
+    ),
+    key("latency", &["pass"], "initial delay in ms"),
+    key("hop", &["live"], "time between readings in ms"),
+    key("display", &["live"], "readings, \"cents\" or \"meter\""),
+    key("precision", &["live"], "decimals of the cents"),
+];
+
+const fn key(
+    name: &'static str,
+    commands: &'static [&'static str],
+    description: &'static str,
+) -> Key {
+    Key {
+        name,
+        commands,
+        description,
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    #[command(subcommand)]
+    action: Option<Action>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Print the path of the configuration file
+    Path,
+    /// List the settings
+    Keys,
+    /// Print a setting
+    Get { key: String },
+    /// Change a setting
+    Set {
+        key: String,
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+    /// Remove a setting, back to the built-in default
+    Unset { key: String },
+}
+
+/// A configuration file.
+pub struct Config {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl Config {
+    /// Loads the configuration file, empty if there is none.
+    pub fn load() -> anyhow::Result<Config> {
+        let path = path().context("no configuration directory, set $HOME")?;
+        let lines = match fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(str::to_string).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        Ok(Config { path, lines })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The settings, checked.
+    pub fn settings(&self) -> anyhow::Result<Vec<(&str, &str)>> {
+        let mut settings = vec![];
+        for (i, line) in self.lines.iter().enumerate() {
+            let Some((key, value)) = entry(line) else {
+                if !line.trim().is_empty() && !line.trim_start().starts_with('#') {
+                    anyhow::bail!("{}:{}: expected `key = value`", self.path.display(), i + 1);
+                }
+                continue;
+            };
+            check(key, value)
+                .with_context(|| format!("{}:{}: invalid {}", self.path.display(), i + 1, key))?;
+            settings.retain(|(k, _)| *k != key);
+            settings.push((key, value));
+        }
+        Ok(settings)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        // the last one counts
+        self.lines
+            .iter()
+            .rev()
+            .filter_map(|line| entry(line))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Changes the setting `key`, in place if set.
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        check(key, value).with_context(|| format!("invalid {}", key))?;
+        let line = format!("{} = {}", key, quoted(value));
+        // in place of the first, if set
+        match self
+            .lines
+            .iter()
+            .position(|l| entry(l).is_some_and(|(k, _)| k == key))
+        {
+            Some(i) => {
+                self.unset(key);
+                self.lines.insert(i, line);
+            }
+            None => self.lines.push(line),
+        }
+        Ok(())
+    }
+
+    /// Removes the setting `key`, returning whether it was set.
+    pub fn unset(&mut self, key: &str) -> bool {
+        let len = self.lines.len();
+        self.lines
+            .retain(|line| entry(line).is_none_or(|(k, _)| k != key));
+        self.lines.len() != len
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        fs::write(&self.path, text)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+}
+
+/// Path of the configuration file.
+pub fn path() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("guitar-tuner").join("config"))
+}
+
+// The key and value of a `key = value` line.
+fn entry(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    let value = value.trim();
+    // quotes keep surrounding spaces
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((key.trim(), value))
+}
+
+// The value, quoted if it starts or ends with spaces.
+fn quoted(value: &str) -> String {
+    match value.trim() == value {
+        true => value.to_string(),
+        false => format!("\"{}\"", value),
+    }
+}
+
+// Checks the value of a setting.
+fn check(key: &str, value: &str) -> anyhow::Result<()> {
+    let number = |value: &str| {
+        value
+            .parse::<f32>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .with_context(|| format!("\"{}\" is not a number", value))
+    };
+    match key {
+        "host" | "input-device" | "output-device" => {}
+        "tuning" => {
+            value.parse::<Tuning>()?;
+        }
+        "filter" => {
+            value.parse::<Chain>()?;
+        }
+        "temperament" => {
+            value.parse::<Temperament>()?;
+        }
+        "detector" => {
+            Detector::from_str(value, true).map_err(anyhow::Error::msg)?;
+        }
+        "display" => {
+            Display::from_str(value, true).map_err(anyhow::Error::msg)?;
+        }
+        "gate" | "detune" => {
+            number(value)?;
+        }
+        "capo" => {
+            value
+                .parse::<u8>()
+                .with_context(|| format!("\"{}\" is not a fret", value))?;
+        }
+        "transpose" => {
+            value
+                .parse::<i8>()
+                .with_context(|| format!("\"{}\" is not a number of semitones", value))?;
+        }
+        "a4" | "latency" | "hop" => {
+            if number(value)? <= 0.0 {
+                anyhow::bail!("{} is not positive", value);
+            }
+        }
+        "analysis-rate" => {
+            value
+                .parse::<u32>()
+                .with_context(|| format!("\"{}\" is not a sample rate", value))?;
+        }
+        "precision" => {
+            value
+                .parse::<u8>()
+                .with_context(|| format!("\"{}\" is not a number of decimals", value))?;
+        }
+        _ => anyhow::bail!("unknown setting, the settings are: {}", names()),
+    }
+    Ok(())
+}
+
+/// Makes the `settings` the defaults of the options of the same name.
+pub fn apply(mut command: clap::Command, settings: &[(&str, &str)]) -> clap::Command {
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|c| c.get_name().to_string())
+        .collect();
+    for name in names {
+        command = command.mut_subcommand(name, |mut sub| {
+            for (key, value) in settings {
+                let id = key.replace('-', "_");
+                let scoped = KEYS
+                    .iter()
+                    .find(|k| k.name == *key)
+                    .is_some_and(|k| k.commands.is_empty() || k.commands.contains(&sub.get_name()));
+                if scoped && sub.get_arguments().any(|a| a.get_id() == id.as_str()) {
+                    sub = sub.mut_arg(id, |arg| arg.default_value(value.to_string()));
+                }
+            }
+            sub
+        });
+    }
+    command
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let mut config = Config::load()?;
+    match opt.action {
+        None => {
+            let settings = config.settings()?;
+            if settings.is_empty() {
+                println!("# no settings in {}", config.path().display());
+            }
+            for (key, value) in settings {
+                println!("{} = {}", key, quoted(value));
+            }
+        }
+        Some(Action::Path) => println!("{}", config.path().display()),
+        Some(Action::Keys) => {
+            for key in KEYS {
+                match key.commands {
+                    [] => println!("{:<14} {}", key.name, key.description),
+                    commands => println!(
+                        "{:<14} {} ({})",
+                        key.name,
+                        key.description,
+                        commands.join(", ")
+                    ),
+                }
+            }
+        }
+        Some(Action::Get { key }) => {
+            check_key(&key)?;
+            match config.get(&key) {
+                Some(value) => println!("{}", value),
+                None => anyhow::bail!("{} is not set", key),
+            }
+        }
+        Some(Action::Set { key, value }) => {
+            config.set(&key, &value)?;
+            config.save()?;
+        }
+        Some(Action::Unset { key }) => {
+            check_key(&key)?;
+            if config.unset(&key) {
+                config.save()?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_key(key: &str) -> anyhow::Result<()> {
+    if !KEYS.iter().any(|k| k.name == key) {
+        anyhow::bail!("unknown setting \"{}\", the settings are: {}", key, names());
+    }
+    Ok(())
+}
+
+fn names() -> String {
+    KEYS.map(|k| k.name).join(", ")
+}
+
+// A configuration of `lines`, not saved.
+#[cfg(test)]
+fn config(lines: &[&str]) -> Config {
+    Config {
+        path: PathBuf::from("config"),
+        lines: lines.iter().map(|line| line.to_string()).collect(),
+    }
+}
+
+// The defaults of the option `id` of the sub command `name`.
+#[cfg(test)]
+fn defaults(command: &clap::Command, name: &str, id: &str) -> Vec<String> {
+    command
+        .find_subcommand(name)
+        .and_then(|sub| sub.get_arguments().find(|a| a.get_id() == id))
+        .map(|arg| {
+            arg.get_default_values()
+                .iter()
+                .map(|v| v.to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn quote_test() {
+    // quotes keep surrounding spaces, added back where needed
+    assert_eq!(entry("host = \" jack \""), Some(("host", " jack ")));
+    assert_eq!(entry("  tuning=drop-d  "), Some(("tuning", "drop-d")));
+    assert_eq!(entry("# a4 = 442"), None);
+    assert_eq!(quoted(" jack "), "\" jack \"");
+    assert_eq!(quoted("jack"), "jack");
+
+    let mut c = config(&[]);
+    c.set("input-device", " USB ").unwrap();
+    assert_eq!(c.lines, ["input-device = \" USB \""]);
+    assert_eq!(c.get("input-device"), Some(" USB "));
+}
+
+#[test]
+fn duplicate_test() {
+    // the last one counts
+    let c = config(&["a4 = 440", "gate = -40", "a4 = 442"]);
+    assert_eq!(c.get("a4"), Some("442"));
+    assert_eq!(c.settings().unwrap(), [("gate", "-40"), ("a4", "442")]);
+
+    // checked, with the line
+    let c = config(&["# tuner", "a4 = -1"]);
+    let e = c.settings().unwrap_err();
+    assert!(
+        format!("{:#}", e).starts_with("config:2: invalid a4"),
+        "{:#}",
+        e
+    );
+    assert!(config(&["a4 442"]).settings().is_err());
+}
+
+#[test]
+fn set_test() {
+    // in place of the first, the others removed, comments kept
+    let mut c = config(&[
+        "# tuner",
+        "a4 = 440",
+        "",
+        "# the gate",
+        "gate = -40",
+        "a4 = 441",
+    ]);
+    c.set("a4", "442").unwrap();
+    assert_eq!(
+        c.lines,
+        ["# tuner", "a4 = 442", "", "# the gate", "gate = -40"]
+    );
+    c.set("hop", "20").unwrap();
+    assert_eq!(c.lines.last().unwrap(), "hop = 20");
+
+    // checked
+    assert!(c.set("a4", "x").is_err());
+    assert!(c.set("capo", "-1").is_err());
+    assert!(c.set("unknown", "1").is_err());
+    assert_eq!(c.get("a4"), Some("442"));
+
+    assert!(c.unset("gate"));
+    assert!(!c.unset("gate"));
+    assert_eq!(
+        c.lines,
+        ["# tuner", "a4 = 442", "", "# the gate", "hop = 20"]
+    );
+}
+
+#[test]
+fn apply_test() {
+    use clap::CommandFactory;
+
+    let command = crate::Cli::command();
+    let applied = apply(
+        crate::Cli::command(),
+        &[("hop", "10"), ("a4", "442"), ("capo", "2")],
+    );
+    // in every sub command with the option
+    for name in ["live", "strum", "tone", "intonation"] {
+        assert_eq!(defaults(&applied, name, "a4"), ["442"], "{}", name);
+    }
+    assert_eq!(defaults(&applied, "live", "capo"), ["2"]);
+    // `hop` of `live` only, not the hop of the detectors
+    assert_eq!(defaults(&applied, "live", "hop"), ["10"]);
+    for name in ["detect", "bend"] {
+        assert!(!defaults(&command, name, "hop").is_empty());
+        assert_eq!(
+            defaults(&applied, name, "hop"),
+            defaults(&command, name, "hop"),
+            "{}",
+            name
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Decodes a telemetry stream (see `guitar_tuner::protocol`) and prints one
+//! line per frame.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use clap::Args;
+use guitar_tuner::protocol::{Decoder, Frame};
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The file to decode, reads from stdin if omitted
+    #[arg(value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Print raw sample chunks
+    #[arg(short, long)]
+    samples: bool,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let mut input: Box<dyn Read> = match &opt.input {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin().lock()),
+    };
+
+    let mut decoder = Decoder::new();
+    let mut buf = [0u8; 4096];
+    let mut nr_frames = 0;
+    let mut nr_errors = 0;
+
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        decoder.feed(&buf[..n], |r| match r {
+            Ok(frame) => {
+                nr_frames += 1;
+                print_frame(&frame, opt.samples);
+            }
+            Err(e) => {
+                nr_errors += 1;
+                eprintln!("error: {}", e);
+            }
+        });
+    }
+
+    eprintln!("{} frames, {} errors", nr_frames, nr_errors);
+    Ok(())
+}
+
+fn print_frame(frame: &Frame, samples: bool) {
+    match frame {
+        Frame::Reading(r) => {
+            let string = r.string.map_or("-".to_string(), |s| (s + 1).to_string());
+            println!(
+                "reading pitch {:.2} Hz, cents {:+.2}, string {}, confidence {:.2}, level {:.1} dB, {:?}",
+                r.pitch, r.cents, string, r.confidence, r.level, r.state
+            );
+        }
+        Frame::Samples(s) => {
+            if samples {
+                println!("samples seq {} {:?}", s.seq, s.samples());
+            } else {
+                println!("samples seq {}, {} samples", s.seq, s.samples().len());
+            }
+        }
+        Frame::Capture(c) => {
+            println!(
+                "capture id {}, {} samples at {} Hz, gain {}, {} bit, timestamp {} ms",
+                c.id, c.length, c.sample_rate, c.gain, c.resolution, c.timestamp
+            );
+        }
+        Frame::Profile(p) => {
+            println!("profile {}: {}", p.name().unwrap_or("custom"), p);
+        }
+    }
+}
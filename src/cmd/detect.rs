@@ -0,0 +1,138 @@
+//! Runs the pitch detectors over a WAV or legacy `data*.txt` capture and
+//! prints one line per analysis frame, followed by a timing summary (on
+//! stderr) for benchmarking the detectors against each other.
+
+use std::{borrow::Cow, path::PathBuf, time::Instant};
+
+use clap::{Args, ValueEnum};
+use guitar_tuner::{
+    fft::{self, Method},
+    filter::Chain,
+    note::{Note, A4},
+    protocol::State,
+    track::{Tracker, MAX_FREQ, MIN_FREQ},
+};
+
+use super::HISTORY;
+
+/// FFT size, ~6 Hz per bin at 48 kHz.
+const FFT_SIZE: usize = 8192;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detector {
+    /// Time-domain tracker (search and track)
+    Track,
+    /// FFT detector (harmonic product spectrum)
+    Fft,
+    /// FFT detector, cepstrum
+    Cepstrum,
+    /// Time-domain tracker seeded by the FFT detector
+    Seeded,
+}
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The WAV or legacy `.txt` capture to analyze
+    #[arg(value_name = "FILE")]
+    input: PathBuf,
+
+    /// Detector to run
+    #[arg(short, long, value_enum, default_value_t = Detector::Track)]
+    detector: Detector,
+
+    /// Pre-processing filters, "none" or stages separated by commas: "dc",
+    /// "hp:HZ", "lp:HZ", "notch:HZ[:Q]", "pre:COEFFICIENT", "hum[:HZ]"
+    #[arg(long, default_value = "none")]
+    filter: Chain,
+
+    /// Sample rate of the detectors in Hz, decimated to from the rate of
+    /// the capture (e.g. 8000 for 48 kHz captures)
+    #[arg(long, value_name = "HZ")]
+    analysis_rate: Option<u32>,
+
+    /// Time between analysis frames in ms
+    #[arg(long, value_name = "MS", default_value_t = 10.0)]
+    hop: f32,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Only print the summary
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let capture = super::load(&opt.input)?;
+    let mut fs = capture.header.sample_rate;
+    let mut samples = capture.normalized();
+    // counted with the detectors, at most as fast as the tracker takes
+    let start = Instant::now();
+    if let (Cow::Owned(decimated), factor) = super::decimate(&samples, fs, opt.analysis_rate)? {
+        samples = decimated;
+        fs /= factor as f32;
+    }
+    let decimation = start.elapsed();
+    super::filter(&opt.filter, fs)?.process(&mut samples);
+    let hop = ((opt.hop / 1000.0 * fs) as usize).max(1);
+
+    let mut tracker = Tracker::<HISTORY>::with_range(fs, MIN_FREQ, MAX_FREQ)?;
+    let mut detector = fft::Detector::<FFT_SIZE>::new();
+    if opt.detector == Detector::Cepstrum {
+        detector.method = Method::Cepstrum;
+    }
+    // one FFT frame of about 100 ms
+    let fft_len = ((0.1 * fs) as usize).clamp(64, FFT_SIZE);
+
+    let mut nr_frames = 0;
+    let mut nr_pitched = 0;
+    let mut elapsed = decimation;
+    for end in (hop..=samples.len()).step_by(hop) {
+        let start = Instant::now();
+        let estimate = match opt.detector {
+            Detector::Track | Detector::Seeded => {
+                tracker.extend(&samples[end - hop..end]);
+                if opt.detector == Detector::Seeded && tracker.state() != State::Track {
+                    if let Some(e) = detector.detect(&samples[end.saturating_sub(fft_len)..end], fs)
+                    {
+                        tracker.seed(e.period(fs));
+                    }
+                }
+                tracker.update();
+                tracker.pitch().map(|p| (p, tracker.confidence()))
+            }
+            Detector::Fft | Detector::Cepstrum => detector
+                .detect(&samples[end.saturating_sub(fft_len)..end], fs)
+                .map(|e| (e.pitch, e.confidence)),
+        };
+        elapsed += start.elapsed();
+        nr_frames += 1;
+
+        if let Some((pitch, confidence)) = estimate {
+            nr_pitched += 1;
+            if !opt.quiet {
+                let (note, cents) = Note::nearest(pitch, opt.a4);
+                println!(
+                    "{:8.3} s  {:8.2} Hz  {:<4} {:+6.1} c  confidence {:.2}",
+                    end as f32 / fs,
+                    pitch,
+                    note.to_string(),
+                    cents,
+                    confidence
+                );
+            }
+        } else if !opt.quiet {
+            println!("{:8.3} s  -", end as f32 / fs);
+        }
+    }
+
+    eprintln!(
+        "{:?}: {} frames, {} pitched, {:.1} us per frame",
+        opt.detector,
+        nr_frames,
+        nr_pitched,
+        elapsed.as_secs_f64() * 1e6 / nr_frames.max(1) as f64
+    );
+    Ok(())
+}
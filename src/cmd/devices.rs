@@ -0,0 +1,182 @@
+//! Lists the audio devices of all available hosts, for selecting them by
+//! `--input-device` and `--output-device` (by index, name, part of a name or
+//! ID), as text or in a machine-readable format (tab separated or JSON).
+
+use std::fmt::Write;
+
+use clap::{Args, ValueEnum};
+use cpal::traits::DeviceTrait;
+
+use super::audio::{self, Direction};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// For reading
+    Text,
+    /// Tab separated values, with a header
+    Tsv,
+    /// JSON array of devices
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Only input or output devices
+    #[arg(long, value_name = "DIRECTION", value_parser = ["input", "output"])]
+    only: Option<String>,
+}
+
+// A device, as listed.
+struct Entry {
+    host: String,
+    direction: Direction,
+    index: usize,
+    name: String,
+    id: String,
+    default: bool,
+    // the default configuration, if available
+    rate: Option<u32>,
+    channels: Option<u16>,
+    format: Option<String>,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let directions = match opt.only.as_deref() {
+        Some("input") => vec![Direction::Input],
+        Some("output") => vec![Direction::Output],
+        _ => vec![Direction::Input, Direction::Output],
+    };
+    let mut entries = vec![];
+    for host_id in cpal::available_hosts() {
+        let host = cpal::host_from_id(host_id)?;
+        for direction in &directions {
+            let default = audio::default_device(&host, *direction).and_then(|d| d.name().ok());
+            for (i, (name, device)) in audio::devices(&host, *direction)?.into_iter().enumerate() {
+                let config = match direction {
+                    Direction::Input => device.default_input_config(),
+                    Direction::Output => device.default_output_config(),
+                }
+                .ok();
+                entries.push(Entry {
+                    host: host_id.name().to_string(),
+                    direction: *direction,
+                    index: i + 1,
+                    id: audio::id(&host, &name),
+                    default: default.as_ref() == Some(&name),
+                    name,
+                    rate: config.as_ref().map(|c| c.sample_rate().0),
+                    channels: config.as_ref().map(|c| c.channels()),
+                    format: config.as_ref().map(|c| c.sample_format().to_string()),
+                });
+            }
+        }
+    }
+
+    let text = match opt.format {
+        Format::Text => text(&entries),
+        Format::Tsv => tsv(&entries),
+        Format::Json => json(&entries),
+    };
+    print!("{}", text);
+    Ok(())
+}
+
+fn text(entries: &[Entry]) -> String {
+    let mut text = String::new();
+    let mut group = None;
+    for entry in entries {
+        if group != Some((&entry.host, entry.direction)) {
+            group = Some((&entry.host, entry.direction));
+            let _ = writeln!(text, "{} {} devices:", entry.host, entry.direction);
+        }
+        let _ = write!(
+            text,
+            "{} {:>2}. \"{}\" ({})",
+            if entry.default { "*" } else { " " },
+            entry.index,
+            entry.name,
+            entry.id
+        );
+        if let (Some(rate), Some(channels), Some(format)) =
+            (entry.rate, entry.channels, &entry.format)
+        {
+            let _ = write!(text, ", {} Hz, {} channels, {}", rate, channels, format);
+        }
+        text.push('\n');
+    }
+    if entries.is_empty() {
+        text += "no devices\n";
+    }
+    text
+}
+
+fn tsv(entries: &[Entry]) -> String {
+    let mut tsv =
+        String::from("host\tdirection\tindex\tid\tname\tdefault\trate\tchannels\tformat\n");
+    let field = |value: Option<String>| value.unwrap_or_default();
+    for entry in entries {
+        let _ = writeln!(
+            tsv,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            entry.host,
+            entry.direction,
+            entry.index,
+            entry.id.replace('\t', " "),
+            entry.name.replace('\t', " "),
+            entry.default,
+            field(entry.rate.map(|r| r.to_string())),
+            field(entry.channels.map(|c| c.to_string())),
+            field(entry.format.clone())
+        );
+    }
+    tsv
+}
+
+fn json(entries: &[Entry]) -> String {
+    let value = |value: Option<String>| value.unwrap_or("null".to_string());
+    let objects: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "  {{\"host\": {}, \"direction\": \"{}\", \"index\": {}, \"id\": {}, \
+                 \"name\": {}, \"default\": {}, \"rate\": {}, \"channels\": {}, \"format\": {}}}",
+                string(&entry.host),
+                entry.direction,
+                entry.index,
+                string(&entry.id),
+                string(&entry.name),
+                entry.default,
+                value(entry.rate.map(|r| r.to_string())),
+                value(entry.channels.map(|c| c.to_string())),
+                value(entry.format.as_deref().map(string))
+            )
+        })
+        .collect();
+    match objects.is_empty() {
+        true => "[]\n".to_string(),
+        false => format!("[\n{}\n]\n", objects.join(",\n")),
+    }
+}
+
+// A JSON string.
+fn string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            '\n' => json += "\\n",
+            '\t' => json += "\\t",
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -0,0 +1,108 @@
+//! Imports raw captures into WAV files (with a `.meta` file holding the
+//! capture metadata) for offline analysis.
+//!
+//! Accepts telemetry streams recorded from the device (e.g. `rtic_capture`),
+//! where each capture is stored as `<OUT>_<id>.wav`, and legacy `data*.txt`
+//! files (one sample per line), stored as `<OUT>.wav`.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use clap::Args;
+use guitar_tuner::{
+    adc::AdcProfile,
+    capture::{Assembler, Capture, LEGACY},
+    protocol::{CaptureHeader, Decoder},
+};
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The telemetry stream or legacy `.txt` capture to import
+    #[arg(value_name = "FILE")]
+    input: PathBuf,
+
+    /// Output path without extension, defaults to the input path
+    #[arg(short, long, value_name = "OUT")]
+    output: Option<PathBuf>,
+
+    /// Treat the input as legacy text capture (default for `.txt` files)
+    #[arg(short, long)]
+    legacy: bool,
+
+    /// Sample rate of legacy captures
+    #[arg(long, value_name = "HZ", default_value_t = LEGACY.sample_rate)]
+    sample_rate: f32,
+
+    /// ADC profile of legacy captures
+    #[arg(long, value_name = "NAME", default_value = "default", value_parser = super::profile::parse_name)]
+    profile: AdcProfile,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let output = opt
+        .output
+        .clone()
+        .unwrap_or_else(|| opt.input.with_extension(""));
+    let legacy = opt.legacy || opt.input.extension().is_some_and(|e| e == "txt");
+
+    if legacy {
+        let text = fs::read_to_string(&opt.input)
+            .with_context(|| format!("failed to read {}", opt.input.display()))?;
+        let header = CaptureHeader {
+            sample_rate: opt.sample_rate,
+            gain: opt.profile.gain.factor(),
+            resolution: opt.profile.resolution.bits(),
+            ..LEGACY
+        };
+        let mut capture = Capture::from_legacy(&text, header)
+            .with_context(|| format!("failed to parse {}", opt.input.display()))?;
+        capture.profile = Some(opt.profile);
+        return store(&capture, output);
+    }
+
+    let bytes =
+        fs::read(&opt.input).with_context(|| format!("failed to read {}", opt.input.display()))?;
+    let mut decoder = Decoder::new();
+    let mut assembler = Assembler::new();
+    let mut captures = vec![];
+    decoder.feed(&bytes, |r| match r {
+        Ok(frame) => captures.extend(assembler.push(&frame)),
+        Err(e) => eprintln!("error: {}", e),
+    });
+    captures.extend(assembler.finish());
+
+    if captures.is_empty() {
+        anyhow::bail!("no captures found in {}", opt.input.display());
+    }
+    for capture in captures {
+        let mut name = output.clone().into_os_string();
+        name.push(format!("_{}", capture.header.id));
+        store(&capture, name.into())?;
+    }
+    Ok(())
+}
+
+fn store(capture: &Capture, output: PathBuf) -> anyhow::Result<()> {
+    let wav = output.with_extension("wav");
+    let meta = output.with_extension("meta");
+    capture
+        .write_wav(&wav)
+        .with_context(|| format!("failed to write {}", wav.display()))?;
+    capture
+        .write_meta(&meta)
+        .with_context(|| format!("failed to write {}", meta.display()))?;
+
+    if capture.dropped > 0 {
+        eprintln!(
+            "warning: capture {} is missing {} samples",
+            capture.header.id, capture.dropped
+        );
+    }
+    println!(
+        "{}: {} samples at {} Hz",
+        wav.display(),
+        capture.samples.len(),
+        capture.header.sample_rate
+    );
+    Ok(())
+}
@@ -0,0 +1,361 @@
+//! Checks the intonation of each string from the open string and the note
+//! fretted at the 12th fret (see `guitar_tuner::intonation`), printing the
+//! saddle adjustments as a setup report.
+//!
+//! Without captures, it guides through the strings on the input device,
+//! prompting for the open string and the 12th fret and measuring each with
+//! the tracker once steady. Ctrl-C ends early, reporting the strings
+//! measured.
+
+use std::{fmt::Write, path::PathBuf, thread, time::Duration};
+
+use anyhow::Context;
+use clap::Args;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use guitar_tuner::{
+    decimate::Decimator,
+    fft::Detector,
+    harmonics::Analyzer,
+    intonation::{Intonation, Saddle, SCALE},
+    note::{cents, A4},
+    protocol::State,
+    track::{Tracker, MAX_FREQ},
+    tuning::{Tuning, MAX_STRINGS},
+};
+use ringbuf::{
+    traits::{Consumer, Producer, Split},
+    HeapCons, HeapRb,
+};
+
+use super::{audio, HISTORY};
+
+/// FFT size of the coarse estimate.
+const FFT_SIZE: usize = 32768;
+
+/// Time between readings of the tracker when guided.
+const HOP: Duration = Duration::from_millis(50);
+
+// The open string and its intonation, per string measured.
+type Strings = [Option<(f32, Intonation)>; MAX_STRINGS];
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// WAV or legacy `.txt` captures, the open string followed by the 12th
+    /// fret for each string (in any order of strings), guided on the input
+    /// device if none
+    #[arg(value_name = "FILE")]
+    inputs: Vec<PathBuf>,
+
+    /// Measure the open string, the harmonic at the 12th fret and the 12th
+    /// fret, the harmonic being the reference
+    #[arg(long)]
+    harmonic: bool,
+
+    /// Tuning, "standard", "drop-d", "bass" or notes, e.g. "D2,A2,D3,G3,A3,D4"
+    #[arg(short, long, default_value = "standard")]
+    tuning: Tuning,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Maximum deviation in cents considered intonated
+    #[arg(long, value_name = "CENTS", default_value_t = 2.0)]
+    tolerance: f32,
+
+    /// Scale length in mm
+    #[arg(long, value_name = "MM", default_value_t = SCALE)]
+    scale: f32,
+
+    /// Save the report to a file
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// The audio host to use, e.g. "alsa" or "jack" (guided)
+    #[arg(long, default_value = "default")]
+    host: String,
+
+    /// The input audio device to use: "default", an index, a name or part of
+    /// it, or an ID (see `devices`) (guided)
+    #[arg(short, long, value_name = "IN", default_value = "default")]
+    input_device: String,
+
+    /// Input channel of the instrument, 1 is the first (guided)
+    #[arg(short, long, default_value_t = 1)]
+    channel: usize,
+
+    /// Noise gate in dBFS (guided)
+    #[arg(long, value_name = "DB", default_value_t = -50.0, allow_hyphen_values = true)]
+    gate: f32,
+
+    /// Time a note is measured for in seconds, once steady (guided)
+    #[arg(long, value_name = "SEC", default_value_t = 1.0)]
+    hold: f32,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let strings = match opt.inputs.is_empty() {
+        true => guided(&opt)?,
+        false => from_files(&opt)?,
+    };
+    report(&opt, &strings)
+}
+
+// Measures the strings from the captures.
+fn from_files(opt: &Opt) -> anyhow::Result<Strings> {
+    let group = if opt.harmonic { 3 } else { 2 };
+    if !opt.inputs.len().is_multiple_of(group) {
+        anyhow::bail!(
+            "expected {} per string, got {} files",
+            if opt.harmonic {
+                "the open string, the harmonic and the 12th fret"
+            } else {
+                "the open string and the 12th fret"
+            },
+            opt.inputs.len()
+        );
+    }
+
+    let mut detector = Box::new(Detector::<FFT_SIZE>::new());
+    // not the octave of a bass string
+    detector.min_freq = super::min_freq(&opt.tuning, opt.a4);
+    let analyzer = Analyzer::new();
+    let mut measure = |path: &PathBuf| -> anyhow::Result<f32> {
+        let capture = super::load(path)?;
+        let fs = capture.header.sample_rate;
+        let samples = capture.normalized();
+        let start = super::after_attack(&samples, fs).min(samples.len());
+        let samples = &samples[start..];
+        let window = ((analyzer.window * fs) as usize).min(samples.len());
+        let estimate = detector
+            .detect(&samples[..window.min(FFT_SIZE)], fs)
+            .with_context(|| format!("{}: no pitch found", path.display()))?;
+        Ok(analyzer.analyze(samples, fs, estimate.pitch).fundamental)
+    };
+
+    let mut strings: Strings = [None; MAX_STRINGS];
+    for files in opt.inputs.chunks(group) {
+        let open = measure(&files[0])?;
+        let fretted = measure(&files[group - 1])?;
+        let intonation = match opt.harmonic {
+            true => Intonation::harmonic(measure(&files[1])?, fretted),
+            false => Intonation::new(open, fretted),
+        };
+        let string = match opt.tuning.classify(open, opt.a4) {
+            Some((string, deviation)) if deviation.abs() <= 100.0 => string,
+            _ => anyhow::bail!(
+                "{}: {:.2} Hz is not close to a string of {}",
+                files[0].display(),
+                open,
+                opt.tuning
+            ),
+        };
+        if strings[string].is_some() {
+            anyhow::bail!(
+                "{}: string {} is captured twice",
+                files[0].display(),
+                string + 1
+            );
+        }
+        strings[string] = Some((open, intonation));
+    }
+    Ok(strings)
+}
+
+// Measures the strings on the input device, one after the other, prompting
+// for each note.
+fn guided(opt: &Opt) -> anyhow::Result<Strings> {
+    audio::handle_interrupts();
+    let host = audio::host(&opt.host)?;
+    let device = audio::input_device(&host, &opt.input_device)?;
+    let config = audio::input_config(&device, None, Some(opt.channel as u16))?;
+    let nr_channels = config.channels() as usize;
+    if !(1..=nr_channels).contains(&opt.channel) {
+        anyhow::bail!(
+            "no channel {}, the input has {} channels",
+            opt.channel,
+            nr_channels
+        );
+    }
+    println!(
+        "Using input device: \"{}\", {}",
+        device.name()?,
+        audio::describe(&config)
+    );
+    let fs = config.sample_rate().0 as f32;
+
+    // from below the lowest string to above the highest 12th fret
+    let min_freq = super::min_freq(&opt.tuning, opt.a4);
+    let max_freq = opt
+        .tuning
+        .range(opt.a4)
+        .map_or(MAX_FREQ, |(_, high)| (4.0 * high).max(MAX_FREQ));
+    let decimator = super::decimator(fs, None, Tracker::<HISTORY>::max_fs(min_freq))?;
+    let analysis_fs = fs / decimator.map_or(1, |d| d.factor()) as f32;
+    let mut tracker = Box::new(Tracker::<HISTORY>::with_range(
+        analysis_fs,
+        min_freq,
+        max_freq,
+    )?);
+    tracker.gate = opt.gate;
+
+    let (mut producer, consumer) = HeapRb::<f32>::new(fs as usize).split();
+    let channel = opt.channel - 1;
+    let input_data_fn = move |data: &[f32], _: &cpal::InputCallbackInfo| {
+        for frame in data.chunks(nr_channels) {
+            // dropped if the tracker fell behind
+            let _ = producer.try_push(frame[channel]);
+        }
+    };
+    let stream = audio::build_input_stream(&device, &config, input_data_fn, audio::err_fn)?;
+    stream.play()?;
+
+    let steps: &[&str] = match opt.harmonic {
+        true => &["open string", "harmonic at the 12th fret", "12th fret"],
+        false => &["open string", "12th fret"],
+    };
+    let mut input = Input {
+        consumer,
+        decimator,
+        tracker,
+        buf: vec![0.0; fs as usize],
+        decimated: vec![],
+    };
+    let mut strings: Strings = [None; MAX_STRINGS];
+    let mut measured = false;
+    for (s, note) in opt.tuning.strings().iter().enumerate() {
+        let target = opt.tuning.target(s, opt.a4);
+        let mut pitches = vec![];
+        for (step, name) in steps.iter().enumerate() {
+            // each note plucked anew, not the last one ringing
+            if measured && !input.wait_silence() {
+                return Ok(strings);
+            }
+            // an octave up but the open string
+            let expected = if step == 0 { target } else { 2.0 * target };
+            println!("string {} ({}): play the {}", s + 1, note, name);
+            let Some(pitch) = input.measure(expected, opt.hold) else {
+                return Ok(strings);
+            };
+            println!("  {:.2} Hz", pitch);
+            pitches.push(pitch);
+            measured = true;
+        }
+        let intonation = match opt.harmonic {
+            true => Intonation::harmonic(pitches[1], pitches[2]),
+            false => Intonation::new(pitches[0], pitches[1]),
+        };
+        println!("  12th fret {:+.1} c", intonation.cents());
+        strings[s] = Some((pitches[0], intonation));
+    }
+    Ok(strings)
+}
+
+// The input of the guided mode, feeding the tracker.
+struct Input {
+    consumer: HeapCons<f32>,
+    decimator: Option<Decimator>,
+    tracker: Box<Tracker<HISTORY>>,
+    buf: Vec<f32>,
+    decimated: Vec<f32>,
+}
+
+impl Input {
+    // Feeds the samples received and updates the tracker, None once
+    // interrupted.
+    fn update(&mut self) -> Option<State> {
+        thread::sleep(HOP);
+        if audio::interrupted() {
+            return None;
+        }
+        let n = self.consumer.pop_slice(&mut self.buf);
+        let samples = match &mut self.decimator {
+            Some(decimator) => {
+                self.decimated.clear();
+                let decimated = &mut self.decimated;
+                decimator.process(&self.buf[..n], |x| decimated.push(x));
+                &self.decimated[..]
+            }
+            None => &self.buf[..n],
+        };
+        self.tracker.extend(samples);
+        Some(self.tracker.update())
+    }
+
+    // The median pitch over `hold` seconds of a note within a semitone of
+    // `expected`, None once interrupted.
+    fn measure(&mut self, expected: f32, hold: f32) -> Option<f32> {
+        let mut pitches = vec![];
+        while pitches.is_empty() || (pitches.len() as f32) * HOP.as_secs_f32() < hold {
+            self.update()?;
+            match self.tracker.pitch() {
+                Some(pitch) if cents(pitch, expected).abs() <= 100.0 => pitches.push(pitch),
+                // steady from the start again
+                _ => pitches.clear(),
+            }
+        }
+        pitches.sort_by(f32::total_cmp);
+        Some(pitches[pitches.len() / 2])
+    }
+
+    // Waits for the string to be muted (or to decay below the gate), false
+    // once interrupted.
+    fn wait_silence(&mut self) -> bool {
+        let mut prompted = false;
+        while let Some(state) = self.update() {
+            if state != State::Track {
+                return true;
+            }
+            if !prompted {
+                println!("  mute the string");
+                prompted = true;
+            }
+        }
+        false
+    }
+}
+
+// Prints the report of the `strings` measured, saving it if asked to.
+fn report(opt: &Opt, strings: &Strings) -> anyhow::Result<()> {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "intonation, tuning {}, scale {} mm, {} as reference",
+        opt.tuning,
+        opt.scale,
+        if opt.harmonic { "harmonic" } else { "octave" }
+    )?;
+    writeln!(
+        report,
+        "string  note  open Hz  12th Hz   cents   open c  saddle"
+    )?;
+    for (s, note) in opt.tuning.strings().iter().enumerate() {
+        let Some((open, intonation)) = strings[s] else {
+            writeln!(report, "{:>6}  {:<4}  -", s + 1, note.to_string())?;
+            continue;
+        };
+        let saddle = match intonation.saddle(opt.tolerance) {
+            Saddle::InTune => "ok".to_string(),
+            Saddle::Forward => format!("forward {:.2} mm", -intonation.distance(opt.scale)),
+            Saddle::Back => format!("back {:.2} mm", intonation.distance(opt.scale)),
+        };
+        writeln!(
+            report,
+            "{:>6}  {:<4}  {:>7.2}  {:>7.2}  {:>+6.1}  {:>+7.1}  {}",
+            s + 1,
+            note.to_string(),
+            open,
+            intonation.fretted,
+            intonation.cents(),
+            cents(open, opt.tuning.target(s, opt.a4)),
+            saddle
+        )?;
+    }
+
+    print!("{}", report);
+    if let Some(path) = &opt.report {
+        std::fs::write(path, &report)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
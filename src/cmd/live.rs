@@ -0,0 +1,498 @@
+//! Tunes live from the input device, running a tracker (see
+//! `guitar_tuner::track`) per selected input channel, so several
+//! instruments on a multi-input interface are tuned at once.
+//!
+//! When the device is lost (e.g., a USB interface unplugged) or the stream
+//! stalls, the stream is opened again, on the default device if the
+//! selected one is gone, until it returns. Ctrl-C quits.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::{Args, ValueEnum};
+use cpal::{
+    traits::{DeviceTrait, StreamTrait},
+    Host, Stream, StreamError,
+};
+use guitar_tuner::{
+    decimate::Decimator,
+    filter::{Chain, Filter},
+    note::{Note, A4},
+    protocol::State,
+    resample::Resampler,
+    temperament::Temperament,
+    track::{Tracker, MAX_FREQ, MIN_FREQ},
+    tuning::Tuning,
+};
+use ringbuf::{
+    traits::{Consumer, Producer, Split},
+    HeapCons, HeapRb,
+};
+
+use super::{audio, HISTORY};
+
+/// Input channels, 1 is the first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Channels {
+    All,
+    List(Vec<usize>),
+}
+
+impl Channels {
+    /// The channels (from 0) of `n` channels.
+    fn select(&self, n: usize) -> anyhow::Result<Vec<usize>> {
+        match self {
+            Channels::All => Ok((0..n).collect()),
+            Channels::List(list) => list
+                .iter()
+                .map(|c| match c {
+                    1.. if *c <= n => Ok(c - 1),
+                    _ => anyhow::bail!("no channel {}, the input has {} channels", c, n),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for Channels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            return Ok(Channels::All);
+        }
+        s.split(',')
+            .map(|c| {
+                c.trim()
+                    .parse()
+                    .map_err(|_| format!("invalid channel \"{}\"", c))
+            })
+            .collect::<Result<_, _>>()
+            .map(Channels::List)
+    }
+}
+
+impl fmt::Display for Channels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channels::All => write!(f, "all"),
+            Channels::List(list) => {
+                let list: Vec<String> = list.iter().map(|c| c.to_string()).collect();
+                write!(f, "{}", list.join(","))
+            }
+        }
+    }
+}
+
+/// Readings shown.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Display {
+    /// Note and deviation in cents
+    Cents,
+    /// A meter of the deviation as well
+    Meter,
+}
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The audio host to use, e.g. "alsa" or "jack"
+    #[arg(long, default_value = "default")]
+    host: String,
+
+    /// The input audio device to use: "default", an index, a name or part of
+    /// it, or an ID (see `devices`)
+    #[arg(short, long, value_name = "IN", default_value = "default")]
+    input_device: String,
+
+    /// Input channels to tune, e.g. "1,3" or "all"
+    #[arg(short, long, default_value = "1")]
+    channels: Channels,
+
+    /// Tuning per channel, "standard", "drop-d", "bass" or notes, e.g.
+    /// "D2,A2,D3,G3,A3,D4", the last one for the remaining channels
+    #[arg(short, long, default_value = "standard")]
+    tuning: Vec<Tuning>,
+
+    /// Temperament, "equal", "just", "pythagorean", "meantone" or
+    /// "werckmeister", optionally with a key, e.g. "just:G"
+    #[arg(long, default_value = "equal")]
+    temperament: Temperament,
+
+    /// Fret of the capo per channel, e.g. "0,2", the last one for the
+    /// remaining channels
+    #[arg(
+        long,
+        value_name = "FRET,..",
+        value_delimiter = ',',
+        default_value = "0"
+    )]
+    capo: Vec<u8>,
+
+    /// Transposition in semitones per channel, e.g. -1 for a band tuned a
+    /// semitone down, the last one for the remaining channels
+    #[arg(
+        long,
+        value_name = "SEMITONES,..",
+        value_delimiter = ',',
+        default_value = "0",
+        allow_hyphen_values = true
+    )]
+    transpose: Vec<i8>,
+
+    /// Transposition in cents per channel, e.g. -50 for a quarter tone flat,
+    /// the last one for the remaining channels
+    #[arg(
+        long,
+        value_name = "CENTS,..",
+        value_delimiter = ',',
+        default_value = "0",
+        allow_hyphen_values = true
+    )]
+    detune: Vec<f32>,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Noise gate in dBFS
+    #[arg(long, value_name = "DB", default_value_t = -50.0, allow_hyphen_values = true)]
+    gate: f32,
+
+    /// Pre-processing filters, "none" or stages separated by commas: "dc",
+    /// "hp:HZ", "lp:HZ", "notch:HZ[:Q]", "pre:COEFFICIENT", "hum[:HZ]"
+    #[arg(long, default_value = "none")]
+    filter: Chain,
+
+    /// Sample rate in Hz, resampled if the device does not support it,
+    /// defaults to the rate of the device
+    #[arg(long, value_name = "HZ")]
+    rate: Option<u32>,
+
+    /// Sample rate of the tracker in Hz, decimated to from the (resampled)
+    /// rate of the device, e.g. 8000 to save CPU
+    #[arg(long, value_name = "HZ")]
+    analysis_rate: Option<u32>,
+
+    /// Time between readings in ms
+    #[arg(
+        long,
+        value_name = "MS",
+        default_value_t = 50.0,
+        value_parser = super::parse_positive
+    )]
+    hop: f32,
+
+    /// Readings shown
+    #[arg(long, value_enum, default_value_t = Display::Cents)]
+    display: Display,
+
+    /// Decimals of the deviation in cents
+    #[arg(long, value_name = "DIGITS", default_value_t = 1)]
+    precision: usize,
+
+    /// Tune for this long and quit
+    #[arg(long, value_name = "SEC")]
+    duration: Option<f32>,
+}
+
+// A tuned channel.
+struct Channel {
+    index: usize,
+    tuning: Tuning,
+    tracker: Box<Tracker<HISTORY>>,
+    decimator: Option<Decimator>,
+    filter: Filter,
+    consumer: HeapCons<f32>,
+}
+
+// Why tuning stopped.
+enum End {
+    /// Interrupted or done.
+    Quit,
+    /// The stream failed.
+    Failed(String),
+    /// The selected device is back (when on the default device).
+    Back,
+}
+
+/// Time without samples until the stream is considered stalled.
+const STALLED: Duration = Duration::from_secs(2);
+/// Time between checks for the selected device to return.
+const RETURN: Duration = Duration::from_secs(5);
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    audio::handle_interrupts();
+    let host = audio::host(&opt.host)?;
+    let start = Instant::now();
+    // on the default device, until the selected one returns
+    let mut fallback = false;
+    let mut opened = false;
+    while !audio::interrupted() {
+        let name = if fallback {
+            "default"
+        } else {
+            &opt.input_device
+        };
+        let (stream, mut channels, failed) = match open(&host, name, &opt) {
+            Ok(session) => session,
+            // give up if it never worked
+            Err(e) if !opened => return Err(e),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                if !fallback && opt.input_device != "default" {
+                    eprintln!("falling back to the default device");
+                    fallback = true;
+                } else {
+                    // the default failed as well, the selected one may be back
+                    fallback = false;
+                    thread::sleep(Duration::from_secs(1));
+                }
+                continue;
+            }
+        };
+        opened = true;
+        match tune(&opt, &host, &mut channels, &failed, start, fallback)? {
+            End::Quit => break,
+            End::Failed(reason) => {
+                eprintln!("\n{}, reopening the stream", reason);
+                drop(stream);
+                thread::sleep(Duration::from_millis(500));
+            }
+            End::Back => {
+                println!("\n\"{}\" is back", opt.input_device);
+                fallback = false;
+            }
+        }
+    }
+    println!();
+    Ok(())
+}
+
+// Opens the stream, returning it with the channels to tune and the failure
+// flag set by stream errors.
+fn open(
+    host: &Host,
+    name: &str,
+    opt: &Opt,
+) -> anyhow::Result<(Stream, Vec<Channel>, Arc<AtomicBool>)> {
+    let device = audio::input_device(host, name)?;
+    let channels = match &opt.channels {
+        Channels::All => u16::MAX,
+        Channels::List(list) => list.iter().max().map_or(1, |c| *c as u16),
+    };
+    let config = audio::input_config(&device, opt.rate, Some(channels))?;
+    let nr_channels = config.channels() as usize;
+    let selected = opt.channels.select(nr_channels)?;
+    println!(
+        "Using input device: \"{}\", {}",
+        device.name()?,
+        audio::describe(&config)
+    );
+    let device_fs = config.sample_rate().0 as f32;
+    let fs = opt.rate.map_or(device_fs, |r| r as f32);
+    if fs != device_fs {
+        println!("resampled to {} Hz", fs);
+    }
+    // decimated if too fast for the tracker of the lowest tuning
+    let tunings: Vec<Tuning> = (0..selected.len())
+        .map(|i| {
+            nth(&opt.tuning, i)
+                .with_temperament(opt.temperament)
+                .with_capo(nth(&opt.capo, i))
+                .transposed(nth(&opt.transpose, i), nth(&opt.detune, i))
+        })
+        .collect();
+    let min_freq = tunings
+        .iter()
+        .map(|t| range(t, opt.a4).0)
+        .fold(MIN_FREQ, f32::min);
+    let max_fs = Tracker::<HISTORY>::max_fs(min_freq);
+    let decimator = super::decimator(fs, opt.analysis_rate, max_fs)?;
+    let analysis_fs = fs / decimator.map_or(1, |d| d.factor()) as f32;
+    if decimator.is_some() {
+        println!("analyzed at {} Hz", analysis_fs);
+    }
+
+    // a buffer per channel, from the stream to the trackers
+    let mut producers = vec![];
+    let mut channels = vec![];
+    for (index, tuning) in selected.iter().zip(tunings) {
+        let (producer, consumer) = HeapRb::<f32>::new(fs as usize).split();
+        producers.push((*index, Resampler::new(device_fs, fs), producer));
+        let (min_freq, max_freq) = range(&tuning, opt.a4);
+        let mut tracker = Box::new(Tracker::with_range(analysis_fs, min_freq, max_freq)?);
+        tracker.gate = opt.gate;
+        println!("channel {}: {}{}", index + 1, tuning, super::shift(&tuning));
+        channels.push(Channel {
+            index: *index,
+            tuning,
+            tracker,
+            decimator,
+            filter: super::filter(&opt.filter, analysis_fs)?,
+            consumer,
+        });
+    }
+
+    let mut samples = Vec::new();
+    let input_data_fn = move |data: &[f32], _: &cpal::InputCallbackInfo| {
+        for (index, resampler, producer) in &mut producers {
+            samples.clear();
+            samples.extend(data.chunks(nr_channels).map(|frame| frame[*index]));
+            // dropped if the trackers fell behind
+            match device_fs == fs {
+                true => {
+                    producer.push_slice(&samples);
+                }
+                false => resampler.process(&samples, |x| {
+                    let _ = producer.try_push(x);
+                }),
+            }
+        }
+    };
+    // a lost device fails the stream, other errors (e.g., overruns) only
+    // if it stalls
+    let failed = Arc::new(AtomicBool::new(false));
+    let error_fn = {
+        let failed = failed.clone();
+        move |err: StreamError| {
+            eprintln!("\nan error occurred on stream: {}", err);
+            if let StreamError::DeviceNotAvailable = err {
+                failed.store(true, Ordering::Relaxed);
+            }
+        }
+    };
+    let stream = audio::build_input_stream(&device, &config, input_data_fn, error_fn)?;
+    stream.play()?;
+    Ok((stream, channels, failed))
+}
+
+// The option of the `i`th channel tuned, the last one for the remaining
+// channels.
+fn nth<T: Copy>(values: &[T], i: usize) -> T {
+    values[i.min(values.len() - 1)]
+}
+
+// Tunes until interrupted, done or the stream fails.
+fn tune(
+    opt: &Opt,
+    host: &Host,
+    channels: &mut [Channel],
+    failed: &AtomicBool,
+    start: Instant,
+    fallback: bool,
+) -> anyhow::Result<End> {
+    let hop = Duration::try_from_secs_f32(opt.hop / 1000.0)?;
+    let mut buf = vec![0.0; 96000];
+    let mut decimated = Vec::with_capacity(buf.len());
+    let mut last_samples = Instant::now();
+    let mut last_check = Instant::now();
+    loop {
+        if audio::interrupted()
+            || opt
+                .duration
+                .is_some_and(|d| start.elapsed().as_secs_f32() >= d)
+        {
+            return Ok(End::Quit);
+        }
+        if failed.load(Ordering::Relaxed) {
+            return Ok(End::Failed("the device is not available".to_string()));
+        }
+        if last_samples.elapsed() > STALLED {
+            return Ok(End::Failed("the stream stalled".to_string()));
+        }
+        if fallback && last_check.elapsed() > RETURN {
+            last_check = Instant::now();
+            if audio::input_device(host, &opt.input_device).is_ok() {
+                return Ok(End::Back);
+            }
+        }
+
+        thread::sleep(hop);
+        let mut line = String::new();
+        for channel in channels.iter_mut() {
+            let n = channel.consumer.pop_slice(&mut buf);
+            if n > 0 {
+                last_samples = Instant::now();
+            }
+            let samples = match &mut channel.decimator {
+                Some(decimator) => {
+                    decimated.clear();
+                    decimator.process(&buf[..n], |x| decimated.push(x));
+                    &mut decimated[..]
+                }
+                None => &mut buf[..n],
+            };
+            channel.filter.process(samples);
+            channel.tracker.extend(samples);
+            channel.tracker.update();
+            line += &format!(
+                "  {}: {}",
+                channel.index + 1,
+                status(&channel.tracker, &channel.tuning, opt)
+            );
+        }
+        print!("\r{}\x1b[K", line.trim_start());
+        io::stdout().flush()?;
+    }
+}
+
+/// The pitch range tracked for `tuning`, from a fourth below the lowest
+/// string to an octave above the highest, at least the default range.
+pub fn range(tuning: &Tuning, a4: f32) -> (f32, f32) {
+    let high = tuning.range(a4).map_or(MAX_FREQ, |(_, high)| 2.0 * high);
+    (super::min_freq(tuning, a4), high.max(MAX_FREQ))
+}
+
+// The reading of a tracker, as the string and deviation.
+fn status(tracker: &Tracker<HISTORY>, tuning: &Tuning, opt: &Opt) -> String {
+    let reading = tracker.reading(tuning, opt.a4);
+    let width = match opt.display {
+        Display::Cents => 0,
+        Display::Meter => METER + 3,
+    };
+    if reading.state != State::Track {
+        return format!("{:<1$}", "-", 24 + opt.precision + width);
+    }
+    let note = match reading.string {
+        Some(s) => format!("{} ({})", tuning.note(s as usize), s + 1),
+        None => Note::nearest(reading.pitch, opt.a4).0.to_string(),
+    };
+    let mut status = format!(
+        "{:<8} {:>+3$.4$} c {:>5.0} dB",
+        note,
+        reading.cents,
+        reading.level,
+        4 + opt.precision,
+        opt.precision
+    );
+    if opt.display == Display::Meter {
+        status += &format!(" [{}]", meter(reading.cents));
+    }
+    status
+}
+
+/// Width of the meter, ±50 cents.
+const METER: usize = 21;
+
+// A meter of `cents`, in tune at the center.
+fn meter(cents: f32) -> String {
+    let center = METER / 2;
+    let position = (center as f32 + cents / 50.0 * center as f32).round();
+    let position = position.clamp(0.0, (METER - 1) as f32) as usize;
+    (0..METER)
+        .map(|i| match i {
+            _ if i == position => '|',
+            _ if i == center => '+',
+            _ => '-',
+        })
+        .collect()
+}
@@ -0,0 +1,436 @@
+//! Passes the input through to the output, stomp box style, muting the
+//! output while tuning (see `guitar_tuner::mute`). The input is tracked
+//! (see `guitar_tuner::track`), the reading shown while muted.
+//!
+//! The mute is toggled from stdin (an empty line or `m`, `a` toggles the
+//! auto mute, `q` quits), by a MIDI control change on a raw MIDI port
+//! (e.g., `/dev/snd/midiC1D0`), or by OSC messages over UDP: `/mute` and
+//! `/auto` with a switch argument (or none to toggle).
+
+use std::{
+    fs::File,
+    io::{self, BufRead, Read, Write},
+    net::UdpSocket,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::Context;
+use clap::Args;
+use cpal::traits::{DeviceTrait, StreamTrait};
+use guitar_tuner::{
+    decimate::Decimator,
+    latency::Latency,
+    midi::{Message, Parser},
+    mute::Mute,
+    note::{Note, A4},
+    osc,
+    protocol::State,
+    resample::Resampler,
+    temperament::Temperament,
+    track::Tracker,
+    tuning::Tuning,
+};
+use ringbuf::{
+    traits::{Consumer, Observer, Producer, Split},
+    HeapCons, HeapRb,
+};
+
+use super::{audio, HISTORY};
+
+/// Time between readings in ms.
+const HOP: Duration = Duration::from_millis(50);
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The audio host to use, e.g. "alsa" or "jack"
+    #[arg(long, default_value = "default")]
+    host: String,
+
+    /// The input audio device to use: "default", an index, a name or part of
+    /// it, or an ID (see `devices`)
+    #[arg(short, long, value_name = "IN", default_value = "default")]
+    input_device: String,
+
+    /// The output audio device to use: "default", an index, a name or part
+    /// of it, or an ID (see `devices`)
+    #[arg(short, long, value_name = "OUT", default_value = "default")]
+    output_device: String,
+
+    /// Delay between input and output in ms, initially (adapted to the
+    /// lowest safe delay) or fixed
+    #[arg(short, long, value_name = "MS", default_value_t = 150.0)]
+    latency: f32,
+
+    /// Keep the delay fixed
+    #[arg(long)]
+    fixed: bool,
+
+    /// Smallest safety margin of the adapted delay in ms
+    #[arg(long, value_name = "MS", default_value_t = 2.0)]
+    margin: f32,
+
+    /// Start muted
+    #[arg(short, long)]
+    muted: bool,
+
+    /// Mute automatically when a string is plucked, until quiet
+    #[arg(short, long)]
+    auto: bool,
+
+    /// Time quiet until unmuted in seconds (auto mute)
+    #[arg(long, value_name = "SEC", default_value_t = 1.0)]
+    hold: f32,
+
+    /// Noise gate in dBFS (auto mute)
+    #[arg(long, value_name = "DB", default_value_t = -50.0, allow_hyphen_values = true)]
+    gate: f32,
+
+    /// Fade time in ms
+    #[arg(long, value_name = "MS", default_value_t = 20.0)]
+    fade: f32,
+
+    /// Raw MIDI port to read, e.g. /dev/snd/midiC1D0
+    #[arg(long, value_name = "PORT")]
+    midi: Option<PathBuf>,
+
+    /// MIDI control muting (values 64..=127) and unmuting (0..=63)
+    #[arg(long, value_name = "CC", default_value_t = 64)]
+    midi_cc: u8,
+
+    /// UDP address to receive OSC messages on, e.g. 0.0.0.0:9000
+    #[arg(long, value_name = "ADDR")]
+    osc: Option<String>,
+
+    /// Tuning, "standard", "drop-d", "bass" or notes, e.g. "D2,A2,D3,G3,A3,D4"
+    #[arg(short, long, default_value = "standard")]
+    tuning: Tuning,
+
+    /// Temperament, "equal", "just", "pythagorean", "meantone" or
+    /// "werckmeister", optionally with a key, e.g. "just:G"
+    #[arg(long, default_value = "equal")]
+    temperament: Temperament,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+}
+
+// Commands to the input stream.
+enum Control {
+    Mute(bool),
+    Toggle,
+    Auto(bool),
+    ToggleAuto,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let host = audio::host(&opt.host)?;
+    let input_device = audio::input_device(&host, &opt.input_device)?;
+    let output_device = audio::output_device(&host, &opt.output_device)?;
+    let input_config = audio::input_config(&input_device, None, None)?;
+    // at the input rate if possible, else resampled
+    let output_config = audio::output_config(&output_device, Some(input_config.sample_rate().0))?;
+    println!(
+        "Using input device: \"{}\", {}",
+        input_device.name()?,
+        audio::describe(&input_config)
+    );
+    println!(
+        "Using output device: \"{}\", {}",
+        output_device.name()?,
+        audio::describe(&output_config)
+    );
+    let fs = output_config.sample_rate().0 as f32;
+    let in_channels = input_config.channels() as usize;
+    let out_channels = output_config.channels() as usize;
+    let mut resampler = Resampler::new(input_config.sample_rate().0 as f32, fs);
+    let resample = input_config.sample_rate() != output_config.sample_rate();
+
+    // the first input channel, delayed by the latency
+    let latency = (opt.latency / 1000.0 * fs) as usize;
+    let ring = HeapRb::<f32>::new((latency * 2).max(fs as usize));
+    let (mut producer, mut consumer) = ring.split();
+    for _ in 0..latency {
+        producer.try_push(0.0).unwrap();
+    }
+
+    // the input tracked, at a rate the tracker takes
+    let input_fs = input_config.sample_rate().0 as f32;
+    let tuning = opt.tuning.with_temperament(opt.temperament);
+    let (min_freq, max_freq) = super::live::range(&tuning, opt.a4);
+    let decimator = super::decimator(input_fs, None, Tracker::<HISTORY>::max_fs(min_freq))?;
+    let analysis_fs = input_fs / decimator.map_or(1, |d| d.factor()) as f32;
+    let mut tracker = Box::new(Tracker::<HISTORY>::with_range(
+        analysis_fs,
+        min_freq,
+        max_freq,
+    )?);
+    tracker.gate = opt.gate;
+    let (mut tracked, tracked_consumer) = HeapRb::<f32>::new(input_fs as usize).split();
+    let muted = Arc::new(AtomicBool::new(opt.muted));
+
+    let mut mute = Mute::new(input_fs);
+    mute.set_muted(opt.muted);
+    mute.auto = opt.auto;
+    mute.hold = opt.hold;
+    mute.gate = opt.gate;
+    mute.fade = opt.fade / 1000.0;
+
+    // latencies of the input device, the buffer and the output device in us
+    let report = Arc::new([const { AtomicU32::new(0) }; 3]);
+    let set = {
+        let report = report.clone();
+        move |i: usize, d: Option<Duration>| {
+            if let Some(d) = d {
+                report[i].store(d.as_micros() as u32, Ordering::Relaxed);
+            }
+        }
+    };
+    let set_output = set.clone();
+
+    let (sender, receiver) = mpsc::channel();
+    let mut mono = Vec::new();
+    let muted_input = muted.clone();
+    let input_data_fn = move |data: &[f32], info: &cpal::InputCallbackInfo| {
+        let t = info.timestamp();
+        set(0, t.callback.duration_since(&t.capture));
+        while let Ok(control) = receiver.try_recv() {
+            match control {
+                Control::Mute(muted) => mute.set_muted(muted),
+                Control::Toggle => mute.toggle(),
+                Control::Auto(auto) => mute.auto = auto,
+                Control::ToggleAuto => mute.auto = !mute.auto,
+            }
+        }
+        mono.clear();
+        mono.extend(data.chunks(in_channels).map(|frame| frame[0]));
+        // dropped if the tracker fell behind
+        tracked.push_slice(&mono);
+        mute.process(&mut mono);
+        muted_input.store(mute.is_muted(), Ordering::Relaxed);
+        let pushed = match resample {
+            true => {
+                let mut pushed = true;
+                resampler.process(&mono, |x| pushed &= producer.try_push(x).is_ok());
+                pushed
+            }
+            false => producer.push_slice(&mono) == mono.len(),
+        };
+        if !pushed {
+            eprintln!("output stream fell behind: try increasing latency");
+        }
+    };
+    let mut latency = Latency::new(fs);
+    latency.min_margin = opt.margin / 1000.0;
+    let fixed = opt.fixed;
+    // read at `ratio`, interpolating between `prev` and `next`
+    let (mut prev, mut next, mut position) = (0.0, 0.0, 0.0);
+    let output_data_fn = move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+        let t = info.timestamp();
+        set_output(2, t.playback.duration_since(&t.callback));
+        let frames = data.len() / out_channels;
+        let ratio = match fixed {
+            true => 1.0,
+            false => latency.update(consumer.occupied_len(), frames),
+        };
+        set_output(1, Some(Duration::from_secs_f32(latency.latency())));
+
+        let mut input_fell_behind = false;
+        for frame in data.chunks_mut(out_channels) {
+            position += ratio;
+            while position >= 1.0 {
+                position -= 1.0;
+                prev = next;
+                next = consumer.try_pop().unwrap_or_else(|| {
+                    input_fell_behind = true;
+                    0.0
+                });
+            }
+            frame.fill(prev + (next - prev) * position);
+        }
+        if input_fell_behind {
+            latency.underrun();
+            if fixed {
+                eprintln!("input stream fell behind: try increasing latency");
+            }
+        }
+    };
+
+    let input_stream =
+        audio::build_input_stream(&input_device, &input_config, input_data_fn, audio::err_fn)?;
+    let output_stream = audio::build_output_stream(
+        &output_device,
+        &output_config,
+        output_data_fn,
+        audio::err_fn,
+    )?;
+    input_stream.play()?;
+    output_stream.play()?;
+
+    if let Some(path) = &opt.midi {
+        let port =
+            File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let (sender, cc) = (sender.clone(), opt.midi_cc);
+        thread::spawn(move || midi(port, cc, sender));
+    }
+    if let Some(addr) = &opt.osc {
+        let socket = UdpSocket::bind(addr).with_context(|| format!("failed to bind {}", addr))?;
+        let sender = sender.clone();
+        thread::spawn(move || osc(socket, sender));
+    }
+
+    if !opt.fixed {
+        thread::spawn(move || print_latency(&report));
+    }
+    let a4 = opt.a4;
+    thread::spawn(move || track(tracked_consumer, decimator, tracker, tuning, a4, &muted));
+
+    println!(
+        "{}, enter to toggle the mute, a to toggle the auto mute, q to quit",
+        if opt.muted { "muted" } else { "bypassed" }
+    );
+    for line in io::stdin().lock().lines() {
+        let control = match line?.trim() {
+            "q" => return Ok(()),
+            "" | "m" => Control::Toggle,
+            "a" => Control::ToggleAuto,
+            s => {
+                eprintln!("unknown command \"{}\"", s);
+                continue;
+            }
+        };
+        if sender.send(control).is_err() {
+            return Ok(());
+        }
+    }
+    // no stdin (e.g., run as a service), controlled by MIDI or OSC only
+    if opt.midi.is_some() || opt.osc.is_some() {
+        loop {
+            thread::park();
+        }
+    }
+    Ok(())
+}
+
+// Reads control changes from a raw MIDI port.
+fn midi(mut port: File, cc: u8, sender: Sender<Control>) {
+    let mut parser = Parser::new();
+    let mut buf = [0; 64];
+    loop {
+        let n = match port.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                eprintln!("MIDI port: {}", e);
+                return;
+            }
+        };
+        for byte in &buf[..n] {
+            if let Some(Message::ControlChange { control, value, .. }) = parser.feed(*byte) {
+                if control == cc && sender.send(Control::Mute(value >= 64)).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Receives OSC messages.
+fn osc(socket: UdpSocket, sender: Sender<Control>) {
+    let mut buf = [0; 1024];
+    loop {
+        let n = match socket.recv(&mut buf) {
+            Ok(n) => n,
+            Err(e) => {
+                eprintln!("OSC: {}", e);
+                return;
+            }
+        };
+        let Some(message) = osc::parse(&buf[..n]) else {
+            continue;
+        };
+        let control = match (message.address, message.arg) {
+            ("/mute", Some(arg)) => Control::Mute(arg.on()),
+            ("/mute", None) => Control::Toggle,
+            ("/auto", Some(arg)) => Control::Auto(arg.on()),
+            ("/auto", None) => Control::ToggleAuto,
+            _ => continue,
+        };
+        if sender.send(control).is_err() {
+            return;
+        }
+    }
+}
+
+// Tracks the input, printing the reading while muted.
+fn track(
+    mut consumer: HeapCons<f32>,
+    mut decimator: Option<Decimator>,
+    mut tracker: Box<Tracker<HISTORY>>,
+    tuning: Tuning,
+    a4: f32,
+    muted: &AtomicBool,
+) {
+    let mut buf = vec![0.0; consumer.capacity().get()];
+    let mut decimated = Vec::with_capacity(buf.len());
+    let mut shown = false;
+    loop {
+        thread::sleep(HOP);
+        let n = consumer.pop_slice(&mut buf);
+        let samples = match &mut decimator {
+            Some(decimator) => {
+                decimated.clear();
+                decimator.process(&buf[..n], |x| decimated.push(x));
+                &decimated[..]
+            }
+            None => &buf[..n],
+        };
+        tracker.extend(samples);
+        tracker.update();
+        if !muted.load(Ordering::Relaxed) {
+            // cleared once unmuted
+            if shown {
+                print!("\r\x1b[K");
+                let _ = io::stdout().flush();
+                shown = false;
+            }
+            continue;
+        }
+        let reading = tracker.reading(&tuning, a4);
+        let note = match reading.string {
+            Some(s) => format!("{} ({})", tuning.note(s as usize), s + 1),
+            None => Note::nearest(reading.pitch, a4).0.to_string(),
+        };
+        match reading.state {
+            State::Track => print!("\rmuted  {:<8} {:>+6.1} c\x1b[K", note, reading.cents),
+            _ => print!("\rmuted  -\x1b[K"),
+        }
+        let _ = io::stdout().flush();
+        shown = true;
+    }
+}
+
+// Prints the round trip latency as it changes.
+fn print_latency(report: &[AtomicU32; 3]) {
+    let mut last = 0;
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let [input, buffer, output] = report.each_ref().map(|a| a.load(Ordering::Relaxed) / 1000);
+        let total = input + buffer + output;
+        if total.abs_diff(last) >= 1 {
+            println!(
+                "latency {} ms (input {} ms, buffer {} ms, output {} ms)",
+                total, input, buffer, output
+            );
+            last = total;
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Minimal SVG line and scatter plots for the analysis commands, replacing
+//! the Octave figures.
+
+use std::{fmt::Write, path::Path};
+
+use anyhow::Context;
+
+const WIDTH: f32 = 800.0;
+const HEIGHT: f32 = 400.0;
+// margins left, right, top, bottom
+const LEFT: f32 = 70.0;
+const RIGHT: f32 = 20.0;
+const TOP: f32 = 40.0;
+const BOTTOM: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Line,
+    Points,
+}
+
+#[derive(Debug)]
+struct Series {
+    label: String,
+    color: &'static str,
+    style: Style,
+    points: Vec<(f32, f32)>,
+}
+
+#[derive(Debug)]
+struct Marker {
+    x: f32,
+    label: String,
+    color: &'static str,
+}
+
+#[derive(Debug)]
+pub struct Plot {
+    title: String,
+    x_label: String,
+    y_label: String,
+    x_range: Option<(f32, f32)>,
+    y_range: Option<(f32, f32)>,
+    series: Vec<Series>,
+    markers: Vec<Marker>,
+}
+
+impl Plot {
+    pub fn new(title: &str, x_label: &str, y_label: &str) -> Self {
+        Plot {
+            title: title.into(),
+            x_label: x_label.into(),
+            y_label: y_label.into(),
+            x_range: None,
+            y_range: None,
+            series: vec![],
+            markers: vec![],
+        }
+    }
+
+    /// Fixed x axis range, by default fitted to the data.
+    pub fn x_range(&mut self, low: f32, high: f32) -> &mut Self {
+        self.x_range = Some((low, high));
+        self
+    }
+
+    /// Fixed y axis range, values outside are clamped.
+    pub fn y_range(&mut self, low: f32, high: f32) -> &mut Self {
+        self.y_range = Some((low, high));
+        self
+    }
+
+    /// Adds a line through `points`, non-finite points are skipped.
+    pub fn line(&mut self, label: &str, color: &'static str, points: Vec<(f32, f32)>) -> &mut Self {
+        self.add(label, color, Style::Line, points)
+    }
+
+    /// Adds a dot per point.
+    pub fn points(
+        &mut self,
+        label: &str,
+        color: &'static str,
+        points: Vec<(f32, f32)>,
+    ) -> &mut Self {
+        self.add(label, color, Style::Points, points)
+    }
+
+    /// Adds a labeled vertical line at `x`.
+    pub fn marker(&mut self, x: f32, label: &str, color: &'static str) -> &mut Self {
+        self.markers.push(Marker {
+            x,
+            label: label.into(),
+            color,
+        });
+        self
+    }
+
+    fn add(
+        &mut self,
+        label: &str,
+        color: &'static str,
+        style: Style,
+        points: Vec<(f32, f32)>,
+    ) -> &mut Self {
+        self.series.push(Series {
+            label: label.into(),
+            color,
+            style,
+            points,
+        });
+        self
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, self.render())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn render(&self) -> String {
+        let finite = || {
+            self.series
+                .iter()
+                .flat_map(|s| s.points.iter())
+                .filter(|(x, y)| x.is_finite() && y.is_finite())
+        };
+        let (x0, x1) = self.x_range.unwrap_or_else(|| {
+            bounds(
+                finite()
+                    .map(|p| p.0)
+                    .chain(self.markers.iter().map(|m| m.x)),
+            )
+        });
+        let (y0, y1) = self
+            .y_range
+            .unwrap_or_else(|| bounds(finite().map(|p| p.1)));
+
+        let (w, h) = (WIDTH - LEFT - RIGHT, HEIGHT - TOP - BOTTOM);
+        let px = |x: f32| LEFT + (x - x0) / (x1 - x0) * w;
+        let py = |y: f32| TOP + h - (y.clamp(y0, y1) - y0) / (y1 - y0) * h;
+
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="12">"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="white"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<defs><clipPath id="area"><rect x="{LEFT}" y="{TOP}" width="{w}" height="{h}"/></clipPath></defs>"#
+        );
+
+        // grid and tick labels
+        for x in ticks(x0, x1) {
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{0:.1}" y1="{TOP}" x2="{0:.1}" y2="{1}" stroke="#ddd"/><text x="{0:.1}" y="{2}" text-anchor="middle">{3}</text>"##,
+                px(x),
+                TOP + h,
+                TOP + h + 16.0,
+                label(x, x1 - x0)
+            );
+        }
+        for y in ticks(y0, y1) {
+            let _ = writeln!(
+                svg,
+                r##"<line x1="{LEFT}" y1="{0:.1}" x2="{1}" y2="{0:.1}" stroke="#ddd"/><text x="{2}" y="{3:.1}" text-anchor="end">{4}</text>"##,
+                py(y),
+                LEFT + w,
+                LEFT - 6.0,
+                py(y) + 4.0,
+                label(y, y1 - y0)
+            );
+        }
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{LEFT}" y="{TOP}" width="{w}" height="{h}" fill="none" stroke="black"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="24" text-anchor="middle" font-size="16">{}</text>"#,
+            LEFT + w / 2.0,
+            escape(&self.title)
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+            LEFT + w / 2.0,
+            HEIGHT - 12.0,
+            escape(&self.x_label)
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text transform="translate(18 {}) rotate(-90)" text-anchor="middle">{}</text>"#,
+            TOP + h / 2.0,
+            escape(&self.y_label)
+        );
+
+        let _ = writeln!(svg, r#"<g clip-path="url(#area)">"#);
+        for m in &self.markers {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{0:.1}" y1="{TOP}" x2="{0:.1}" y2="{1}" stroke="{2}" stroke-dasharray="4 3"/><text x="{3:.1}" y="{4}" fill="{2}">{5}</text>"#,
+                px(m.x),
+                TOP + h,
+                m.color,
+                px(m.x) + 3.0,
+                TOP + 14.0,
+                escape(&m.label)
+            );
+        }
+        for s in &self.series {
+            let points = s
+                .points
+                .iter()
+                .filter(|(x, y)| x.is_finite() && !y.is_nan());
+            match s.style {
+                Style::Line => {
+                    let _ = write!(
+                        svg,
+                        r#"<polyline fill="none" stroke="{}" stroke-width="1" points=""#,
+                        s.color
+                    );
+                    for &(x, y) in points {
+                        let _ = write!(svg, "{:.1},{:.1} ", px(x), py(y));
+                    }
+                    let _ = writeln!(svg, r#""/>"#);
+                }
+                Style::Points => {
+                    for &(x, y) in points {
+                        let _ = writeln!(
+                            svg,
+                            r#"<circle cx="{:.1}" cy="{:.1}" r="2.5" fill="{}"/>"#,
+                            px(x),
+                            py(y),
+                            s.color
+                        );
+                    }
+                }
+            }
+        }
+        let _ = writeln!(svg, "</g>");
+
+        // legend
+        for (i, s) in self
+            .series
+            .iter()
+            .filter(|s| !s.label.is_empty())
+            .enumerate()
+        {
+            let y = TOP + 16.0 + 16.0 * i as f32;
+            let x = LEFT + w - 150.0;
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{x}" y="{}" width="12" height="4" fill="{}"/><text x="{}" y="{y}">{}</text>"#,
+                y - 6.0,
+                s.color,
+                x + 18.0,
+                escape(&s.label)
+            );
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+// Range of the finite `values`, widened if empty or constant.
+fn bounds(values: impl Iterator<Item = f32>) -> (f32, f32) {
+    let (low, high) = values.fold((f32::INFINITY, f32::NEG_INFINITY), |(l, h), v| {
+        (l.min(v), h.max(v))
+    });
+    if low > high {
+        (0.0, 1.0)
+    } else if low == high {
+        (low - 1.0, high + 1.0)
+    } else {
+        (low, high)
+    }
+}
+
+// Tick step of 1, 2 or 5 times a power of ten, about 8 ticks per axis.
+fn step(span: f32) -> f32 {
+    let raw = span / 8.0;
+    let magnitude = 10f32.powf(raw.log10().floor());
+    match raw / magnitude {
+        r if r < 1.5 => magnitude,
+        r if r < 3.5 => 2.0 * magnitude,
+        r if r < 7.5 => 5.0 * magnitude,
+        _ => 10.0 * magnitude,
+    }
+}
+
+fn ticks(low: f32, high: f32) -> impl Iterator<Item = f32> {
+    let step = step(high - low);
+    let first = (low / step).ceil() as i64;
+    let last = (high / step).floor() as i64;
+    (first..=last).map(move |i| i as f32 * step)
+}
+
+// Tick label with as many decimals as the step needs.
+fn label(v: f32, span: f32) -> String {
+    let decimals = (-step(span).log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, v)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
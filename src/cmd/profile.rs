@@ -0,0 +1,102 @@
+//! Selects the ADC profile of the device.
+//!
+//! Writes a `Profile` frame to the serial device (or stdout), e.g.:
+//!
+//! guitar-tuner profile low-gain --device /dev/ttyACM0
+
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::Args;
+use guitar_tuner::{
+    adc::{AdcProfile, Gain, Oversample, Resolution},
+    protocol::{Frame, MAX_FRAME},
+};
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// Named profile to start from
+    #[arg(value_name = "NAME", default_value = "default", value_parser = parse_name)]
+    profile: AdcProfile,
+
+    /// Override the gain (nearest of 1/6..4)
+    #[arg(long)]
+    gain: Option<f32>,
+
+    /// Override the resolution (8, 10, 12 or 14 bits)
+    #[arg(long, value_name = "BITS", value_parser = parse_resolution)]
+    resolution: Option<Resolution>,
+
+    /// Override the oversampling (1, 2, 4 .. 256 samples per result)
+    #[arg(long, value_name = "N", value_parser = parse_oversample)]
+    oversample: Option<Oversample>,
+
+    /// Serial device of the tuner, stdout if not given
+    #[arg(short, long, value_name = "PATH")]
+    device: Option<PathBuf>,
+
+    /// List the named profiles and exit
+    #[arg(short, long)]
+    list: bool,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    if opt.list {
+        for (name, profile) in AdcProfile::NAMED {
+            println!("{:<14}{}", name, profile);
+        }
+        return Ok(());
+    }
+
+    let mut profile = opt.profile;
+    if let Some(gain) = opt.gain {
+        profile.gain = Gain::from_factor(gain);
+    }
+    if let Some(resolution) = opt.resolution {
+        profile.resolution = resolution;
+    }
+    if let Some(oversample) = opt.oversample {
+        profile.oversample = oversample;
+    }
+    eprintln!(
+        "profile {}: {}",
+        profile.name().unwrap_or("custom"),
+        profile
+    );
+
+    let mut buf = [0; MAX_FRAME];
+    let n = Frame::Profile(profile).encode(&mut buf)?;
+    match &opt.device {
+        Some(path) => OpenOptions::new()
+            .write(true)
+            .open(path)
+            .and_then(|mut f| f.write_all(&buf[..n]))
+            .with_context(|| format!("failed to write to {}", path.display())),
+        None => io::stdout().write_all(&buf[..n]).map_err(Into::into),
+    }
+}
+
+pub fn parse_name(name: &str) -> Result<AdcProfile, String> {
+    AdcProfile::named(name).ok_or_else(|| {
+        let names: Vec<_> = AdcProfile::NAMED.iter().map(|(n, _)| *n).collect();
+        format!("unknown profile, expected one of {}", names.join(", "))
+    })
+}
+
+fn parse_resolution(s: &str) -> Result<Resolution, String> {
+    s.parse()
+        .ok()
+        .and_then(Resolution::from_bits)
+        .ok_or_else(|| "expected 8, 10, 12 or 14".to_string())
+}
+
+fn parse_oversample(s: &str) -> Result<Oversample, String> {
+    s.parse()
+        .ok()
+        .and_then(Oversample::from_samples)
+        .ok_or_else(|| "expected a power of two up to 256".to_string())
+}
@@ -0,0 +1,148 @@
+//! Estimates the inharmonicity of each string from captures of single
+//! plucks (see `guitar_tuner::harmonics`) and prints the stretched targets,
+//! along with the `--inharmonicity` argument to tune to them by `strum`.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use guitar_tuner::{
+    fft::Detector,
+    harmonics::{stretch_tuning, Analyzer},
+    note::{cents, A4},
+    tuning::{Tuning, MAX_STRINGS},
+};
+
+/// FFT size of the coarse estimate.
+const FFT_SIZE: usize = 32768;
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// WAV or legacy `.txt` captures of single strings, in any order
+    #[arg(value_name = "FILE")]
+    inputs: Vec<PathBuf>,
+
+    /// Tuning, "standard", "drop-d", "bass" or notes, e.g. "D2,A2,D3,G3,A3,D4",
+    /// optionally offset in cents, e.g. "E2,A2,D3,G3,B3-2c,E4"
+    #[arg(short, long, default_value = "standard")]
+    tuning: Tuning,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Inharmonicity coefficients per string, instead of (or for strings
+    /// not in) the captures
+    #[arg(long, value_name = "B,..", value_delimiter = ',')]
+    inharmonicity: Vec<f32>,
+
+    /// String kept at its target (1 is the lowest), defaults to the string
+    /// closest to A4
+    #[arg(long, value_name = "STRING")]
+    reference: Option<usize>,
+
+    /// Number of harmonics fitted
+    #[arg(long, default_value_t = 8)]
+    harmonics: usize,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let strings = opt.tuning.strings().len();
+    if opt.inharmonicity.len() > strings {
+        anyhow::bail!(
+            "{} coefficients for {} strings",
+            opt.inharmonicity.len(),
+            strings
+        );
+    }
+    let reference = match opt.reference {
+        Some(r) if (1..=strings).contains(&r) => r - 1,
+        Some(r) => anyhow::bail!("no string {}, the tuning has {} strings", r, strings),
+        None => reference(&opt.tuning, opt.a4),
+    };
+
+    let mut b = [0.0; MAX_STRINGS];
+    b[..opt.inharmonicity.len()].copy_from_slice(&opt.inharmonicity);
+    // measurements per string, averaged
+    let mut measured = [(0.0, 0); MAX_STRINGS];
+
+    let mut detector = Box::new(Detector::<FFT_SIZE>::new());
+    // not the octave of a bass string
+    detector.min_freq = super::min_freq(&opt.tuning, opt.a4);
+    let mut analyzer = Analyzer::new();
+    analyzer.harmonics = opt.harmonics;
+    for path in &opt.inputs {
+        let capture = super::load(path)?;
+        let fs = capture.header.sample_rate;
+        let samples = capture.normalized();
+        let start = super::after_attack(&samples, fs).min(samples.len());
+        let samples = &samples[start..];
+        let window = ((analyzer.window * fs) as usize).min(samples.len());
+
+        let Some(estimate) = detector.detect(&samples[..window.min(FFT_SIZE)], fs) else {
+            println!("{}: no pitch found", path.display());
+            continue;
+        };
+        let analysis = analyzer.analyze(samples, fs, estimate.pitch);
+        let string = match opt.tuning.classify(analysis.fundamental, opt.a4) {
+            Some((string, deviation)) if deviation.abs() <= 100.0 => string,
+            _ => {
+                println!(
+                    "{}: {:.2} Hz is not close to a string of {}",
+                    path.display(),
+                    analysis.fundamental,
+                    opt.tuning
+                );
+                continue;
+            }
+        };
+        let deviation = cents(analysis.fundamental, opt.tuning.target(string, opt.a4));
+        match analysis.inharmonicity {
+            Some(coefficient) => {
+                println!(
+                    "{}: string {} ({} {:+.1} c), B = {:.2e}",
+                    path.display(),
+                    string + 1,
+                    opt.tuning.strings()[string],
+                    deviation,
+                    coefficient
+                );
+                measured[string].0 += coefficient;
+                measured[string].1 += 1;
+            }
+            None => println!(
+                "{}: string {}, too few harmonics for the inharmonicity",
+                path.display(),
+                string + 1
+            ),
+        }
+    }
+    for (b, (sum, n)) in b.iter_mut().zip(measured) {
+        if n > 0 {
+            *b = sum / n as f32;
+        }
+    }
+
+    let offsets = stretch_tuning(&opt.tuning, &b[..strings], reference);
+    let stretched = opt.tuning.add_offsets(&offsets);
+    println!("string  note  B          target Hz  stretched Hz    cents");
+    for (s, note) in opt.tuning.strings().iter().enumerate() {
+        println!(
+            "{:>6}  {:<4}  {:<9.2e}  {:>9.2}  {:>12.2}  {:>+7.2}{}",
+            s + 1,
+            note.to_string(),
+            b[s],
+            opt.tuning.target(s, opt.a4),
+            stretched.target(s, opt.a4),
+            cents(stretched.target(s, opt.a4), opt.tuning.target(s, opt.a4)),
+            if s == reference { "  reference" } else { "" }
+        );
+    }
+    let list: Vec<String> = b[..strings].iter().map(|b| format!("{:.2e}", b)).collect();
+    println!("--inharmonicity {}", list.join(","));
+    Ok(())
+}
+
+/// The string closest to A4, kept at its target when stretching.
+pub fn reference(tuning: &Tuning, a4: f32) -> usize {
+    tuning.classify(a4, a4).map_or(0, |(s, _)| s)
+}
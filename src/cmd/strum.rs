@@ -0,0 +1,162 @@
+//! Checks the tuning of all strings at once from a strum (see
+//! `guitar_tuner::strum`), given a WAV or legacy `data*.txt` capture.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use guitar_tuner::{
+    harmonics::stretch_tuning,
+    note::A4,
+    strum::{Status, Strum},
+    temperament::Temperament,
+    tuning::Tuning,
+};
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// The WAV or legacy `.txt` capture of the strum
+    #[arg(value_name = "FILE")]
+    input: PathBuf,
+
+    /// Tuning, "standard", "drop-d", "bass" or notes, e.g. "D2,A2,D3,G3,A3,D4",
+    /// optionally offset in cents, e.g. "E2,A2,D3,G3,B3-2c,E4"
+    #[arg(short, long, default_value = "standard")]
+    tuning: Tuning,
+
+    /// Temperament, "equal", "just", "pythagorean", "meantone" or
+    /// "werckmeister", optionally with a key, e.g. "just:G"
+    #[arg(long, default_value = "equal")]
+    temperament: Temperament,
+
+    /// Fret of the capo
+    #[arg(long, value_name = "FRET", default_value_t = 0)]
+    capo: u8,
+
+    /// Transposition in semitones, e.g. -1 for a band tuned a semitone down
+    #[arg(
+        long,
+        value_name = "SEMITONES",
+        default_value_t = 0,
+        allow_hyphen_values = true
+    )]
+    transpose: i8,
+
+    /// Transposition in cents, e.g. -50 for a quarter tone flat
+    #[arg(
+        long,
+        value_name = "CENTS",
+        default_value_t = 0.0,
+        allow_hyphen_values = true
+    )]
+    detune: f32,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Start of the analysis in seconds, defaults to just after the attack
+    #[arg(long, value_name = "SEC")]
+    start: Option<f32>,
+
+    /// Length of the analysis in seconds
+    #[arg(long, value_name = "SEC", default_value_t = 1.0)]
+    duration: f32,
+
+    /// Maximum deviation in cents considered in tune
+    #[arg(long, value_name = "CENTS", default_value_t = 5.0)]
+    tolerance: f32,
+
+    /// Inharmonicity coefficients per string (see `stretch`), tuning to
+    /// stretched targets
+    #[arg(long, value_name = "B,..", value_delimiter = ',')]
+    inharmonicity: Vec<f32>,
+
+    /// String kept at its target when stretched (1 is the lowest), defaults
+    /// to the string closest to A4
+    #[arg(long, value_name = "STRING")]
+    reference: Option<usize>,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let capture = super::load(&opt.input)?;
+    let fs = capture.header.sample_rate;
+    let samples = capture.normalized();
+
+    let start = match opt.start {
+        Some(s) => (s * fs) as usize,
+        None => super::after_attack(&samples, fs),
+    };
+    let end = (start + (opt.duration * fs) as usize).min(samples.len());
+    if start >= end {
+        anyhow::bail!(
+            "no samples to analyze, the capture is {} samples",
+            samples.len()
+        );
+    }
+
+    let mut tuning = opt
+        .tuning
+        .with_temperament(opt.temperament)
+        .with_capo(opt.capo)
+        .transposed(opt.transpose, opt.detune);
+    if !opt.inharmonicity.is_empty() {
+        let reference = match opt.reference {
+            Some(r) if (1..=tuning.strings().len()).contains(&r) => r - 1,
+            Some(r) => anyhow::bail!("no string {} in tuning {}", r, tuning),
+            None => super::stretch::reference(&tuning, opt.a4),
+        };
+        tuning = tuning.add_offsets(&stretch_tuning(&tuning, &opt.inharmonicity, reference));
+    }
+
+    let mut strum = Strum::new(tuning);
+    strum.a4 = opt.a4;
+    strum.tolerance = opt.tolerance;
+    let report = strum.analyze(&samples[start..end], fs);
+
+    println!(
+        "{:.2} s .. {:.2} s, tuning {} ({}){}",
+        start as f32 / fs,
+        end as f32 / fs,
+        opt.tuning,
+        opt.temperament,
+        super::shift(&tuning)
+    );
+    println!("string  note  target Hz  pitch Hz    cents  level dB  status");
+    for (i, s) in report.strings().iter().enumerate() {
+        // the note sounding, with capo and transposition
+        let note = tuning.note(i);
+        let status = match s.status {
+            Status::Missing => "missing",
+            Status::Flat => "flat",
+            Status::InTune => "in tune",
+            Status::Sharp => "sharp",
+        };
+        if s.status == Status::Missing {
+            println!(
+                "{:>6}  {:<4}  {:>9.2}  {:>8}  {:>7}  {:>8.1}  {}",
+                i + 1,
+                note.to_string(),
+                s.target,
+                "-",
+                "-",
+                s.level,
+                status
+            );
+        } else {
+            println!(
+                "{:>6}  {:<4}  {:>9.2}  {:>8.2}  {:>+7.1}  {:>8.1}  {}",
+                i + 1,
+                note.to_string(),
+                s.target,
+                s.pitch,
+                s.cents,
+                s.level,
+                status
+            );
+        }
+    }
+    if report.in_tune() {
+        println!("all strings in tune");
+    }
+    Ok(())
+}
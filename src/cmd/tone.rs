@@ -0,0 +1,205 @@
+//! Plays the target of a string as a reference tone on the output device
+//! (see `guitar_tuner::tone`), for tuning by ear.
+//!
+//! Reads commands from stdin: the number of a string selects it, an empty
+//! line plucks again, `q` quits.
+
+use std::{
+    io::{self, BufRead},
+    sync::mpsc,
+    time::Duration,
+};
+
+use clap::{Args, ValueEnum};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use guitar_tuner::{
+    note::A4,
+    temperament::Temperament,
+    tone::{self, Tone},
+    tuning::Tuning,
+};
+
+use super::audio;
+
+/// Delay line of the plucked tone, down to 23 Hz at 96 kHz.
+const DELAY: usize = 4096;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timbre {
+    Sine,
+    /// Plucked string
+    Pluck,
+}
+
+impl From<Timbre> for tone::Timbre {
+    fn from(timbre: Timbre) -> Self {
+        match timbre {
+            Timbre::Sine => tone::Timbre::Sine,
+            Timbre::Pluck => tone::Timbre::Pluck,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Opt {
+    /// String to play (1 is the lowest)
+    #[arg(short, long, value_name = "STRING", default_value_t = 1)]
+    string: usize,
+
+    /// Tuning, "standard", "drop-d", "bass" or notes, e.g. "D2,A2,D3,G3,A3,D4",
+    /// optionally offset in cents, e.g. "E2,A2,D3,G3,B3-2c,E4"
+    #[arg(short, long, default_value = "standard")]
+    tuning: Tuning,
+
+    /// Temperament, "equal", "just", "pythagorean", "meantone" or
+    /// "werckmeister", optionally with a key, e.g. "just:G"
+    #[arg(long, default_value = "equal")]
+    temperament: Temperament,
+
+    /// Fret of the capo
+    #[arg(long, value_name = "FRET", default_value_t = 0)]
+    capo: u8,
+
+    /// Transposition in semitones, e.g. -1 for a band tuned a semitone down
+    #[arg(
+        long,
+        value_name = "SEMITONES",
+        default_value_t = 0,
+        allow_hyphen_values = true
+    )]
+    transpose: i8,
+
+    /// Transposition in cents, e.g. -50 for a quarter tone flat
+    #[arg(
+        long,
+        value_name = "CENTS",
+        default_value_t = 0.0,
+        allow_hyphen_values = true
+    )]
+    detune: f32,
+
+    /// Reference pitch in Hz
+    #[arg(long, value_name = "HZ", default_value_t = A4)]
+    a4: f32,
+
+    /// Timbre of the tone
+    #[arg(long, value_enum, default_value_t = Timbre::Sine)]
+    timbre: Timbre,
+
+    /// Time between plucks in seconds, 0 plucks once
+    #[arg(long, value_name = "SEC", default_value_t = 2.0)]
+    repeat: f32,
+
+    /// Peak amplitude, 0.0..=1.0
+    #[arg(long, default_value_t = 0.5)]
+    volume: f32,
+
+    /// Play for this long and quit, instead of reading commands from stdin
+    #[arg(long, value_name = "SEC", value_parser = super::parse_positive)]
+    duration: Option<f32>,
+
+    /// The audio host to use, e.g. "alsa" or "jack"
+    #[arg(long, default_value = "default")]
+    host: String,
+
+    /// The output audio device to use: "default", an index, a name or part
+    /// of it, or an ID (see `devices`)
+    #[arg(short, long, value_name = "OUT", default_value = "default")]
+    output_device: String,
+}
+
+// Commands to the output stream.
+enum Control {
+    Play(f32),
+    Pluck,
+}
+
+pub fn run(opt: Opt) -> anyhow::Result<()> {
+    let tuning = opt
+        .tuning
+        .with_temperament(opt.temperament)
+        .with_capo(opt.capo)
+        .transposed(opt.transpose, opt.detune);
+    let strings = tuning.strings().len();
+    let target = |string: usize| -> anyhow::Result<f32> {
+        match string {
+            1.. if string <= strings => Ok(tuning.target(string - 1, opt.a4)),
+            _ => anyhow::bail!("no string {}, the tuning has {} strings", string, strings),
+        }
+    };
+    let freq = target(opt.string)?;
+
+    let host = audio::host(&opt.host)?;
+    let device = audio::output_device(&host, &opt.output_device)?;
+    let config = audio::output_config(&device, None)?;
+    let channels = config.channels() as usize;
+    println!(
+        "Using output device: \"{}\", {}",
+        device.name()?,
+        audio::describe(&config)
+    );
+
+    let mut tone = Box::new(Tone::<DELAY>::new(config.sample_rate().0 as f32));
+    tone.amplitude = opt.volume.clamp(0.0, 1.0);
+    tone.repeat = opt.repeat;
+    let timbre = opt.timbre.into();
+    if !tone.play(freq, timbre) {
+        anyhow::bail!("{:.2} Hz is too low to pluck", freq);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let output_data_fn = move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+        while let Ok(control) = receiver.try_recv() {
+            match control {
+                Control::Play(freq) => {
+                    tone.play(freq, timbre);
+                }
+                Control::Pluck => tone.pluck(),
+            }
+        }
+        // the same on all channels
+        for frame in data.chunks_mut(channels) {
+            frame.fill(tone.next_sample());
+        }
+    };
+    let stream = audio::build_output_stream(&device, &config, output_data_fn, audio::err_fn)?;
+    stream.play()?;
+    println!(
+        "string {} ({}) at {:.2} Hz{}",
+        opt.string,
+        tuning.note(opt.string - 1),
+        freq,
+        super::shift(&tuning)
+    );
+
+    if let Some(duration) = opt.duration {
+        std::thread::sleep(Duration::try_from_secs_f32(duration)?);
+        return Ok(());
+    }
+    println!(
+        "enter a string (1..={}) to play, nothing to pluck again, q to quit",
+        strings
+    );
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let control = match line.trim() {
+            "q" => break,
+            "" => Control::Pluck,
+            s => match s.parse().map_err(anyhow::Error::from).and_then(target) {
+                Ok(freq) => {
+                    println!("{:.2} Hz", freq);
+                    Control::Play(freq)
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    continue;
+                }
+            },
+        };
+        // the stream is gone if the receiver is
+        if sender.send(control).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
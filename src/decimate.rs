@@ -0,0 +1,334 @@
+// decimate, anti-aliased reduction of the sample rate ahead of the detectors
+//
+// The cost of the time-domain tracker grows with the square of the sample
+// rate (it compares a window of the longest period at every lag), while
+// the strings need only a few kHz. A `Decimator` reduces the rate by an
+// integer factor: by a half-band FIR stage per factor of two (every other
+// coefficient is zero), then by a FIR for the remaining factor, computing
+// only the outputs kept (the polyphase form). The filters are windowed
+// sincs (Kaiser, about 70 dB), the transition bands centered at the
+// Nyquist frequency of each stage, so the output is flat up to about
+// `PASSBAND` of the new Nyquist frequency, where nothing aliases into.
+
+use core::f32::consts::PI;
+
+/// Taps of a half-band stage.
+pub const HALF_BAND_TAPS: usize = 51;
+
+/// Maximum number of half-band stages, a factor of 16.
+pub const MAX_HALF_BANDS: usize = 4;
+
+/// Maximum remaining factor, after the half-band stages.
+pub const MAX_FACTOR: usize = 8;
+
+/// Taps of the last stage per unit of its factor.
+const TAPS_PER_PHASE: usize = 24;
+
+const MAX_TAPS: usize = TAPS_PER_PHASE * MAX_FACTOR + 1;
+
+/// Part of the new Nyquist frequency passed.
+pub const PASSBAND: f32 = 0.8;
+
+/// Kaiser window parameter, about 70 dB of stop band attenuation.
+const BETA: f32 = 7.0;
+
+// Windowed sinc low-pass of `cutoff` (relative to the sample rate) into
+// `taps`, of unity gain at DC.
+fn design(taps: &mut [f32], cutoff: f32) {
+    let center = (taps.len() - 1) as f32 / 2.0;
+    for (n, h) in taps.iter_mut().enumerate() {
+        let t = n as f32 - center;
+        let sinc = match t == 0.0 {
+            true => 2.0 * cutoff,
+            false => libm::sinf(2.0 * PI * cutoff * t) / (PI * t),
+        };
+        let r = t / center;
+        *h = sinc * bessel_i0(BETA * libm::sqrtf((1.0 - r * r).max(0.0))) / bessel_i0(BETA);
+    }
+    let sum: f32 = taps.iter().sum();
+    taps.iter_mut().for_each(|h| *h /= sum);
+}
+
+// Modified Bessel function of the first kind, order 0 (by its series).
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..32 {
+        term *= (x / (2.0 * k as f32)) * (x / (2.0 * k as f32));
+        sum += term;
+        if term < 1e-9 * sum {
+            break;
+        }
+    }
+    sum
+}
+
+// Decimation by two, the half-band filter keeping the center and the odd
+// taps (the even ones are zero).
+#[derive(Debug, Clone, Copy)]
+struct HalfBand {
+    center: f32,
+    // the odd taps from the center outwards
+    taps: [f32; (HALF_BAND_TAPS + 1) / 4],
+    // twice, the last `HALF_BAND_TAPS` samples contiguous from `index`
+    history: [f32; 2 * HALF_BAND_TAPS],
+    index: usize,
+    odd: bool,
+}
+
+impl HalfBand {
+    fn new() -> Self {
+        let mut full = [0.0; HALF_BAND_TAPS];
+        design(&mut full, 0.25);
+        let center = HALF_BAND_TAPS / 2;
+        HalfBand {
+            center: full[center],
+            taps: core::array::from_fn(|i| full[center + 2 * i + 1]),
+            history: [0.0; 2 * HALF_BAND_TAPS],
+            index: 0,
+            odd: false,
+        }
+    }
+
+    fn push(&mut self, x: f32) -> Option<f32> {
+        self.history[self.index] = x;
+        self.history[self.index + HALF_BAND_TAPS] = x;
+        self.index = if self.index + 1 == HALF_BAND_TAPS {
+            0
+        } else {
+            self.index + 1
+        };
+        self.odd = !self.odd;
+        if self.odd {
+            return None;
+        }
+        // from the oldest sample
+        let x = &self.history[self.index..self.index + HALF_BAND_TAPS];
+        let center = HALF_BAND_TAPS / 2;
+        let mut y = self.center * x[center];
+        for (i, h) in self.taps.iter().enumerate() {
+            y += h * (x[center - 2 * i - 1] + x[center + 2 * i + 1]);
+        }
+        Some(y)
+    }
+}
+
+// Decimation by `factor`, the FIR evaluated for the outputs only.
+#[derive(Debug, Clone, Copy)]
+struct Fir {
+    factor: usize,
+    len: usize,
+    taps: [f32; MAX_TAPS],
+    // twice, the last `len` samples contiguous from `index`
+    history: [f32; 2 * MAX_TAPS],
+    index: usize,
+    phase: usize,
+}
+
+impl Fir {
+    fn new(factor: usize) -> Self {
+        let len = TAPS_PER_PHASE * factor + 1;
+        let mut taps = [0.0; MAX_TAPS];
+        design(&mut taps[..len], 0.5 / factor as f32);
+        Fir {
+            factor,
+            len,
+            taps,
+            history: [0.0; 2 * MAX_TAPS],
+            index: 0,
+            phase: 0,
+        }
+    }
+
+    fn push(&mut self, x: f32) -> Option<f32> {
+        self.history[self.index] = x;
+        self.history[self.index + self.len] = x;
+        self.index = if self.index + 1 == self.len {
+            0
+        } else {
+            self.index + 1
+        };
+        self.phase += 1;
+        if self.phase < self.factor {
+            return None;
+        }
+        self.phase = 0;
+        // from the oldest sample
+        let x = &self.history[self.index..self.index + self.len];
+        Some(dot(x, &self.taps[..self.len]))
+    }
+}
+
+// The dot product of `a` and `b`, in four sums (that vectorize).
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let mut sums = [0.0; 4];
+    let (a4, b4) = (a.chunks_exact(4), b.chunks_exact(4));
+    let rest: f32 = a4
+        .remainder()
+        .iter()
+        .zip(b4.remainder())
+        .map(|(x, h)| x * h)
+        .sum();
+    for (a, b) in a4.zip(b4) {
+        for i in 0..4 {
+            sums[i] += a[i] * b[i];
+        }
+    }
+    sums.iter().sum::<f32>() + rest
+}
+
+/// Anti-aliased decimation by an integer factor.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimator {
+    factor: usize,
+    half_bands: [HalfBand; MAX_HALF_BANDS],
+    nr_half_bands: usize,
+    fir: Option<Fir>,
+}
+
+impl Decimator {
+    /// Decimation by `factor`, None unless a power of two (up to
+    /// 2^`MAX_HALF_BANDS`) times at most `MAX_FACTOR`.
+    pub fn new(factor: usize) -> Option<Self> {
+        if factor == 0 {
+            return None;
+        }
+        let nr_half_bands = factor.trailing_zeros() as usize;
+        let rest = factor >> nr_half_bands;
+        if nr_half_bands > MAX_HALF_BANDS || rest > MAX_FACTOR {
+            return None;
+        }
+        Some(Decimator {
+            factor,
+            half_bands: [HalfBand::new(); MAX_HALF_BANDS],
+            nr_half_bands,
+            fir: (rest > 1).then(|| Fir::new(rest)),
+        })
+    }
+
+    /// Decimation from `from` Hz to `to` Hz, None unless by a factor
+    /// accepted by `new`.
+    pub fn from_rates(from: f32, to: f32) -> Option<Self> {
+        let factor = libm::roundf(from / to);
+        if factor < 1.0 || libm::fabsf(factor * to - from) > 0.001 * from {
+            return None;
+        }
+        Decimator::new(factor as usize)
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// Filters the next sample, returning the output every `factor`
+    /// samples.
+    pub fn push(&mut self, x: f32) -> Option<f32> {
+        let mut x = x;
+        for half_band in &mut self.half_bands[..self.nr_half_bands] {
+            x = half_band.push(x)?;
+        }
+        match &mut self.fir {
+            Some(fir) => fir.push(x),
+            None => Some(x),
+        }
+    }
+
+    /// Decimates the next `input` samples, calling `out` for each output
+    /// sample.
+    pub fn process(&mut self, input: &[f32], mut out: impl FnMut(f32)) {
+        for x in input {
+            if let Some(y) = self.push(*x) {
+                out(y);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::testing::{sine, DEVICE_FS as FS};
+
+// Gain of `decimator` for a sine of `freq` Hz in dB.
+#[cfg(test)]
+fn gain(decimator: &mut Decimator, freq: f32) -> f32 {
+    let (mut sum, mut n) = (0.0, 0);
+    for i in 0..FS as usize / 2 {
+        let x = sine(freq, FS, i);
+        // after the transient
+        if let Some(y) = decimator.push(x).filter(|_| i > FS as usize / 10) {
+            sum += y * y;
+            n += 1;
+        }
+    }
+    10.0 * libm::log10f(2.0 * sum / n as f32)
+}
+
+#[test]
+fn decimate_test() {
+    assert!(Decimator::new(0).is_none());
+    assert!(Decimator::new(9).is_none());
+    assert!(Decimator::new(32).is_none());
+    assert!(Decimator::from_rates(48000.0, 7000.0).is_none());
+    assert_eq!(Decimator::from_rates(44100.0, 4410.0).unwrap().factor(), 10);
+
+    // to 8, 6 and 4 kHz, by a FIR, half-bands and both
+    for to in [8000.0, 6000.0, 4000.0] {
+        let mut d = Decimator::from_rates(FS, to).unwrap();
+        let nyquist = to / 2.0;
+        // flat in the pass band
+        for f in [100.0, 0.5 * nyquist, PASSBAND * nyquist] {
+            let g = gain(&mut d, f);
+            assert!(g.abs() < 0.1, "{} Hz at {} Hz: {} dB", to, f, g);
+        }
+        // what would alias into it rejected
+        for f in [
+            to - PASSBAND * nyquist,
+            to + 0.2 * nyquist,
+            FS / 2.0 - 100.0,
+        ] {
+            let g = gain(&mut d, f);
+            assert!(g < -60.0, "{} Hz at {} Hz: {} dB", to, f, g);
+        }
+    }
+}
+
+// Largest error of the tracker in cents at `to` Hz, decimated from `FS`,
+// over bright (sawtooth like) tones from the low E up to the high E at the
+// 19th fret.
+#[cfg(test)]
+fn error(to: f32) -> f32 {
+    let mut worst: f32 = 0.0;
+    for f in [82.41, 110.0, 146.83, 196.0, 246.94, 329.63, 659.26, 987.77] {
+        let mut d = Decimator::from_rates(FS, to).unwrap();
+        let mut t = crate::track::Tracker::<512>::new(to);
+        let mut n = 0;
+        for i in 0..FS as usize {
+            let phase = 2.0 * PI * f * i as f32 / FS;
+            let x = (1..=12)
+                .map(|k| 0.1 / k as f32 * libm::sinf(k as f32 * phase))
+                .sum();
+            let Some(y) = d.push(x) else {
+                continue;
+            };
+            t.push(y);
+            n += 1;
+            // every 10 ms, once settled
+            if n % (to as usize / 100) == 0 {
+                t.update();
+                if n > to as usize / 2 {
+                    let cents = t
+                        .pitch()
+                        .map_or(f32::INFINITY, |p| crate::note::cents(p, f));
+                    worst = worst.max(cents.abs());
+                }
+            }
+        }
+    }
+    worst
+}
+
+#[test]
+fn accuracy_test() {
+    // measured 1.0 and 3.1 cents, 0.02 cents at 48 kHz
+    assert!(error(8000.0) < 1.5, "{}", error(8000.0));
+    assert!(error(4000.0) < 3.5, "{}", error(4000.0));
+}
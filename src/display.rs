@@ -0,0 +1,311 @@
+// display, renders a detector reading on a 128x64 monochrome display
+//
+// Layout:
+//
+// +------------------------------+
+// | E2                   string 1|
+// |                      +12.3 c |
+// |            |  ||             |
+// | |     |    |  |     |      | |
+// | ---------------------------- |
+// | [#########                 ] |
+// +------------------------------+
+//
+// Drawing is done through the `embedded-graphics` `DrawTarget` trait, so the
+// same code drives an SSD1306 on the device and the `FrameBuffer` on the host.
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::{
+    mono_font::{
+        ascii::{FONT_10X20, FONT_6X10},
+        MonoTextStyle,
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
+};
+
+use crate::{
+    note::Note,
+    protocol::{Reading, State},
+};
+
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 64;
+
+/// Cents at the ends of the needle scale.
+const SCALE_CENTS: f32 = 50.0;
+/// Level (dBFS) of an empty level bar.
+const LEVEL_MIN: f32 = -60.0;
+
+const SCALE_Y: i32 = 46;
+const NEEDLE_HEIGHT: i32 = 16;
+const LEVEL_Y: i32 = 54;
+
+/// Draws `reading` on `target`, `a4` is used to name the note.
+pub fn draw<D>(target: &mut D, reading: &Reading, a4: f32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    target.clear(BinaryColor::Off)?;
+
+    let large = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    let small = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let top_right = TextStyleBuilder::new()
+        .alignment(Alignment::Right)
+        .baseline(Baseline::Top)
+        .build();
+
+    draw_scale(target)?;
+    draw_level(target, reading.level)?;
+
+    if reading.state == State::Idle {
+        Text::with_baseline("--", Point::zero(), large, Baseline::Top).draw(target)?;
+        return Ok(());
+    }
+
+    // the note we are tuning towards
+    let target_freq = reading.pitch * libm::exp2f(-reading.cents / 1200.0);
+    let (note, _) = Note::nearest(target_freq, a4);
+
+    let mut text = TextBuf::<16>::new();
+    write!(text, "{}", note).ok();
+    Text::with_baseline(text.as_str(), Point::zero(), large, Baseline::Top).draw(target)?;
+
+    if let Some(string) = reading.string {
+        text.clear();
+        write!(text, "string {}", string + 1).ok();
+        Text::with_text_style(
+            text.as_str(),
+            Point::new(WIDTH as i32 - 1, 0),
+            small,
+            top_right,
+        )
+        .draw(target)?;
+    }
+
+    text.clear();
+    write!(text, "{:+.1} c", reading.cents).ok();
+    Text::with_text_style(
+        text.as_str(),
+        Point::new(WIDTH as i32 - 1, 11),
+        small,
+        top_right,
+    )
+    .draw(target)?;
+
+    // the needle, clamped to the ends of the scale
+    let x = scale_x(reading.cents.clamp(-SCALE_CENTS, SCALE_CENTS));
+    Line::new(
+        Point::new(x, SCALE_Y - NEEDLE_HEIGHT),
+        Point::new(x, SCALE_Y),
+    )
+    .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 3))
+    .draw(target)
+}
+
+fn scale_x(cents: f32) -> i32 {
+    let half = (WIDTH as i32 - 8) / 2;
+    WIDTH as i32 / 2 + libm::roundf(cents / SCALE_CENTS * half as f32) as i32
+}
+
+fn draw_scale<D>(target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+    Line::new(
+        Point::new(scale_x(-SCALE_CENTS), SCALE_Y),
+        Point::new(scale_x(SCALE_CENTS), SCALE_Y),
+    )
+    .into_styled(style)
+    .draw(target)?;
+
+    for cents in [-50.0, -25.0, 0.0, 25.0, 50.0] {
+        let height = if cents == 0.0 { 8 } else { 4 };
+        let x = scale_x(cents);
+        Line::new(Point::new(x, SCALE_Y - height), Point::new(x, SCALE_Y))
+            .into_styled(style)
+            .draw(target)?;
+    }
+    Ok(())
+}
+
+fn draw_level<D>(target: &mut D, level: f32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let outline = Rectangle::new(
+        Point::new(0, LEVEL_Y),
+        Size::new(WIDTH, HEIGHT - LEVEL_Y as u32),
+    );
+    outline
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)?;
+
+    let fraction = ((level - LEVEL_MIN) / -LEVEL_MIN).clamp(0.0, 1.0);
+    let width = libm::roundf(fraction * (WIDTH - 4) as f32) as u32;
+    Rectangle::new(
+        Point::new(2, LEVEL_Y + 2),
+        Size::new(width, HEIGHT - LEVEL_Y as u32 - 4),
+    )
+    .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+    .draw(target)
+}
+
+// Fixed capacity string for formatting without allocation, excess
+// characters are dropped.
+struct TextBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> TextBuf<N> {
+    fn new() -> Self {
+        TextBuf {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn as_str(&self) -> &str {
+        // only complete `&str`s are ever copied in
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl<const N: usize> Write for TextBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let bytes = s.as_bytes();
+        if self.len + bytes.len() > N {
+            return Err(fmt::Error);
+        }
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+}
+
+/// In-memory 128x64 monochrome frame buffer, one bit per pixel.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FrameBuffer {
+    pixels: [u8; (WIDTH * HEIGHT / 8) as usize],
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameBuffer {
+    pub const fn new() -> Self {
+        FrameBuffer {
+            pixels: [0; (WIDTH * HEIGHT / 8) as usize],
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        let i = (y * WIDTH + x) as usize;
+        self.pixels[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    fn set(&mut self, x: u32, y: u32, on: bool) {
+        let i = (y * WIDTH + x) as usize;
+        if on {
+            self.pixels[i / 8] |= 1 << (i % 8);
+        } else {
+            self.pixels[i / 8] &= !(1 << (i % 8));
+        }
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH, HEIGHT)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(p, c) in pixels {
+            if p.x >= 0 && p.y >= 0 && (p.x as u32) < WIDTH && (p.y as u32) < HEIGHT {
+                self.set(p.x as u32, p.y as u32, c.is_on());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renders the frame buffer as text, `#` for lit and `.` for dark pixels.
+impl fmt::Display for FrameBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                f.write_char(if self.get(x, y) { '#' } else { '.' })?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+// Compares the rendering to the snapshot in `src/snapshots`, run with
+// `UPDATE_SNAPSHOTS=1` to regenerate after intended layout changes.
+#[cfg(test)]
+fn assert_snapshot(name: &str, fb: &FrameBuffer) {
+    let path = format!("{}/src/snapshots/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+    let rendered = fb.to_string();
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, &rendered).unwrap();
+    }
+    let expected = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        rendered == expected,
+        "snapshot {} differs:\n{}",
+        name,
+        rendered
+    );
+}
+
+#[test]
+fn display_track_test() {
+    let mut fb = FrameBuffer::new();
+    let reading = Reading {
+        pitch: 83.0,
+        cents: 12.3,
+        string: Some(0),
+        confidence: 1.0,
+        level: -20.0,
+        state: State::Track,
+    };
+    draw(&mut fb, &reading, crate::note::A4).unwrap();
+    assert_snapshot("display_track", &fb);
+}
+
+#[test]
+fn display_idle_test() {
+    let mut fb = FrameBuffer::new();
+    let reading = Reading {
+        pitch: 0.0,
+        cents: 0.0,
+        string: None,
+        confidence: 0.0,
+        level: -80.0,
+        state: State::Idle,
+    };
+    draw(&mut fb, &reading, crate::note::A4).unwrap();
+    assert_snapshot("display_idle", &fb);
+}
@@ -0,0 +1,383 @@
+// fft, spectrum based pitch detection
+//
+// The samples are windowed, zero padded to `N` and transformed (radix-2
+// FFT). The fundamental is then picked from the magnitude spectrum, either
+// as the strongest peak, by the harmonic product spectrum (HPS) or from the
+// cepstrum, and refined between bins by quadratic or Gaussian interpolation.
+//
+// The estimate is coarse compared to the time-domain tracker (`track`), but
+// needs no search and is robust against octave errors (HPS), making it a
+// good seed for the tracker. It also serves as a standalone detector for
+// benchmarking.
+
+use core::f64::consts::PI;
+
+/// In-place radix-2 FFT, `re` and `im` must have the same power of two
+/// length.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    assert!(n.is_power_of_two() && im.len() == n);
+
+    // bit reversal permutation
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        let (dc, ds) = (libm::cos(angle), libm::sin(angle));
+        for start in (0..n).step_by(len) {
+            // twiddle factor by rotation, kept in f64 for accuracy
+            let (mut wr, mut wi) = (1.0f64, 0.0f64);
+            for k in 0..len / 2 {
+                let (a, b) = (start + k, start + k + len / 2);
+                let (wr32, wi32) = (wr as f32, wi as f32);
+                let tr = re[b] * wr32 - im[b] * wi32;
+                let ti = re[b] * wi32 + im[b] * wr32;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+                (wr, wi) = (wr * dc - wi * ds, wr * ds + wi * dc);
+            }
+        }
+        len *= 2;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Blackman,
+}
+
+impl Window {
+    /// Half width of the main lobe in bins.
+    pub fn lobe(self) -> usize {
+        match self {
+            Window::Rectangular => 1,
+            Window::Hann => 2,
+            Window::Blackman => 3,
+        }
+    }
+
+    /// Window value at `i` of `n`.
+    pub fn at(self, i: usize, n: usize) -> f32 {
+        if n < 2 {
+            return 1.0;
+        }
+        let x = 2.0 * core::f32::consts::PI * i as f32 / (n - 1) as f32;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * libm::cosf(x),
+            Window::Blackman => 0.42 - 0.5 * libm::cosf(x) + 0.08 * libm::cosf(2.0 * x),
+        }
+    }
+}
+
+/// How the fundamental is picked from the spectrum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// The strongest peak, prone to octave errors.
+    Peak,
+    /// Harmonic product spectrum over the given number of harmonics.
+    Hps(u8),
+    /// The strongest peak of the real cepstrum.
+    Cepstrum,
+}
+
+/// Sub-bin interpolation of the peak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    None,
+    /// Parabola through the magnitudes.
+    Quadratic,
+    /// Parabola through the log magnitudes, exact for Gaussian peaks
+    /// (approximately so for Hann/Blackman windows).
+    Gaussian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// Fundamental frequency in Hz.
+    pub pitch: f32,
+    /// Fraction of the spectral energy at the harmonics of `pitch`.
+    pub confidence: f32,
+}
+
+impl Estimate {
+    /// Period in samples at sample rate `fs`, e.g., to seed the tracker.
+    pub fn period(&self, fs: f32) -> f32 {
+        fs / self.pitch
+    }
+}
+
+// Harmonics accounted in the confidence.
+const CONFIDENCE_HARMONICS: usize = 8;
+// Floor of the log magnitude.
+const EPSILON: f32 = 1e-12;
+
+/// FFT pitch detector of size `N` (a power of two).
+pub struct Detector<const N: usize> {
+    pub window: Window,
+    pub method: Method,
+    pub interpolation: Interpolation,
+    /// Search range of the fundamental in Hz.
+    pub min_freq: f32,
+    pub max_freq: f32,
+    re: [f32; N],
+    im: [f32; N],
+    magnitude: [f32; N],
+}
+
+impl<const N: usize> Default for Detector<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Detector<N> {
+    /// Hann window, HPS over 4 harmonics with Gaussian interpolation,
+    /// searching 60..1200 Hz (guitar and bass).
+    pub const fn new() -> Self {
+        assert!(N.is_power_of_two());
+        Detector {
+            window: Window::Hann,
+            method: Method::Hps(4),
+            interpolation: Interpolation::Gaussian,
+            min_freq: 60.0,
+            max_freq: 1200.0,
+            re: [0.0; N],
+            im: [0.0; N],
+            magnitude: [0.0; N],
+        }
+    }
+
+    /// Magnitude spectrum (`N / 2` bins) of the last `detect`, bin `k`
+    /// is at `k * fs / N` Hz.
+    pub fn spectrum(&self) -> &[f32] {
+        &self.magnitude[..N / 2]
+    }
+
+    /// Estimates the fundamental of `samples` (at most `N`, the rest is
+    /// ignored) sampled at `fs`.
+    pub fn detect(&mut self, samples: &[f32], fs: f32) -> Option<Estimate> {
+        let n = samples.len().min(N);
+        // scaled so a full scale sine gives a magnitude of 1
+        let mut sum = 0.0;
+        self.re.fill(0.0);
+        self.im.fill(0.0);
+        for (i, (re, x)) in self.re.iter_mut().zip(&samples[..n]).enumerate() {
+            let w = self.window.at(i, n);
+            sum += w;
+            *re = x * w;
+        }
+        fft(&mut self.re, &mut self.im);
+        let scale = if sum > 0.0 { 2.0 / sum } else { 0.0 };
+        for k in 0..N / 2 {
+            self.magnitude[k] =
+                libm::sqrtf(self.re[k] * self.re[k] + self.im[k] * self.im[k]) * scale;
+        }
+
+        let bin = fs / N as f32;
+        let low = (libm::floorf(self.min_freq / bin) as usize).max(1);
+        let high = (libm::ceilf(self.max_freq / bin) as usize).min(N / 2 - 2);
+        if low >= high {
+            return None;
+        }
+
+        let pitch = match self.method {
+            Method::Peak => {
+                let k = argmax(&self.magnitude, low, high)?;
+                self.interpolate(&self.magnitude, k) * bin
+            }
+            Method::Hps(harmonics) => {
+                // log domain, sums instead of products
+                let harmonics = harmonics.max(1) as usize;
+                for k in low..=high {
+                    self.re[k] = (1..=harmonics)
+                        .map(|h| self.magnitude[..N / 2].get(h * k).copied().unwrap_or(0.0))
+                        .map(|m| libm::logf(m + EPSILON))
+                        .sum();
+                }
+                let k = argmax(&self.re, low, high)?;
+                // a sub-harmonic wins for tones with few harmonics, the
+                // fundamental must stand out of the noise floor (none in
+                // range for tones above it)
+                let peak = self.magnitude[low..N / 2]
+                    .iter()
+                    .fold(0.0f32, |a, m| a.max(*m));
+                let (m, k) = (1..=harmonics)
+                    .map(|m| (m, m * k))
+                    .take_while(|&(m, k)| k <= high && k + m < N / 2)
+                    .find(|&(m, k)| {
+                        self.magnitude[k - m..=k + m]
+                            .iter()
+                            .any(|v| *v >= 0.1 * peak)
+                    })?;
+                // refined on the fundamental peak, the HPS maximum is only
+                // accurate to a bin (`m` bins when multiplied)
+                let k = argmax(&self.magnitude, k - m, k + m)?;
+                self.interpolate(&self.magnitude, k) * bin
+            }
+            Method::Cepstrum => {
+                // the log spectrum, mirrored as for a real signal
+                for k in 0..N / 2 {
+                    self.re[k] = libm::logf(self.magnitude[k] + EPSILON);
+                }
+                self.re[N / 2] = self.re[N / 2 - 1];
+                for k in 1..N / 2 {
+                    self.re[N - k] = self.re[k];
+                }
+                self.im.fill(0.0);
+                fft(&mut self.re, &mut self.im);
+                // quefrency in samples of the zero padded signal
+                let q_low = (libm::floorf(fs / self.max_freq) as usize).max(1);
+                let q_high = (libm::ceilf(fs / self.min_freq) as usize).min(N / 2 - 2);
+                if q_low >= q_high {
+                    return None;
+                }
+                let q = argmax(&self.re, q_low, q_high)?;
+                fs / quadratic(self.re[q - 1], self.re[q], self.re[q + 1], q)
+            }
+        };
+        if pitch.is_nan() || pitch <= 0.0 {
+            return None;
+        }
+
+        Some(Estimate {
+            pitch,
+            confidence: self.harmonicity(pitch / bin, self.window.lobe() * N.div_ceil(n.max(1))),
+        })
+    }
+
+    fn interpolate(&self, m: &[f32], k: usize) -> f32 {
+        if k == 0 || k + 1 >= m.len() {
+            return k as f32;
+        }
+        match self.interpolation {
+            Interpolation::None => k as f32,
+            Interpolation::Quadratic => quadratic(m[k - 1], m[k], m[k + 1], k),
+            Interpolation::Gaussian => quadratic(
+                libm::logf(m[k - 1] + EPSILON),
+                libm::logf(m[k] + EPSILON),
+                libm::logf(m[k + 1] + EPSILON),
+                k,
+            ),
+        }
+    }
+
+    // Fraction of the energy within `width` bins of the harmonics of `f0`
+    // (in bins).
+    fn harmonicity(&self, f0: f32, width: usize) -> f32 {
+        let m = self.spectrum();
+        let total: f32 = m[1..].iter().map(|v| v * v).sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let mut harmonic = 0.0;
+        for h in 1..=CONFIDENCE_HARMONICS {
+            let k = libm::roundf(f0 * h as f32) as usize;
+            if k + width >= m.len() {
+                break;
+            }
+            harmonic += m[k.saturating_sub(width).max(1)..=k + width]
+                .iter()
+                .map(|v| v * v)
+                .sum::<f32>();
+        }
+        (harmonic / total).min(1.0)
+    }
+}
+
+fn argmax(v: &[f32], low: usize, high: usize) -> Option<usize> {
+    (low..=high.min(v.len() - 1)).max_by(|a, b| v[*a].total_cmp(&v[*b]))
+}
+
+// Position of the vertex of the parabola through (k-1, a), (k, b), (k+1, c).
+fn quadratic(a: f32, b: f32, c: f32, k: usize) -> f32 {
+    let d = a - 2.0 * b + c;
+    if d >= 0.0 {
+        return k as f32;
+    }
+    k as f32 + (0.5 * (a - c) / d).clamp(-0.5, 0.5)
+}
+
+#[test]
+fn fft_test() {
+    // impulse gives a flat spectrum, a cosine two lines
+    let mut re = [0.0; 8];
+    let mut im = [0.0; 8];
+    re[0] = 1.0;
+    fft(&mut re, &mut im);
+    assert!(re.iter().all(|v| (v - 1.0).abs() < 1e-6));
+    assert!(im.iter().all(|v| v.abs() < 1e-6));
+
+    let mut re: [f32; 16] =
+        core::array::from_fn(|i| libm::cosf(2.0 * core::f32::consts::PI * 3.0 * i as f32 / 16.0));
+    let mut im = [0.0; 16];
+    fft(&mut re, &mut im);
+    for (k, v) in re.iter().enumerate() {
+        let expected = if k == 3 || k == 13 { 8.0 } else { 0.0 };
+        assert!((v - expected).abs() < 1e-4, "bin {} {}", k, v);
+    }
+}
+
+#[cfg(test)]
+use crate::testing::{sine, FS};
+
+// Guitar like tone, weak fundamental and strong second harmonic.
+#[cfg(test)]
+fn tone(f: f32) -> [f32; 4096] {
+    core::array::from_fn(|i| {
+        let t = i as f32 / FS;
+        [0.3, 1.0, 0.6, 0.4, 0.2]
+            .iter()
+            .enumerate()
+            .map(|(k, a)| {
+                0.2 * a * libm::sinf(2.0 * core::f32::consts::PI * (k + 1) as f32 * f * t)
+            })
+            .sum()
+    })
+}
+
+#[test]
+fn detect_test() {
+    let mut d = Detector::<8192>::new();
+    for f in [82.41, 110.0, 146.83, 196.0, 246.94, 329.63] {
+        let x = tone(f);
+        let e = d.detect(&x, FS).unwrap();
+        // within 2 cents, a bin is ~1 Hz
+        assert!(crate::note::cents(e.pitch, f).abs() < 2.0, "{} {:?}", f, e);
+        assert!(e.confidence > 0.9);
+        assert!((e.period(FS) - FS / f).abs() < 0.2);
+    }
+
+    // the strongest peak is the second harmonic
+    d.method = Method::Peak;
+    let e = d.detect(&tone(110.0), FS).unwrap();
+    assert!((e.pitch - 220.0).abs() < 1.0);
+
+    d.method = Method::Cepstrum;
+    let e = d.detect(&tone(110.0), FS).unwrap();
+    assert!(crate::note::cents(e.pitch, 110.0).abs() < 20.0, "{:?}", e);
+
+    // above the range, not taken for its sub-harmonic nor beyond max_freq
+    d.method = Method::Hps(4);
+    for f in [1500.0, 2000.0, 3000.0] {
+        let x: [f32; 4096] = core::array::from_fn(|i| 0.5 * sine(f, FS, i));
+        assert!(d.detect(&x, FS).is_none(), "{}", f);
+    }
+
+    // silence
+    assert!(d
+        .detect(&[0.0; 1024], FS)
+        .is_none_or(|e| e.confidence < 0.5));
+}
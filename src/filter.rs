@@ -0,0 +1,351 @@
+// filter, pre-processing chain of biquads ahead of the pitch detectors
+//
+// A chain is declared as stages separated by commas, applied in order:
+//
+// - `dc`: DC blocker, a one pole high-pass at a few Hz
+// - `hp:70`: high-pass (Butterworth) against rumble below the fundamental
+// - `lp:1500`: low-pass (Butterworth) against the upper harmonics
+// - `notch:50`: notch against mains hum (50 or 60 Hz), Q 10 unless given,
+//   e.g. `notch:60:30`
+// - `pre:0.9`: pre-emphasis, y[n] = x[n] - a x[n-1]
+// - `hum`: hum rejection, detected (see `hum`) or of a mains frequency,
+//   e.g. `hum:50`
+//
+// or `none`. The chain is parsed independently of the sample rate, and
+// built for a sample rate by `Chain::build`.
+
+use core::{fmt, str::FromStr};
+
+use biquad::{Biquad, Coefficients, DirectForm2Transposed, Type, Q_BUTTERWORTH_F32};
+
+use crate::hum::Hum;
+
+/// Maximum number of stages of a chain.
+pub const MAX_STAGES: usize = 8;
+
+/// Cutoff of the DC blocker in Hz.
+pub const DC_CUTOFF: f32 = 5.0;
+
+/// Q of a notch unless given.
+pub const NOTCH_Q: f32 = 10.0;
+
+/// A stage of a chain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    /// DC blocker.
+    Dc,
+    /// High-pass at a frequency in Hz.
+    HighPass(f32),
+    /// Low-pass at a frequency in Hz.
+    LowPass(f32),
+    /// Notch at a frequency in Hz, of a Q.
+    Notch(f32, f32),
+    /// Pre-emphasis by a coefficient.
+    PreEmphasis(f32),
+    /// Hum rejection, of a mains frequency in Hz or detected.
+    Hum(Option<f32>),
+}
+
+impl Stage {
+    // The section at `fs`, None if the frequency is not below Nyquist.
+    fn section(self, fs: f32) -> Option<Section> {
+        match self {
+            Stage::Hum(None) => Some(Section::Hum(Hum::new(fs))),
+            Stage::Hum(Some(f)) if f < fs / 2.0 => Some(Section::Hum(Hum::fixed(fs, f))),
+            Stage::Hum(_) => None,
+            stage => Some(Section::Biquad(DirectForm2Transposed::new(
+                stage.coefficients(fs)?,
+            ))),
+        }
+    }
+
+    fn coefficients(self, fs: f32) -> Option<Coefficients<f32>> {
+        // normalized to Nyquist (`from_params` of biquad 0.5 is off by 4)
+        let params = |kind, f: f32, q| match f > 0.0 && f < fs / 2.0 {
+            true => Coefficients::from_normalized_params(kind, 2.0 * f / fs, q).ok(),
+            false => None,
+        };
+        match self {
+            Stage::Dc => {
+                let r = 1.0 - 2.0 * core::f32::consts::PI * DC_CUTOFF / fs;
+                Some(Coefficients {
+                    a1: -r,
+                    a2: 0.0,
+                    b0: 1.0,
+                    b1: -1.0,
+                    b2: 0.0,
+                })
+            }
+            Stage::HighPass(f) => params(Type::HighPass, f, Q_BUTTERWORTH_F32),
+            Stage::LowPass(f) => params(Type::LowPass, f, Q_BUTTERWORTH_F32),
+            Stage::Notch(f, q) => params(Type::Notch, f, q),
+            Stage::PreEmphasis(a) => Some(Coefficients {
+                a1: 0.0,
+                a2: 0.0,
+                b0: 1.0,
+                b1: -a,
+                b2: 0.0,
+            }),
+            Stage::Hum(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Dc => write!(f, "dc"),
+            Stage::HighPass(freq) => write!(f, "hp:{}", freq),
+            Stage::LowPass(freq) => write!(f, "lp:{}", freq),
+            Stage::Notch(freq, q) if *q == NOTCH_Q => write!(f, "notch:{}", freq),
+            Stage::Notch(freq, q) => write!(f, "notch:{}:{}", freq, q),
+            Stage::PreEmphasis(a) => write!(f, "pre:{}", a),
+            Stage::Hum(None) => write!(f, "hum"),
+            Stage::Hum(Some(mains)) => write!(f, "hum:{}", mains),
+        }
+    }
+}
+
+/// The stages of a pre-processing chain, none by default.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Chain {
+    stages: [Option<Stage>; MAX_STAGES],
+    len: usize,
+}
+
+impl Chain {
+    pub const NONE: Chain = Chain {
+        stages: [None; MAX_STAGES],
+        len: 0,
+    };
+
+    /// Panics if more than `MAX_STAGES` stages are given.
+    pub const fn new(stages: &[Stage]) -> Chain {
+        assert!(stages.len() <= MAX_STAGES);
+        let mut chain = Chain::NONE;
+        while chain.len < stages.len() {
+            chain.stages[chain.len] = Some(stages[chain.len]);
+            chain.len += 1;
+        }
+        chain
+    }
+
+    pub fn stages(&self) -> impl Iterator<Item = Stage> + '_ {
+        self.stages[..self.len].iter().flatten().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The filter of the chain at `fs`, None if a frequency is not below
+    /// the Nyquist frequency.
+    pub fn build(&self, fs: f32) -> Option<Filter> {
+        let mut filter = Filter {
+            sections: [None; MAX_STAGES],
+        };
+        for (section, stage) in filter.sections.iter_mut().zip(self.stages()) {
+            *section = Some(stage.section(fs)?);
+        }
+        Some(filter)
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        for (i, stage) in self.stages().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", stage)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseChainError;
+
+impl fmt::Display for ParseChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid filter, expected \"none\" or stages separated by commas, \"dc\", \"hp:HZ\", \"lp:HZ\", \"notch:HZ[:Q]\", \"pre:COEFFICIENT\" or \"hum[:HZ]\", e.g. \"dc,hp:70,notch:50\""
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseChainError {}
+
+impl FromStr for Chain {
+    type Err = ParseChainError;
+
+    /// Parses "none" or up to `MAX_STAGES` stages separated by commas.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chain = Chain::NONE;
+        if s.trim() == "none" {
+            return Ok(chain);
+        }
+        for stage in s.split(',').map(str::trim) {
+            if chain.len == MAX_STAGES {
+                return Err(ParseChainError);
+            }
+            let (name, args) = stage.split_once(':').unwrap_or((stage, ""));
+            let mut numbers = [0.0; 2];
+            let mut n = 0;
+            for arg in args.split(':').filter(|_| !args.is_empty()) {
+                if n == numbers.len() {
+                    return Err(ParseChainError);
+                }
+                numbers[n] = arg
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|x| x.is_finite() && *x > 0.0)
+                    .ok_or(ParseChainError)?;
+                n += 1;
+            }
+            let stage = match (name, &numbers[..n]) {
+                ("dc", []) => Stage::Dc,
+                ("hp", [f]) => Stage::HighPass(*f),
+                ("lp", [f]) => Stage::LowPass(*f),
+                ("notch", [f]) => Stage::Notch(*f, NOTCH_Q),
+                ("notch", [f, q]) => Stage::Notch(*f, *q),
+                ("pre", [a]) if *a < 1.0 => Stage::PreEmphasis(*a),
+                ("hum", []) => Stage::Hum(None),
+                ("hum", [f]) => Stage::Hum(Some(*f)),
+                _ => return Err(ParseChainError),
+            };
+            chain.stages[chain.len] = Some(stage);
+            chain.len += 1;
+        }
+        Ok(chain)
+    }
+}
+
+// A stage built for a sample rate.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Copy)]
+enum Section {
+    Biquad(DirectForm2Transposed<f32>),
+    Hum(Hum),
+}
+
+/// A chain built for a sample rate.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter {
+    sections: [Option<Section>; MAX_STAGES],
+}
+
+impl Filter {
+    /// Filters the next sample.
+    pub fn run(&mut self, mut x: f32) -> f32 {
+        for section in self.sections.iter_mut().flatten() {
+            x = match section {
+                Section::Biquad(biquad) => biquad.run(x),
+                Section::Hum(hum) => hum.run(x),
+            };
+        }
+        x
+    }
+
+    /// Filters the next `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for x in samples {
+            *x = self.run(*x);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for section in self.sections.iter_mut().flatten() {
+            match section {
+                Section::Biquad(biquad) => biquad.reset_state(),
+                Section::Hum(hum) => hum.reset(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::testing::{sine, DEVICE_FS as FS};
+
+// RMS of a second of a sine of `freq` after `filter`, less the first half
+// second of transients.
+#[cfg(test)]
+fn rms(filter: &mut Filter, freq: f32, offset: f32) -> f32 {
+    let mut sum = 0.0;
+    for i in 0..FS as usize {
+        let y = filter.run(offset + sine(freq, FS, i));
+        if i >= FS as usize / 2 {
+            sum += y * y;
+        }
+    }
+    libm::sqrtf(sum / (FS / 2.0))
+}
+
+#[test]
+fn parse_test() {
+    let chain: Chain = "dc, hp:70,lp:1500,notch:50,notch:60:30,pre:0.9,hum,hum:60"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        chain,
+        Chain::new(&[
+            Stage::Dc,
+            Stage::HighPass(70.0),
+            Stage::LowPass(1500.0),
+            Stage::Notch(50.0, NOTCH_Q),
+            Stage::Notch(60.0, 30.0),
+            Stage::PreEmphasis(0.9),
+            Stage::Hum(None),
+            Stage::Hum(Some(60.0)),
+        ])
+    );
+    assert_eq!(chain.to_string().parse(), Ok(chain));
+    assert_eq!("none".parse(), Ok(Chain::NONE));
+    assert_eq!(Chain::NONE.to_string(), "none");
+    for s in [
+        "",
+        "hp",
+        "hp:-70",
+        "lp:x",
+        "notch:50:10:1",
+        "pre:1",
+        "bp:100",
+    ] {
+        assert_eq!(s.parse::<Chain>(), Err(ParseChainError), "{}", s);
+    }
+    assert!(Chain::new(&[Stage::LowPass(30000.0)]).build(FS).is_none());
+}
+
+#[test]
+fn filter_test() {
+    let sine = core::f32::consts::FRAC_1_SQRT_2;
+    // the offset removed, the sine kept
+    let mut dc = Chain::new(&[Stage::Dc]).build(FS).unwrap();
+    assert!((rms(&mut dc, 82.41, 0.5) - sine).abs() < 0.01);
+
+    // rumble and hum gone, low E kept
+    let chain: Chain = "hp:70,notch:50".parse().unwrap();
+    let mut filter = chain.build(FS).unwrap();
+    assert!(rms(&mut filter, 20.0, 0.0) < 0.1 * sine);
+    filter.reset();
+    assert!(rms(&mut filter, 50.0, 0.0) < 0.01 * sine);
+    filter.reset();
+    assert!(rms(&mut filter, 82.41, 0.0) > 0.7 * sine);
+
+    // the upper harmonics down, the fundamental kept
+    let mut lp = Chain::new(&[Stage::LowPass(500.0)]).build(FS).unwrap();
+    assert!(rms(&mut lp, 4000.0, 0.0) < 0.02 * sine);
+    lp.reset();
+    assert!((rms(&mut lp, 110.0, 0.0) - sine).abs() < 0.01);
+
+    // pre-emphasis tilts the spectrum up
+    let mut pre = Chain::new(&[Stage::PreEmphasis(0.9)]).build(FS).unwrap();
+    let low = rms(&mut pre, 100.0, 0.0);
+    pre.reset();
+    assert!(rms(&mut pre, 4000.0, 0.0) > 4.0 * low);
+}
@@ -0,0 +1,355 @@
+// harmonics, analysis of the partials of a plucked note
+//
+// Given (an estimate of) the fundamental, each harmonic is searched for
+// close to where it is expected and measured by the Goertzel algorithm (as
+// in `strum`). Strings are stiff, so the partials are stretched, following
+// f_k = k f0 sqrt(1 + B k^2) where B is the inharmonicity coefficient. B is
+// fitted to the harmonics found so far to predict the next one.
+//
+// Harmonics above the Nyquist frequency fold back into the spectrum when
+// sampled without an anti-aliasing filter (e.g., the SAADC at 1kHz), these
+// are measured at their alias. The decay of each harmonic is fitted to its
+// level over consecutive frames.
+//
+// Stretch tuning (as for pianos) compensates for the inharmonicity: each
+// interval between adjacent strings is tuned such that the partials the
+// strings have in common (e.g., the 4th of the lower and the 3rd of the
+// upper string for a fourth) are as far apart as for ideal strings. Starting
+// from a reference string, the strings above end up sharp and those below
+// flat, the more so the more inharmonic the strings (e.g., wound bass
+// strings).
+
+use crate::{
+    strum::{amplitude, search},
+    tuning::{Tuning, MAX_STRINGS},
+};
+
+/// Maximum number of harmonics analyzed.
+pub const MAX_HARMONICS: usize = 16;
+/// Candidate spacing of the coarse and fine search in cents.
+const COARSE: f32 = 5.0;
+const FINE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Harmonic {
+    /// Predicted frequency in Hz.
+    pub expected: f32,
+    /// Measured frequency in Hz, that of the alias if `aliased`.
+    pub freq: f32,
+    /// Level at the start of the signal in dBFS.
+    pub level: f32,
+    /// Time to decay by 60 dB in seconds, infinite if not decaying and NaN
+    /// if too weak or short to tell.
+    pub decay: f32,
+    /// Above the Nyquist frequency, folded back to `freq`.
+    pub aliased: bool,
+}
+
+/// Result of `Analyzer::analyze`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Analysis {
+    /// Frequency of the first harmonic in Hz.
+    pub fundamental: f32,
+    /// Inharmonicity coefficient B, given three or more harmonics.
+    pub inharmonicity: Option<f32>,
+    harmonics: [Harmonic; MAX_HARMONICS],
+    len: usize,
+}
+
+impl Analysis {
+    /// The harmonics, the fundamental first.
+    pub fn harmonics(&self) -> &[Harmonic] {
+        &self.harmonics[..self.len]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Analyzer {
+    /// Number of harmonics, at most `MAX_HARMONICS`.
+    pub harmonics: usize,
+    /// Search range around each expected harmonic in cents.
+    pub range: f32,
+    /// Length of the signal used to measure frequencies in seconds.
+    pub window: f32,
+    /// Length of the frames used to measure the decay in seconds.
+    pub frame: f32,
+    /// Harmonics below this level (dBFS) are ignored.
+    pub floor: f32,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analyzer {
+    pub const fn new() -> Self {
+        Analyzer {
+            harmonics: 8,
+            range: 50.0,
+            window: 1.0,
+            frame: 0.1,
+            floor: -70.0,
+        }
+    }
+
+    /// Analyzes `samples` at sample rate `fs`, starting after the attack,
+    /// with the fundamental close to `f0` Hz.
+    pub fn analyze(&self, samples: &[f32], fs: f32, f0: f32) -> Analysis {
+        let window = &samples[..((self.window * fs) as usize).min(samples.len())];
+        let none = Harmonic {
+            expected: 0.0,
+            freq: 0.0,
+            level: f32::NEG_INFINITY,
+            decay: f32::NAN,
+            aliased: false,
+        };
+        let mut analysis = Analysis {
+            fundamental: f0,
+            inharmonicity: None,
+            harmonics: [none; MAX_HARMONICS],
+            len: self.harmonics.min(MAX_HARMONICS),
+        };
+
+        // (harmonic number, frequency) of the harmonics found in band
+        let mut partials = [(0, 0.0); MAX_HARMONICS];
+        let mut found = 0;
+        for (i, h) in analysis.harmonics[..analysis.len].iter_mut().enumerate() {
+            let k = i + 1;
+            h.expected = match inharmonicity(&partials[..found]) {
+                Some((f, b)) => stretch(f, b.max(0.0), k),
+                None if found > 0 => k as f32 * partials[0].1 / partials[0].0 as f32,
+                None => k as f32 * f0,
+            };
+            h.aliased = h.expected >= fs / 2.0;
+
+            // an alias might fall next to a (stronger) harmonic in band, it is
+            // only searched for close to where predicted
+            let center = fold(h.expected, fs);
+            let range = if h.aliased { 2.0 * COARSE } else { self.range };
+            let score = |c: f32| amplitude(window, fs, center * libm::exp2f(c / 1200.0));
+            let (coarse, _) = search(-range, range, COARSE, score);
+            let (c, _) = search(coarse - COARSE, coarse + COARSE, FINE, score);
+            h.freq = center * libm::exp2f(c / 1200.0);
+            h.level = 20.0 * libm::log10f(amplitude(window, fs, h.freq));
+            if h.level >= self.floor {
+                if let Some((level, slope)) = self.decay(samples, fs, h.freq) {
+                    h.level = level;
+                    h.decay = if slope < 0.0 {
+                        -60.0 / slope
+                    } else {
+                        f32::INFINITY
+                    };
+                }
+                if !h.aliased {
+                    partials[found] = (k, h.freq);
+                    found += 1;
+                }
+            }
+        }
+        analysis.fundamental = analysis.harmonics[0].freq;
+        analysis.inharmonicity = inharmonicity(&partials[..found]).map(|(_, b)| b);
+        analysis
+    }
+
+    // Initial level and decay in dB/s, fitted to the level of `f` per frame
+    // until it falls below the floor.
+    fn decay(&self, samples: &[f32], fs: f32, f: f32) -> Option<(f32, f32)> {
+        let len = ((self.frame * fs) as usize).max(2);
+        let mut r = Regression::default();
+        for (i, frame) in samples.chunks_exact(len).enumerate() {
+            let level = 20.0 * libm::log10f(amplitude(frame, fs, f));
+            if level < self.floor {
+                break;
+            }
+            r.add((i as f64 + 0.5) * len as f64 / fs as f64, level as f64);
+        }
+        let (level, slope) = r.line().filter(|_| r.n >= 3)?;
+        Some((level as f32, slope as f32))
+    }
+}
+
+/// Frequency of harmonic `k` of a string with fundamental `f0` and
+/// inharmonicity coefficient `b`.
+pub fn stretch(f0: f32, b: f32, k: usize) -> f32 {
+    let k = k as f32;
+    k * f0 * libm::sqrtf(1.0 + b * k * k)
+}
+
+// Partials (of the lower, of the upper note) in common for an interval of
+// 0..12 semitones, the ratios of small integers closest to equal temperament.
+const COMMON: [(usize, usize); 12] = [
+    (1, 1),
+    (16, 15),
+    (9, 8),
+    (6, 5),
+    (5, 4),
+    (4, 3),
+    (7, 5),
+    (3, 2),
+    (8, 5),
+    (5, 3),
+    (9, 5),
+    (15, 8),
+];
+
+/// Offsets in cents stretching the targets of `tuning` for strings with
+/// inharmonicity coefficients `b` (per string, missing are taken as 0), the
+/// `reference` string is kept at its target.
+pub fn stretch_tuning(tuning: &Tuning, b: &[f32], reference: usize) -> [f32; MAX_STRINGS] {
+    let notes = tuning.strings();
+    let b = |s: usize| b.get(s).copied().unwrap_or(0.0);
+    // offset of string `to` relative to its neighbour `from`
+    let step = |from: usize, to: usize| {
+        let (low, high) = if notes[from] <= notes[to] {
+            (from, to)
+        } else {
+            (to, from)
+        };
+        let semitones = (notes[high].0 - notes[low].0) as usize;
+        let (p, q) = COMMON[semitones % 12];
+        let (p, q) = (p << (semitones / 12), q);
+        // the partials stretch by sqrt(1 + B k^2)
+        let s_low = libm::sqrtf(1.0 + b(low) * (p * p) as f32);
+        let s_high = libm::sqrtf(1.0 + b(high) * (q * q) as f32);
+        let c = 1200.0 * libm::log2f(s_low / s_high);
+        if high == to {
+            c
+        } else {
+            -c
+        }
+    };
+
+    let mut offsets = [0.0; MAX_STRINGS];
+    for s in reference + 1..notes.len() {
+        offsets[s] = offsets[s - 1] + step(s - 1, s);
+    }
+    for s in (0..reference.min(notes.len())).rev() {
+        offsets[s] = offsets[s + 1] + step(s + 1, s);
+    }
+    offsets
+}
+
+/// Fits f0 and the inharmonicity coefficient B to (harmonic number,
+/// frequency) pairs, needs at least three harmonics.
+pub fn inharmonicity(partials: &[(usize, f32)]) -> Option<(f32, f32)> {
+    if partials.len() < 3 {
+        return None;
+    }
+    // (f_k / k)^2 = f0^2 + f0^2 B k^2 is linear in k^2
+    let mut r = Regression::default();
+    for &(k, f) in partials {
+        let k = k as f64;
+        r.add(k * k, (f as f64 / k) * (f as f64 / k));
+    }
+    let (a, b) = r.line()?;
+    (a > 0.0).then(|| (libm::sqrt(a) as f32, (b / a) as f32))
+}
+
+/// The frequency `f` is observed at when sampled at `fs`.
+pub fn fold(f: f32, fs: f32) -> f32 {
+    let f = libm::fmodf(libm::fabsf(f), fs);
+    if f > fs / 2.0 {
+        fs - f
+    } else {
+        f
+    }
+}
+
+// Least squares line fit, accumulated point by point.
+#[derive(Debug, Default, Clone, Copy)]
+struct Regression {
+    n: usize,
+    x: f64,
+    y: f64,
+    xx: f64,
+    xy: f64,
+}
+
+impl Regression {
+    fn add(&mut self, x: f64, y: f64) {
+        self.n += 1;
+        self.x += x;
+        self.y += y;
+        self.xx += x * x;
+        self.xy += x * y;
+    }
+
+    // Intercept and slope.
+    fn line(&self) -> Option<(f64, f64)> {
+        let n = self.n as f64;
+        let d = n * self.xx - self.x * self.x;
+        if self.n < 2 || d <= 0.0 {
+            return None;
+        }
+        let slope = (n * self.xy - self.x * self.y) / d;
+        Some(((self.y - slope * self.x) / n, slope))
+    }
+}
+
+#[cfg(test)]
+const FS: f32 = 4000.0;
+
+#[test]
+fn fold_test() {
+    assert_eq!(fold(1000.0, FS), 1000.0);
+    assert_eq!(fold(2500.0, FS), 1500.0);
+    assert_eq!(fold(4100.0, FS), 100.0);
+}
+
+#[test]
+fn analyze_test() {
+    // B3 with B = 1e-4, harmonics 9 and 10 above Nyquist, the fundamental
+    // decaying in 4 s and the higher harmonics faster
+    let (f0, b) = (246.94, 1e-4);
+    let mut x = [0.0; 2 * FS as usize];
+    for k in 1..=10 {
+        let f = stretch(f0, b, k);
+        let t60 = 2.0 + 2.0 / k as f32;
+        for (i, y) in x.iter_mut().enumerate() {
+            let t = i as f32 / FS;
+            let a = 0.2 / k as f32 * libm::exp10f(-3.0 * t / t60);
+            *y += a * libm::sinf(2.0 * core::f32::consts::PI * f * t);
+        }
+    }
+
+    let mut analyzer = Analyzer::new();
+    analyzer.harmonics = 10;
+    let a = analyzer.analyze(&x, FS, 245.0);
+    assert!(crate::note::cents(a.fundamental, stretch(f0, b, 1)).abs() < 1.0);
+    let fitted = a.inharmonicity.unwrap();
+    assert!((fitted - b).abs() < 2e-5, "{}", fitted);
+
+    for (i, h) in a.harmonics().iter().enumerate() {
+        let k = i + 1;
+        assert_eq!(h.aliased, k > 8);
+        let f = fold(stretch(f0, b, k), FS);
+        assert!((h.freq - f).abs() < 1.0, "{} {:?}", k, h);
+        assert!((h.level - 20.0 * libm::log10f(0.2 / k as f32)).abs() < 1.0);
+        let t60 = 2.0 + 2.0 / k as f32;
+        assert!((h.decay - t60).abs() < 0.1 * t60, "{} {:?}", k, h);
+    }
+}
+
+#[test]
+fn stretch_tuning_test() {
+    // ideal strings are not stretched
+    let t = Tuning::BASS;
+    assert_eq!(stretch_tuning(&t, &[], 3), [0.0; MAX_STRINGS]);
+
+    // fourths, the 4th partial of the lower string meets the 3rd of the
+    // upper one
+    let b = [4e-4, 3e-4, 2e-4, 1e-4];
+    let offsets = stretch_tuning(&t, &b, 3);
+    assert_eq!(offsets[3], 0.0);
+    for s in 0..3 {
+        let lower = t.target(s, 440.0) * libm::exp2f(offsets[s] / 1200.0);
+        let upper = t.target(s + 1, 440.0) * libm::exp2f(offsets[s + 1] / 1200.0);
+        let ideal = crate::note::cents(3.0 * t.target(s + 1, 440.0), 4.0 * t.target(s, 440.0));
+        let beat = crate::note::cents(stretch(upper, b[s + 1], 3), stretch(lower, b[s], 4));
+        assert!((beat - ideal).abs() < 0.01, "{} {}", beat, ideal);
+        // flat below the reference
+        assert!(offsets[s] < offsets[s + 1]);
+    }
+}
@@ -0,0 +1,406 @@
+// hum, detection and rejection of mains hum
+//
+// Pickups and cables pick up hum at the mains frequency (50 or 60 Hz) and
+// its harmonics, next to the fundamentals of the low strings. The hum is
+// measured over blocks of `BLOCK` seconds by Goertzel filters at the first
+// harmonics of both mains frequencies and `SIDE` Hz to either side. A
+// harmonic stands out when above its sides by `threshold` dB, which a
+// string near it does as well (a low string at 100 Hz, its octave at 150
+// Hz). So hum needs more than one harmonic standing out, at a level within
+// `LEVEL` dB over `PERSIST` blocks in a row, a stationary line that the
+// partials of a string, decaying, do not produce. The mains frequency with
+// the most hum is then taken as detected, and no hum over as many blocks
+// stops the rejection.
+//
+// Detected hum is cancelled by an adaptive comb: per harmonic an LMS
+// canceller, fed a cosine and sine reference at the harmonic, subtracts its
+// estimate of the hum from the signal. It follows the amplitude, the phase
+// of the hum, acting as notches of about `bandwidth` Hz that leave the
+// strings in between untouched. The mains frequency drifts (by up to a few
+// tenths of a Hz), turning the weights of the fundamental, so the rate they
+// turn at corrects the frequency of the references.
+
+use core::f32::consts::PI;
+
+/// Mains frequencies in Hz.
+pub const MAINS: [f32; 2] = [50.0, 60.0];
+
+/// Maximum number of harmonics cancelled.
+pub const MAX_HARMONICS: usize = 8;
+
+/// Harmonics measured for detection.
+const DETECTED: usize = 3;
+
+/// Distance of the sides of a harmonic in Hz.
+const SIDE: f32 = 4.0;
+
+/// Detection block in seconds.
+const BLOCK: f32 = 0.5;
+
+/// Blocks in a row the hum (or its absence) is detected in.
+const PERSIST: usize = 4;
+
+/// Largest change in dB of the level of hum over these blocks.
+const LEVEL: f32 = 1.0;
+
+/// Largest drift of the mains frequency in Hz.
+const DRIFT: f32 = 0.5;
+
+// Power of one frequency over a block.
+#[derive(Debug, Clone, Copy)]
+struct Goertzel {
+    coefficient: f32,
+    s1: f32,
+    s2: f32,
+}
+
+impl Goertzel {
+    fn new(f: f32, fs: f32) -> Self {
+        Goertzel {
+            coefficient: 2.0 * libm::cosf(2.0 * PI * f / fs),
+            s1: 0.0,
+            s2: 0.0,
+        }
+    }
+
+    fn push(&mut self, x: f32) {
+        let s = x + self.coefficient * self.s1 - self.s2;
+        self.s2 = self.s1;
+        self.s1 = s;
+    }
+
+    // The power of the block, starting the next.
+    fn power(&mut self) -> f32 {
+        let power = self.s1 * self.s1 + self.s2 * self.s2 - self.coefficient * self.s1 * self.s2;
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+        power
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hum {
+    /// Sample rate in Hz.
+    pub fs: f32,
+    /// Level of a harmonic above its sides in dB to detect hum.
+    pub threshold: f32,
+    /// Harmonics cancelled (at most `MAX_HARMONICS`).
+    pub harmonics: usize,
+    /// Width of the notches in Hz.
+    pub bandwidth: f32,
+    mains: Option<f32>,
+    fixed: bool,
+    // the tracked mains frequency
+    freq: f32,
+    // per mains frequency and harmonic, below, at and above
+    goertzel: [[[Goertzel; 3]; DETECTED]; MAINS.len()],
+    n: usize,
+    // the mains frequency detected in the last blocks, its level, the count
+    candidate: Option<f32>,
+    level: f32,
+    count: usize,
+    // the reference at the fundamental and its rotation per sample
+    phasor: (f32, f32),
+    rotation: (f32, f32),
+    weights: [(f32, f32); MAX_HARMONICS],
+    // samples until the next correction, the last angle of the fundamental
+    correction: usize,
+    angle: Option<f32>,
+}
+
+impl Hum {
+    /// Detects hum of either mains frequency at sample rate `fs`.
+    pub fn new(fs: f32) -> Self {
+        let goertzel = MAINS.map(|mains| {
+            core::array::from_fn(|k| {
+                let f = (k + 1) as f32 * mains;
+                [f - SIDE, f, f + SIDE].map(|f| Goertzel::new(f, fs))
+            })
+        });
+        Hum {
+            fs,
+            threshold: 10.0,
+            harmonics: 6,
+            bandwidth: 1.0,
+            mains: None,
+            fixed: false,
+            freq: 0.0,
+            goertzel,
+            n: 0,
+            candidate: None,
+            level: 0.0,
+            count: 0,
+            phasor: (1.0, 0.0),
+            rotation: (1.0, 0.0),
+            weights: [(0.0, 0.0); MAX_HARMONICS],
+            correction: 0,
+            angle: None,
+        }
+    }
+
+    /// Cancels hum of `mains` Hz, without detection.
+    pub fn fixed(fs: f32, mains: f32) -> Self {
+        let mut hum = Hum::new(fs);
+        hum.fixed = true;
+        hum.lock(Some(mains));
+        hum
+    }
+
+    /// The mains frequency of the hum cancelled, if any.
+    pub fn mains(&self) -> Option<f32> {
+        self.mains
+    }
+
+    /// The frequency of the hum cancelled in Hz, as tracked.
+    pub fn frequency(&self) -> Option<f32> {
+        self.mains.map(|_| self.freq)
+    }
+
+    /// Filters the next sample.
+    pub fn run(&mut self, x: f32) -> f32 {
+        if !self.fixed {
+            self.detect(x);
+        }
+        if self.mains.is_none() {
+            return x;
+        }
+
+        // the estimate of the hum, from the references at the harmonics
+        let (c1, s1) = self.phasor;
+        let (mut c, mut s) = (c1, s1);
+        let mut references = [(0.0, 0.0); MAX_HARMONICS];
+        for reference in references.iter_mut().take(self.len()) {
+            *reference = (c, s);
+            (c, s) = (c * c1 - s * s1, s * c1 + c * s1);
+        }
+        let estimate: f32 = references
+            .iter()
+            .zip(&self.weights)
+            .map(|((c, s), (wc, ws))| c * wc + s * ws)
+            .sum();
+        let y = x - estimate;
+
+        // a notch of `bandwidth` Hz per harmonic
+        let mu = 2.0 * PI * self.bandwidth / self.fs;
+        for ((c, s), (wc, ws)) in references.iter().zip(&mut self.weights) {
+            *wc += mu * y * c;
+            *ws += mu * y * s;
+        }
+        self.track();
+        let (rc, rs) = self.rotation;
+        let (c, s) = (c1 * rc - s1 * rs, s1 * rc + c1 * rs);
+        // kept on the unit circle
+        let gain = 1.5 - 0.5 * (c * c + s * s);
+        self.phasor = (c * gain, s * gain);
+        y
+    }
+
+    /// Forgets the hum, detected again unless fixed.
+    pub fn reset(&mut self) {
+        for goertzel in self.goertzel.iter_mut().flatten().flatten() {
+            goertzel.power();
+        }
+        self.n = 0;
+        self.candidate = None;
+        self.count = 0;
+        self.lock(if self.fixed { self.mains } else { None });
+    }
+
+    /// Filters the next `samples` in place.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for x in samples {
+            *x = self.run(*x);
+        }
+    }
+
+    // The harmonics cancelled, below Nyquist.
+    fn len(&self) -> usize {
+        let below = self.mains.map_or(0, |f| (0.5 * self.fs / f - 0.5) as usize);
+        self.harmonics.min(MAX_HARMONICS).min(below)
+    }
+
+    fn detect(&mut self, x: f32) {
+        for goertzel in self.goertzel.iter_mut().flatten().flatten() {
+            goertzel.push(x);
+        }
+        self.n += 1;
+        if (self.n as f32) < BLOCK * self.fs {
+            return;
+        }
+        self.n = 0;
+
+        // the mains frequency with the most power in the harmonics standing
+        // out, more than one
+        let threshold = libm::powf(10.0, self.threshold / 10.0);
+        let mut detected = None;
+        let mut most = 0.0;
+        for (mains, harmonics) in MAINS.iter().zip(&mut self.goertzel) {
+            let (mut count, mut power) = (0, 0.0);
+            for [below, at, above] in harmonics {
+                let (below, at, above) = (below.power(), at.power(), above.power());
+                if at > threshold * below.max(above) {
+                    count += 1;
+                    power += at;
+                }
+            }
+            if count > 1 && power > most {
+                detected = Some(*mains);
+                most = power;
+            }
+        }
+
+        // over blocks in a row, at a steady level
+        let steady = match detected {
+            Some(_) => (10.0 * libm::log10f(most / self.level)).abs() <= LEVEL,
+            None => true,
+        };
+        if detected == self.candidate && steady {
+            self.count += 1;
+        } else {
+            self.candidate = detected;
+            self.level = most;
+            self.count = 1;
+        }
+        if self.count >= PERSIST && self.candidate != self.mains {
+            self.lock(self.candidate);
+        }
+    }
+
+    // Corrects the frequency by the turn of the weights of the fundamental
+    // (the hum a bit above the references turns them backwards).
+    fn track(&mut self) {
+        if self.correction > 0 {
+            self.correction -= 1;
+            return;
+        }
+        let period = 0.1 * self.fs;
+        self.correction = period as usize;
+        let (wc, ws) = self.weights[0];
+        let angle = libm::atan2f(ws, wc);
+        if let (Some(last), Some(mains)) = (self.angle, self.mains) {
+            let turn = match angle - last {
+                turn if turn > PI => turn - 2.0 * PI,
+                turn if turn < -PI => turn + 2.0 * PI,
+                turn => turn,
+            };
+            // half way, against the noise of the weights
+            let drift = -0.5 * turn / (2.0 * PI) * self.fs / period;
+            self.set_freq((self.freq + drift).clamp(mains - DRIFT, mains + DRIFT));
+        }
+        self.angle = Some(angle);
+    }
+
+    fn set_freq(&mut self, freq: f32) {
+        self.freq = freq;
+        let w = 2.0 * PI * freq / self.fs;
+        self.rotation = (libm::cosf(w), libm::sinf(w));
+    }
+
+    // Starts cancelling hum of `mains` Hz, if any.
+    fn lock(&mut self, mains: Option<f32>) {
+        self.mains = mains;
+        self.set_freq(mains.unwrap_or(0.0));
+        self.phasor = (1.0, 0.0);
+        self.weights = [(0.0, 0.0); MAX_HARMONICS];
+        self.correction = 0;
+        self.angle = None;
+    }
+}
+
+#[cfg(test)]
+use crate::testing::{string, FS};
+
+// An E2, as fed to the trackers in `track`.
+#[cfg(test)]
+fn e2(i: usize) -> f32 {
+    string(2.0 * PI * 82.41 * i as f32 / FS)
+}
+
+// Hum at about `mains` Hz (drifted), stronger than the string.
+#[cfg(test)]
+fn hum(i: usize, mains: f32) -> f32 {
+    let phase = 2.0 * PI * (mains + 0.05) * i as f32 / FS;
+    [0.2, 0.1, 0.05]
+        .iter()
+        .enumerate()
+        .map(|(k, a)| a * libm::sinf((k + 1) as f32 * phase + k as f32))
+        .sum()
+}
+
+// A plucked string of `f` Hz decaying over `decay` seconds (3 s, about 1.5
+// dB per block), steady if infinite.
+#[cfg(test)]
+fn plucked(i: usize, f: f32, decay: f32) -> f32 {
+    let t = i as f32 / FS;
+    string(2.0 * PI * f * t) * libm::expf(-t / decay)
+}
+
+#[test]
+fn detect_test() {
+    for mains in MAINS {
+        let mut h = Hum::new(FS);
+        for i in 0..3 * FS as usize {
+            h.run(e2(i) + hum(i, mains));
+        }
+        assert_eq!(h.mains(), Some(mains));
+    }
+    // a string alone is no hum
+    let mut h = Hum::new(FS);
+    for i in 0..2 * FS as usize {
+        assert_eq!(h.run(e2(i)), e2(i));
+    }
+    assert_eq!(h.mains(), None);
+    // nor a string at a harmonic, decaying or even steady (one harmonic
+    // standing out), or at the mains frequency (decaying)
+    for (f, decay) in [
+        (99.5, 3.0),
+        (100.0, 3.0),
+        (149.5, 3.0),
+        (100.0, f32::INFINITY),
+        (120.0, f32::INFINITY),
+        (50.0, 3.0),
+    ] {
+        let mut h = Hum::new(FS);
+        for i in 0..4 * FS as usize {
+            h.run(plucked(i, f, decay));
+        }
+        assert_eq!(h.mains(), None, "{} {}", f, decay);
+    }
+}
+
+#[test]
+fn cancel_test() {
+    // hum down by 20 dB, once settled
+    let mut h = Hum::new(FS);
+    let (mut residual, mut power) = (0.0, 0.0);
+    for i in 0..5 * FS as usize {
+        let y = h.run(e2(i) + hum(i, 50.0));
+        if i >= 4 * FS as usize {
+            residual += (y - e2(i)) * (y - e2(i));
+            power += hum(i, 50.0) * hum(i, 50.0);
+        }
+    }
+    assert!(residual < 0.01 * power, "{} {}", residual, power);
+    // following the drift
+    assert!((h.frequency().unwrap() - 50.05).abs() < 0.02);
+}
+
+#[test]
+fn lock_test() {
+    // the low E locks through the hum, only once it is rejected
+    for reject in [false, true] {
+        let mut h = Hum::new(FS);
+        let mut t = crate::track::Tracker::<512>::new(FS);
+        for i in 0..4 * FS as usize {
+            let x = e2(i) + hum(i, 60.0);
+            t.push(if reject { h.run(x) } else { x });
+            if i % 64 == 0 {
+                t.update();
+            }
+        }
+        let locked = t
+            .pitch()
+            .is_some_and(|pitch| crate::note::cents(pitch, 82.41).abs() < 1.0);
+        assert_eq!(locked, reject, "{:?}", t.pitch());
+    }
+}
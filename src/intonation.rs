@@ -0,0 +1,92 @@
+// intonation, open string vs. 12th fret
+//
+// A string is intonated when the note fretted at the 12th fret sounds an
+// octave above the open string (or at the harmonic at the 12th fret). When
+// it is sharp, the fretted length is too short and the saddle is moved back
+// (away from the nut), when flat, forward.
+//
+// Moving the saddle by `d` changes both lengths, the fretted one relatively
+// twice as much, so the octave changes by about `d / L` (L the scale
+// length), i.e., 1200 / ln(2) * d / L cents.
+
+use crate::note::cents;
+
+/// Fender scale length (25.5") in mm.
+pub const SCALE: f32 = 647.7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Saddle {
+    /// Towards the nut, the 12th fret is flat.
+    Forward,
+    InTune,
+    /// Away from the nut, the 12th fret is sharp.
+    Back,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Intonation {
+    /// Expected pitch at the 12th fret in Hz.
+    pub reference: f32,
+    /// Pitch fretted at the 12th fret in Hz.
+    pub fretted: f32,
+}
+
+impl Intonation {
+    /// The open string an octave below the 12th fret.
+    pub fn new(open: f32, fretted: f32) -> Self {
+        Intonation {
+            reference: 2.0 * open,
+            fretted,
+        }
+    }
+
+    /// The harmonic at the 12th fret as the reference.
+    pub fn harmonic(harmonic: f32, fretted: f32) -> Self {
+        Intonation {
+            reference: harmonic,
+            fretted,
+        }
+    }
+
+    /// Deviation of the fretted note in cents, positive if sharp.
+    pub fn cents(&self) -> f32 {
+        cents(self.fretted, self.reference)
+    }
+
+    /// Saddle adjustment for a deviation of more than `tolerance` cents.
+    pub fn saddle(&self, tolerance: f32) -> Saddle {
+        match self.cents() {
+            c if c > tolerance => Saddle::Back,
+            c if c < -tolerance => Saddle::Forward,
+            _ => Saddle::InTune,
+        }
+    }
+
+    /// Distance to move the saddle in mm (positive back) for a scale length
+    /// of `scale` mm.
+    pub fn distance(&self, scale: f32) -> f32 {
+        // solving (L + d) / (L / 2 + d) = 2 r for d, r the correction
+        let r = libm::exp2f(-self.cents() / 1200.0);
+        scale * (1.0 - r) / (2.0 * r - 1.0)
+    }
+}
+
+#[test]
+fn intonation_test() {
+    let sharp = Intonation::new(82.41, 82.41 * 2.0 * libm::exp2f(10.0 / 1200.0));
+    assert!((sharp.cents() - 10.0).abs() < 0.01);
+    assert_eq!(sharp.saddle(2.0), Saddle::Back);
+    // about 0.37 mm per cent
+    let d = sharp.distance(SCALE);
+    assert!(d > 3.6 && d < 3.8, "{}", d);
+
+    let flat = Intonation::harmonic(220.0, 218.0);
+    assert_eq!(flat.saddle(2.0), Saddle::Forward);
+    assert!(flat.distance(SCALE) < 0.0);
+    assert_eq!(Intonation::new(110.0, 220.1).saddle(2.0), Saddle::InTune);
+
+    // moving the saddle by the distance corrects the octave
+    let (open, fretted) = (SCALE + d, SCALE / 2.0 + d);
+    let octave = 1200.0 * libm::log2f(open / fretted) - 1200.0;
+    assert!((octave + sharp.cents()).abs() < 0.01, "{}", octave);
+}
@@ -0,0 +1,154 @@
+// latency, adaptive buffering between an input and an output stream
+//
+// The input and output callbacks of a pass-through come in bursts, with
+// jitter, so the buffer in between is filled up front. The fill level just
+// before each output callback is watched over a `window`: its minimum, less
+// the frames taken by the callback, is the slack left at the worst moment.
+// The output reads slightly faster or slower (at most `max_drift`,
+// resampled) to drive that minimum towards a safety `margin`. The margin is
+// doubled on an underrun and relaxes back to `min_margin` while no underrun
+// occurs, so the latency settles at the lowest level the callbacks allow.
+
+#[derive(Debug, Clone)]
+pub struct Latency {
+    /// Sample rate in Hz.
+    pub fs: f32,
+    /// Observation window in seconds.
+    pub window: f32,
+    /// Smallest safety margin in seconds.
+    pub min_margin: f32,
+    /// Largest deviation of the read rate from 1.0.
+    pub max_drift: f32,
+    margin: f32,
+    ratio: f32,
+    // the current window
+    frames: usize,
+    min_fill: usize,
+    max_fill: usize,
+    sum: f32,
+    callbacks: usize,
+    underrun: bool,
+    // the last complete window
+    latency: f32,
+    jitter: f32,
+}
+
+impl Latency {
+    pub const fn new(fs: f32) -> Self {
+        Latency {
+            fs,
+            window: 1.0,
+            min_margin: 0.002,
+            max_drift: 0.005,
+            margin: 0.002,
+            ratio: 1.0,
+            frames: 0,
+            min_fill: usize::MAX,
+            max_fill: 0,
+            sum: 0.0,
+            callbacks: 0,
+            underrun: false,
+            latency: 0.0,
+            jitter: 0.0,
+        }
+    }
+
+    /// Observes the `fill` level (in frames) before an output callback of
+    /// `frames` frames, returning the rate to read at (input frames per
+    /// output frame).
+    pub fn update(&mut self, fill: usize, frames: usize) -> f32 {
+        // the slack is what is left after the callback
+        let slack = fill.saturating_sub(frames);
+        self.min_fill = self.min_fill.min(slack);
+        self.max_fill = self.max_fill.max(slack);
+        self.sum += fill as f32;
+        self.callbacks += 1;
+        self.frames += frames;
+        if (self.frames as f32) < self.window * self.fs {
+            return self.ratio;
+        }
+
+        self.latency = self.sum / self.callbacks as f32 / self.fs;
+        self.jitter = (self.max_fill - self.min_fill) as f32 / self.fs;
+        self.margin = match self.underrun {
+            true => (2.0 * self.margin).min(self.window / 2.0),
+            false => (0.9 * self.margin).max(self.min_margin),
+        };
+        // remove the excess over the next window
+        let excess = self.min_fill as f32 / self.fs - self.margin;
+        self.ratio = 1.0 + (excess / self.window).clamp(-self.max_drift, self.max_drift);
+
+        self.frames = 0;
+        self.min_fill = usize::MAX;
+        self.max_fill = 0;
+        self.sum = 0.0;
+        self.callbacks = 0;
+        self.underrun = false;
+        self.ratio
+    }
+
+    /// The buffer ran empty during an output callback.
+    pub fn underrun(&mut self) {
+        self.underrun = true;
+    }
+
+    /// Mean delay of the buffer over the last window in seconds.
+    pub fn latency(&self) -> f32 {
+        self.latency
+    }
+
+    /// Variation of the fill level over the last window in seconds.
+    pub fn jitter(&self) -> f32 {
+        self.jitter
+    }
+
+    /// Current safety margin in seconds.
+    pub fn margin(&self) -> f32 {
+        self.margin
+    }
+}
+
+#[cfg(test)]
+use crate::testing::DEVICE_FS as FS;
+
+// Runs `seconds` of callbacks of 256 frames, the input delivering two
+// blocks at once every fourth time, returning the number of underruns.
+#[cfg(test)]
+fn simulate(latency: &mut Latency, fill: &mut f32, seconds: usize) -> usize {
+    let mut underruns = 0;
+    for i in 0..seconds * FS as usize / 256 {
+        match i % 4 {
+            2 => {}
+            3 => *fill += 512.0,
+            _ => *fill += 256.0,
+        }
+        let ratio = latency.update(*fill as usize, 256);
+        *fill -= 256.0 * ratio;
+        if *fill < 0.0 {
+            latency.underrun();
+            underruns += 1;
+            *fill = 0.0;
+        }
+    }
+    underruns
+}
+
+#[test]
+fn latency_test() {
+    // 150 ms up front, settled within a minute
+    let mut latency = Latency::new(FS);
+    let mut fill = 0.15 * FS;
+    simulate(&mut latency, &mut fill, 60);
+    assert_eq!(simulate(&mut latency, &mut fill, 10), 0);
+    // a block of jitter, plus the margin
+    assert!((latency.jitter() - 256.0 / FS).abs() < 0.001);
+    assert!(latency.latency() < 0.012, "{:?}", latency);
+    assert_eq!(latency.margin(), latency.min_margin);
+
+    // no buffer up front, the margin grows until no more underruns
+    let mut latency = Latency::new(FS);
+    let mut fill = 0.0;
+    assert!(simulate(&mut latency, &mut fill, 30) > 0);
+    assert_eq!(simulate(&mut latency, &mut fill, 10), 0);
+    assert!(latency.latency() < 0.012, "{:?}", latency);
+}
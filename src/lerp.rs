@@ -15,3 +15,22 @@ fn lerp_test() {
 
     println!("{}", lerp_zero(2.0, 1.0));
 }
+
+/// Zero crossings of `samples` as (interpolated position in samples, rising).
+pub fn zero_crossings(samples: &[f32]) -> impl Iterator<Item = (f32, bool)> + '_ {
+    samples.windows(2).enumerate().filter_map(|(i, w)| {
+        let (y0, y1) = (w[0], w[1]);
+        let rising = y0 < 0.0 && y1 >= 0.0;
+        (rising || (y0 >= 0.0 && y1 < 0.0)).then(|| (i as f32 + lerp_zero(y0, y1), rising))
+    })
+}
+
+#[test]
+fn zero_crossings_test() {
+    let x = [-1.0, 1.0, 2.0, 0.0, -2.0, -1.0, 3.0];
+    let mut c = zero_crossings(&x);
+    assert_eq!(c.next(), Some((0.5, true)));
+    assert_eq!(c.next(), Some((3.0, false)));
+    assert_eq!(c.next(), Some((5.25, true)));
+    assert_eq!(c.next(), None);
+}
@@ -1 +1,27 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod adc;
+pub mod bend;
+pub mod capture;
+pub mod decimate;
+pub mod display;
+pub mod fft;
+pub mod filter;
+pub mod harmonics;
+pub mod hum;
+pub mod intonation;
+pub mod latency;
 pub mod lerp;
+pub mod midi;
+pub mod mute;
+pub mod note;
+pub mod osc;
+pub mod protocol;
+pub mod resample;
+pub mod strum;
+pub mod temperament;
+#[cfg(test)]
+mod testing;
+pub mod tone;
+pub mod track;
+pub mod tuning;
@@ -1,3 +1,74 @@
-fn main() {
-    println!("Hello, world!");
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+mod cmd;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Guitar tuner", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Analyze the spectrum of a captured note
+    Analyze(cmd::analyze::Opt),
+    /// Analyze bends and vibrato in a captured note
+    Bend(cmd::bend::Opt),
+    /// View and edit the settings, the defaults of the options
+    Config(cmd::config::Opt),
+    /// Decode a tuner telemetry stream
+    Decode(cmd::decode::Opt),
+    /// Run a pitch detector over a capture
+    Detect(cmd::detect::Opt),
+    /// List the audio devices
+    Devices(cmd::devices::Opt),
+    /// Import raw captures into WAV files
+    Import(cmd::import::Opt),
+    /// Check the intonation of the strings at the 12th fret
+    Intonation(cmd::intonation::Opt),
+    /// Tune live from the input device
+    Live(cmd::live::Opt),
+    /// Pass the input through to the output, muting while tuning
+    Pass(cmd::pass::Opt),
+    /// Select the ADC profile of the device
+    Profile(cmd::profile::Opt),
+    /// Estimate the inharmonicity of the strings and stretch the targets
+    Stretch(cmd::stretch::Opt),
+    /// Check the tuning of all strings from a strum
+    Strum(cmd::strum::Opt),
+    /// Play a reference tone for tuning by ear
+    Tone(cmd::tone::Opt),
+}
+
+fn main() -> anyhow::Result<()> {
+    // the settings as defaults, ignored if invalid (to be fixed by `config`)
+    let config = cmd::config::Config::load();
+    let settings = config
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("{:#}", e))
+        .and_then(|c| c.settings())
+        .unwrap_or_else(|e| {
+            eprintln!("warning: settings ignored: {:#}", e);
+            vec![]
+        });
+    let matches = cmd::config::apply(Cli::command(), &settings).get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match cli.command {
+        Command::Analyze(opt) => cmd::analyze::run(opt),
+        Command::Bend(opt) => cmd::bend::run(opt),
+        Command::Config(opt) => cmd::config::run(opt),
+        Command::Decode(opt) => cmd::decode::run(opt),
+        Command::Detect(opt) => cmd::detect::run(opt),
+        Command::Devices(opt) => cmd::devices::run(opt),
+        Command::Import(opt) => cmd::import::run(opt),
+        Command::Intonation(opt) => cmd::intonation::run(opt),
+        Command::Live(opt) => cmd::live::run(opt),
+        Command::Pass(opt) => cmd::pass::run(opt),
+        Command::Profile(opt) => cmd::profile::run(opt),
+        Command::Stretch(opt) => cmd::stretch::run(opt),
+        Command::Strum(opt) => cmd::strum::run(opt),
+        Command::Tone(opt) => cmd::tone::run(opt),
+    }
 }
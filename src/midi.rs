@@ -0,0 +1,409 @@
+// midi, turns detector readings into MIDI note and pitch bend messages
+
+use crate::{
+    note::{cents, Note, A4},
+    protocol::{Reading, State},
+};
+
+/// Default pitch bend range in semitones (the General MIDI default).
+pub const BEND_RANGE: f32 = 2.0;
+
+/// Center (no bend) of the 14 bit pitch bend value.
+pub const BEND_CENTER: u16 = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    /// 14 bit value, `BEND_CENTER` means no bend.
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    ControlChange {
+        channel: u8,
+        control: u8,
+        value: u8,
+    },
+}
+
+impl Message {
+    /// The message as MIDI 1.0 bytes.
+    pub fn to_bytes(&self) -> [u8; 3] {
+        match *self {
+            Message::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => [0x80 | (channel & 0x0f), note & 0x7f, velocity & 0x7f],
+            Message::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => [0x90 | (channel & 0x0f), note & 0x7f, velocity & 0x7f],
+            Message::PitchBend { channel, value } => [
+                0xe0 | (channel & 0x0f),
+                (value & 0x7f) as u8,
+                ((value >> 7) & 0x7f) as u8,
+            ],
+            Message::ControlChange {
+                channel,
+                control,
+                value,
+            } => [0xb0 | (channel & 0x0f), control & 0x7f, value & 0x7f],
+        }
+    }
+
+    /// The message as a USB-MIDI event packet on virtual cable `cable`.
+    pub fn to_usb(&self, cable: u8) -> [u8; 4] {
+        let bytes = self.to_bytes();
+        // the code index number equals the high nibble of the status byte
+        // for channel voice messages
+        [(cable << 4) | (bytes[0] >> 4), bytes[0], bytes[1], bytes[2]]
+    }
+}
+
+/// Parses a MIDI 1.0 byte stream (e.g., from a raw MIDI port) into the
+/// messages above, with running status. Other messages are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    status: u8,
+    data: [u8; 2],
+    len: usize,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            status: 0,
+            data: [0; 2],
+            len: 0,
+        }
+    }
+
+    /// Feeds the next byte, returning a message when complete.
+    pub fn feed(&mut self, byte: u8) -> Option<Message> {
+        match byte {
+            // real time messages may come between any bytes
+            0xf8.. => return None,
+            // system common messages cancel the running status
+            0xf0..=0xf7 => {
+                self.status = 0;
+                return None;
+            }
+            0x80..=0xef => {
+                self.status = byte;
+                self.len = 0;
+                return None;
+            }
+            _ if self.status == 0 => return None,
+            _ => {}
+        }
+        self.data[self.len] = byte;
+        self.len += 1;
+        // program change and channel pressure have a single data byte
+        let needed = match self.status & 0xf0 {
+            0xc0 | 0xd0 => 1,
+            _ => 2,
+        };
+        if self.len < needed {
+            return None;
+        }
+        self.len = 0;
+
+        let channel = self.status & 0x0f;
+        let [a, b] = self.data;
+        match self.status & 0xf0 {
+            0x80 => Some(Message::NoteOff {
+                channel,
+                note: a,
+                velocity: b,
+            }),
+            // note on with velocity 0 is note off
+            0x90 if b == 0 => Some(Message::NoteOff {
+                channel,
+                note: a,
+                velocity: 0,
+            }),
+            0x90 => Some(Message::NoteOn {
+                channel,
+                note: a,
+                velocity: b,
+            }),
+            0xb0 => Some(Message::ControlChange {
+                channel,
+                control: a,
+                value: b,
+            }),
+            0xe0 => Some(Message::PitchBend {
+                channel,
+                value: a as u16 | (b as u16) << 7,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Pitch bend value for a deviation of `cents` given the bend range in
+/// semitones.
+pub fn bend(cents: f32, range: f32) -> u16 {
+    let v = BEND_CENTER as f32 + cents / (range * 100.0) * BEND_CENTER as f32;
+    libm::roundf(v).clamp(0.0, 0x3fff as f32) as u16
+}
+
+/// Note on velocity for a signal `level` in dBFS, -60 dB and below maps to 1.
+pub fn velocity(level: f32) -> u8 {
+    let v = 1.0 + (level + 60.0) / 60.0 * 126.0;
+    libm::roundf(v).clamp(1.0, 127.0) as u8
+}
+
+/// Tracks readings and emits note on/off and pitch bend messages as the
+/// player plucks.
+///
+/// Once a note is sounding, small pitch changes are sent as pitch bend. A
+/// new note is only triggered when the pitch moves more than
+/// `50 + hysteresis` cents from the sounding note, to avoid flapping between
+/// neighbouring notes when playing in between.
+#[derive(Debug, Clone)]
+pub struct Converter {
+    pub channel: u8,
+    pub a4: f32,
+    /// Pitch bend range of the receiver in semitones.
+    pub bend_range: f32,
+    pub hysteresis: f32,
+    current: Option<Note>,
+    last_bend: u16,
+}
+
+impl Converter {
+    pub const fn new(channel: u8) -> Self {
+        Converter {
+            channel,
+            a4: A4,
+            bend_range: BEND_RANGE,
+            hysteresis: 20.0,
+            current: None,
+            last_bend: BEND_CENTER,
+        }
+    }
+
+    /// The currently sounding note, if any.
+    pub fn current(&self) -> Option<Note> {
+        self.current
+    }
+
+    /// Processes a reading, calling `emit` for each message to send.
+    pub fn update(&mut self, reading: &Reading, mut emit: impl FnMut(Message)) {
+        if reading.state != State::Track || reading.pitch <= 0.0 {
+            self.release(&mut emit);
+            return;
+        }
+
+        if let Some(note) = self.current {
+            let offset = cents(reading.pitch, note.freq(self.a4));
+            if offset.abs() <= 50.0 + self.hysteresis {
+                let value = bend(offset, self.bend_range);
+                if value != self.last_bend {
+                    self.last_bend = value;
+                    emit(Message::PitchBend {
+                        channel: self.channel,
+                        value,
+                    });
+                }
+                return;
+            }
+            self.release(&mut emit);
+        }
+
+        let (note, offset) = Note::nearest(reading.pitch, self.a4);
+        // bend first so the note starts at the right pitch
+        self.last_bend = bend(offset, self.bend_range);
+        emit(Message::PitchBend {
+            channel: self.channel,
+            value: self.last_bend,
+        });
+        emit(Message::NoteOn {
+            channel: self.channel,
+            note: note.0,
+            velocity: velocity(reading.level),
+        });
+        self.current = Some(note);
+    }
+
+    fn release(&mut self, emit: &mut impl FnMut(Message)) {
+        if let Some(note) = self.current.take() {
+            emit(Message::NoteOff {
+                channel: self.channel,
+                note: note.0,
+                velocity: 0,
+            });
+        }
+    }
+}
+
+#[test]
+fn message_test() {
+    let on = Message::NoteOn {
+        channel: 1,
+        note: 40,
+        velocity: 100,
+    };
+    assert_eq!(on.to_bytes(), [0x91, 40, 100]);
+    assert_eq!(on.to_usb(0), [0x09, 0x91, 40, 100]);
+
+    let bend = Message::PitchBend {
+        channel: 0,
+        value: 0x3fff,
+    };
+    assert_eq!(bend.to_bytes(), [0xe0, 0x7f, 0x7f]);
+    assert_eq!(bend.to_usb(1), [0x1e, 0xe0, 0x7f, 0x7f]);
+}
+
+#[test]
+fn parser_test() {
+    let mut parser = Parser::new();
+    let mut messages = [None; 4];
+    let mut n = 0;
+    // control change, running status with a clock in between, sysex, a
+    // program change and note on with velocity 0
+    for byte in [
+        0xb0, 64, 127, 64, 0xf8, 0, 0xf0, 1, 0xf7, 2, 0xc1, 5, 0x92, 40, 0,
+    ] {
+        if let Some(m) = parser.feed(byte) {
+            messages[n] = Some(m);
+            n += 1;
+        }
+    }
+    let cc = |value| {
+        Some(Message::ControlChange {
+            channel: 0,
+            control: 64,
+            value,
+        })
+    };
+    assert_eq!(
+        messages[..3],
+        [
+            cc(127),
+            cc(0),
+            Some(Message::NoteOff {
+                channel: 2,
+                note: 40,
+                velocity: 0
+            })
+        ]
+    );
+    assert_eq!(n, 3);
+
+    let bend = Message::PitchBend {
+        channel: 3,
+        value: 0x1234,
+    };
+    let mut parser = Parser::new();
+    let parsed: [_; 3] = core::array::from_fn(|i| parser.feed(bend.to_bytes()[i]));
+    assert_eq!(parsed, [None, None, Some(bend)]);
+}
+
+#[test]
+fn bend_test() {
+    assert_eq!(bend(0.0, BEND_RANGE), BEND_CENTER);
+    assert_eq!(bend(200.0, BEND_RANGE), 0x3fff);
+    assert_eq!(bend(-200.0, BEND_RANGE), 0);
+    assert_eq!(bend(-1000.0, BEND_RANGE), 0);
+    assert_eq!(bend(100.0, BEND_RANGE), 0x3000);
+    assert_eq!(velocity(0.0), 127);
+    assert_eq!(velocity(-90.0), 1);
+}
+
+#[cfg(test)]
+fn track(pitch: f32) -> Reading {
+    Reading {
+        pitch,
+        cents: 0.0,
+        string: None,
+        confidence: 1.0,
+        level: 0.0,
+        state: State::Track,
+    }
+}
+
+#[test]
+fn converter_test() {
+    let mut c = Converter::new(0);
+    let mut out = [None; 4];
+    let mut n = 0;
+    let mut run = |c: &mut Converter, r: &Reading| {
+        out = [None; 4];
+        n = 0;
+        c.update(r, |m| {
+            out[n] = Some(m);
+            n += 1;
+        });
+        out
+    };
+
+    // pluck E2
+    let m = run(&mut c, &track(Note(40).freq(A4)));
+    assert_eq!(
+        m[..2],
+        [
+            Some(Message::PitchBend {
+                channel: 0,
+                value: BEND_CENTER
+            }),
+            Some(Message::NoteOn {
+                channel: 0,
+                note: 40,
+                velocity: 127
+            })
+        ]
+    );
+
+    // same pitch, nothing to send
+    assert_eq!(run(&mut c, &track(Note(40).freq(A4))), [None; 4]);
+
+    // bend up 60 cents, within hysteresis, keeps the note
+    let m = run(&mut c, &track(Note(40).freq(A4) * libm::exp2f(0.6 / 12.0)));
+    assert_eq!(
+        m[0],
+        Some(Message::PitchBend {
+            channel: 0,
+            value: bend(60.0, BEND_RANGE)
+        })
+    );
+    assert_eq!(c.current(), Some(Note(40)));
+
+    // a full semitone triggers a new note
+    let m = run(&mut c, &track(Note(41).freq(A4)));
+    assert_eq!(
+        m[0],
+        Some(Message::NoteOff {
+            channel: 0,
+            note: 40,
+            velocity: 0
+        })
+    );
+    assert_eq!(c.current(), Some(Note(41)));
+
+    // silence releases the note
+    let mut idle = track(0.0);
+    idle.state = State::Idle;
+    let m = run(&mut c, &idle);
+    assert_eq!(
+        m[0],
+        Some(Message::NoteOff {
+            channel: 0,
+            note: 41,
+            velocity: 0
+        })
+    );
+    assert_eq!(c.current(), None);
+}
@@ -0,0 +1,149 @@
+// mute, stomp box style muting of a pass-through signal
+//
+// The output is faded in and out over `fade` seconds to avoid clicks. Muted
+// by hand, or automatically once a pluck is detected: the level (a fast
+// envelope) rising `onset` dB above the background (a slow envelope) and
+// above the gate. It stays muted until the level is below the gate for
+// `hold` seconds.
+
+/// Time constants of the envelopes in seconds.
+const FAST: f32 = 0.002;
+const SLOW: f32 = 0.2;
+
+pub struct Mute {
+    /// Sample rate in Hz.
+    pub fs: f32,
+    /// Fade time in seconds.
+    pub fade: f32,
+    /// Mute automatically when plucked.
+    pub auto: bool,
+    /// Noise gate in dBFS.
+    pub gate: f32,
+    /// Rise of the level over the background detected as a pluck in dB.
+    pub onset: f32,
+    /// Time below the gate until unmuted in seconds.
+    pub hold: f32,
+    muted: bool,
+    plucked: bool,
+    gain: f32,
+    fast: f32,
+    slow: f32,
+    quiet: usize,
+}
+
+impl Mute {
+    /// Unmuted (bypassed), without auto mute.
+    pub const fn new(fs: f32) -> Self {
+        Mute {
+            fs,
+            fade: 0.02,
+            auto: false,
+            gate: -50.0,
+            onset: 12.0,
+            hold: 1.0,
+            muted: false,
+            plucked: false,
+            gain: 1.0,
+            fast: 0.0,
+            slow: 0.0,
+            quiet: 0,
+        }
+    }
+
+    /// Muted by hand or automatically.
+    pub fn is_muted(&self) -> bool {
+        self.muted || self.plucked
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        // unmuting by hand also ends an auto mute
+        if !muted {
+            self.plucked = false;
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.set_muted(!self.is_muted());
+    }
+
+    /// Applies the gain to `samples` in place, detecting plucks first.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let fast = 1.0 - libm::expf(-1.0 / (FAST * self.fs));
+        let slow = 1.0 - libm::expf(-1.0 / (SLOW * self.fs));
+        let step = 1.0 / (self.fade * self.fs).max(1.0);
+        let onset = libm::powf(10.0, self.onset / 20.0);
+        let gate = libm::powf(10.0, self.gate / 20.0);
+        let hold = (self.hold * self.fs) as usize;
+        for x in samples {
+            // envelopes of the magnitude
+            let m = x.abs();
+            self.fast += fast * (m - self.fast);
+            self.slow += slow * (m - self.slow);
+            if self.fast > gate {
+                self.quiet = 0;
+                if self.auto && self.fast > onset * self.slow {
+                    self.plucked = true;
+                }
+            } else if self.plucked {
+                self.quiet += 1;
+                if self.quiet >= hold {
+                    self.plucked = false;
+                }
+            }
+
+            let target = if self.is_muted() { 0.0 } else { 1.0 };
+            self.gain = match self.gain < target {
+                true => (self.gain + step).min(target),
+                false => (self.gain - step).max(target),
+            };
+            *x *= self.gain;
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::testing::{sine, FS};
+
+#[test]
+fn fade_test() {
+    let mut mute = Mute::new(FS);
+    let mut x = [0.5; 400];
+    mute.process(&mut x);
+    assert!(x.iter().all(|x| *x == 0.5));
+
+    // faded out over 20 ms, no steps
+    mute.toggle();
+    assert!(mute.is_muted());
+    let mut x = [0.5; 400];
+    mute.process(&mut x);
+    assert!(x.windows(2).all(|w| w[1] <= w[0] && w[0] - w[1] < 0.01));
+    assert!(x[160..].iter().all(|x| *x == 0.0));
+
+    mute.toggle();
+    let mut x = [0.5; 400];
+    mute.process(&mut x);
+    assert!(x[0] < 0.01 && x[399] == 0.5);
+}
+
+#[test]
+fn auto_test() {
+    let mut mute = Mute::new(FS);
+    mute.auto = true;
+    mute.hold = 0.1;
+    // noise below the gate
+    let mut x = [0.001; 800];
+    mute.process(&mut x);
+    assert!(!mute.is_muted());
+
+    // a pluck
+    let mut x: [f32; 800] = core::array::from_fn(|i| 0.5 * sine(110.0, FS, i));
+    mute.process(&mut x);
+    assert!(mute.is_muted());
+    assert!(x[400..].iter().all(|x| *x == 0.0));
+
+    // unmuted once quiet for `hold`
+    let mut x = [0.0; 1600];
+    mute.process(&mut x);
+    assert!(!mute.is_muted());
+}
@@ -0,0 +1,120 @@
+// note, mapping between notes, frequencies and cents
+
+use core::{fmt, str::FromStr};
+
+/// Default reference pitch (A4) in Hz.
+pub const A4: f32 = 440.0;
+
+const NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Deviation of `f` from `reference` in cents.
+pub fn cents(f: f32, reference: f32) -> f32 {
+    1200.0 * libm::log2f(f / reference)
+}
+
+/// A note in 12-TET, represented by its MIDI note number (A4 = 69).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Note(pub u8);
+
+impl Note {
+    pub const A4: Note = Note(69);
+
+    /// Fundamental frequency given the reference pitch `a4`.
+    pub fn freq(self, a4: f32) -> f32 {
+        a4 * libm::exp2f((self.0 as f32 - Self::A4.0 as f32) / 12.0)
+    }
+
+    /// The note closest to `f` and the deviation from it in cents.
+    pub fn nearest(f: f32, a4: f32) -> (Note, f32) {
+        let semitones = libm::roundf(12.0 * libm::log2f(f / a4));
+        let note = Note((semitones + Self::A4.0 as f32).clamp(0.0, 127.0) as u8);
+        (note, cents(f, note.freq(a4)))
+    }
+
+    /// Note name without octave, e.g., "C#".
+    pub fn name(self) -> &'static str {
+        NAMES[self.0 as usize % 12]
+    }
+
+    /// Octave in scientific pitch notation, MIDI note 0 is C-1.
+    pub fn octave(self) -> i8 {
+        (self.0 / 12) as i8 - 1
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.name(), self.octave())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseNoteError;
+
+impl fmt::Display for ParseNoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid note, expected e.g. \"E2\", \"C#3\" or \"Bb3\"")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseNoteError {}
+
+/// Splits a note name, e.g., "C#" of "C#3", into its semitone above C
+/// (-1..=12) and the rest.
+pub(crate) fn pitch_class(s: &str) -> Result<(i16, &str), ParseNoteError> {
+    let mut chars = s.chars();
+    let letter = chars.next().ok_or(ParseNoteError)?;
+    let semitone: i16 = match letter.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return Err(ParseNoteError),
+    };
+    let rest = chars.as_str();
+    Ok(if let Some(rest) = rest.strip_prefix('#') {
+        (semitone + 1, rest)
+    } else if let Some(rest) = rest.strip_prefix('b') {
+        (semitone - 1, rest)
+    } else {
+        (semitone, rest)
+    })
+}
+
+impl FromStr for Note {
+    type Err = ParseNoteError;
+
+    /// Parses scientific pitch notation, e.g., "E2", "C#3" or "Bb3".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (semitone, octave) = pitch_class(s)?;
+        let octave: i16 = octave.parse().map_err(|_| ParseNoteError)?;
+        let midi = (octave + 1) * 12 + semitone;
+        u8::try_from(midi)
+            .ok()
+            .filter(|m| *m < 128)
+            .map(Note)
+            .ok_or(ParseNoteError)
+    }
+}
+
+#[test]
+fn note_test() {
+    assert_eq!("A4".parse(), Ok(Note::A4));
+    assert_eq!("E2".parse(), Ok(Note(40)));
+    assert_eq!("C#3".parse(), Ok(Note(49)));
+    assert_eq!("Db3".parse(), Ok(Note(49)));
+    assert_eq!("H2".parse::<Note>(), Err(ParseNoteError));
+
+    assert!((Note(40).freq(A4) - 82.41).abs() < 0.01);
+    assert_eq!(Note(64).to_string(), "E4");
+
+    let (note, c) = Note::nearest(83.0, A4);
+    assert_eq!(note, Note(40));
+    assert!((c - 12.35).abs() < 0.1);
+}
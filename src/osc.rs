@@ -0,0 +1,86 @@
+// osc, Open Sound Control messages
+//
+// Just enough of OSC 1.0 for remote control: a message is the address and
+// the type tag string (both null terminated and padded to 4 bytes),
+// followed by the big endian arguments. Only the first argument is kept,
+// bundles are not supported.
+
+use core::str;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Float(f32),
+    True,
+    False,
+}
+
+impl Arg {
+    /// The argument as a switch, numbers are on unless zero.
+    pub fn on(self) -> bool {
+        match self {
+            Arg::Int(i) => i != 0,
+            Arg::Float(f) => f != 0.0,
+            Arg::True => true,
+            Arg::False => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Message<'a> {
+    pub address: &'a str,
+    /// The first argument, if any (and supported).
+    pub arg: Option<Arg>,
+}
+
+// A padded string at the start of `buf`, and the rest.
+fn string(buf: &[u8]) -> Option<(&str, &[u8])> {
+    let len = buf.iter().position(|b| *b == 0)?;
+    let padded = (len + 4) & !3;
+    let s = str::from_utf8(&buf[..len]).ok()?;
+    Some((s, buf.get(padded..)?))
+}
+
+/// Parses a message from a packet.
+pub fn parse(packet: &[u8]) -> Option<Message<'_>> {
+    let (address, rest) = string(packet)?;
+    if !address.starts_with('/') {
+        return None;
+    }
+    // the type tags may be missing in old implementations
+    let Some((tags, rest)) = string(rest).filter(|(t, _)| t.starts_with(',')) else {
+        return Some(Message { address, arg: None });
+    };
+    let word = |rest: &[u8]| -> Option<[u8; 4]> { rest.get(..4)?.try_into().ok() };
+    let arg = match tags.as_bytes().get(1) {
+        Some(b'i') => Some(Arg::Int(i32::from_be_bytes(word(rest)?))),
+        Some(b'f') => Some(Arg::Float(f32::from_be_bytes(word(rest)?))),
+        Some(b'T') => Some(Arg::True),
+        Some(b'F') => Some(Arg::False),
+        _ => None,
+    };
+    Some(Message { address, arg })
+}
+
+#[test]
+fn parse_test() {
+    assert_eq!(
+        parse(b"/mute\0\0\0,i\0\0\0\0\0\x01"),
+        Some(Message {
+            address: "/mute",
+            arg: Some(Arg::Int(1))
+        })
+    );
+    let m = parse(b"/tuner/bypass\0\0\0,f\0\0\x3f\x80\0\0").unwrap();
+    assert_eq!(m.address, "/tuner/bypass");
+    assert_eq!(m.arg, Some(Arg::Float(1.0)));
+    assert!(m.arg.unwrap().on());
+    assert_eq!(parse(b"/mute\0\0\0,F\0\0").unwrap().arg, Some(Arg::False));
+    // no arguments, no type tags
+    assert_eq!(parse(b"/mute\0\0\0,\0\0\0").unwrap().arg, None);
+    assert_eq!(parse(b"/mute\0\0\0").unwrap().arg, None);
+    // truncated, bundle
+    assert_eq!(parse(b"/mute\0\0\0,i\0\0\0\0"), None);
+    assert_eq!(parse(b"#bundle\0"), None);
+}
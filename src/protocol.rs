@@ -0,0 +1,603 @@
+// protocol, framing of tuner telemetry
+//
+// Every frame is laid out as:
+//
+// | byte      | content                                  |
+// |-----------|------------------------------------------|
+// | 0         | `SYNC` (0xA5)                            |
+// | 1         | protocol version                         |
+// | 2         | frame kind                               |
+// | 3         | payload length `n` (0..=255)             |
+// | 4..4+n    | payload                                  |
+// | 4+n..6+n  | CRC-16/CCITT-FALSE of bytes 1..4+n, LE   |
+//
+// All multi-byte values are little endian. Version 2 added the `Capture`
+// frame and version 3 the `Profile` frame, older frames are still accepted.
+// The module only depends on `core` so the same code runs on the device and
+// on the host.
+
+use core::fmt;
+
+use crate::adc::{AdcProfile, Input};
+
+pub const SYNC: u8 = 0xA5;
+pub const PROTOCOL_VERSION: u8 = 3;
+
+pub const HEADER_LEN: usize = 4;
+pub const CRC_LEN: usize = 2;
+pub const MAX_PAYLOAD: usize = 255;
+pub const MAX_FRAME: usize = HEADER_LEN + MAX_PAYLOAD + CRC_LEN;
+
+/// Maximum number of samples carried by a single `Samples` frame.
+pub const MAX_SAMPLES: usize = (MAX_PAYLOAD - 2) / 2;
+
+const KIND_READING: u8 = 0x01;
+const KIND_SAMPLES: u8 = 0x02;
+const KIND_CAPTURE: u8 = 0x03;
+const KIND_PROFILE: u8 = 0x04;
+
+const READING_LEN: usize = 11;
+const CAPTURE_LEN: usize = 19;
+const PROFILE_LEN: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The output buffer cannot hold the encoded frame.
+    BufferTooSmall,
+    /// The frame was produced by a newer (or invalid) protocol version.
+    UnsupportedVersion(u8),
+    /// The frame kind is not known to this version.
+    UnknownKind(u8),
+    /// The payload length does not match the frame kind.
+    BadLength(u8),
+    /// Invalid field value, e.g., an unknown state.
+    BadValue,
+    Crc {
+        expected: u16,
+        found: u16,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BufferTooSmall => write!(f, "buffer too small"),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported protocol version {}", v),
+            Error::UnknownKind(k) => write!(f, "unknown frame kind 0x{:02x}", k),
+            Error::BadLength(l) => write!(f, "bad payload length {}", l),
+            Error::BadValue => write!(f, "bad field value"),
+            Error::Crc { expected, found } => write!(
+                f,
+                "crc mismatch, expected 0x{:04x} found 0x{:04x}",
+                expected, found
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// State of the detector producing the reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum State {
+    /// No signal (below noise gate).
+    Idle = 0,
+    /// Searching for the fundamental.
+    Search = 1,
+    /// Locked on and tracking the fundamental.
+    Track = 2,
+}
+
+impl TryFrom<u8> for State {
+    type Error = Error;
+
+    fn try_from(v: u8) -> Result<Self, Error> {
+        match v {
+            0 => Ok(State::Idle),
+            1 => Ok(State::Search),
+            2 => Ok(State::Track),
+            _ => Err(Error::BadValue),
+        }
+    }
+}
+
+/// A single detector result.
+///
+/// On the wire `cents` is stored in 1/100 cents (±327.67), `confidence`
+/// in 1/255 steps and `level` in 1/100 dB (dBFS), so decoded values are
+/// quantized accordingly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reading {
+    /// Detected pitch in Hz.
+    pub pitch: f32,
+    /// Deviation from the target note in cents.
+    pub cents: f32,
+    /// Index of the detected string, if any.
+    pub string: Option<u8>,
+    /// Confidence of the estimate, 0.0..=1.0.
+    pub confidence: f32,
+    /// Signal level in dBFS.
+    pub level: f32,
+    pub state: State,
+}
+
+/// A chunk of raw samples, `seq` wraps around and allows the receiver to
+/// detect dropped chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Samples {
+    pub seq: u16,
+    len: u8,
+    data: [i16; MAX_SAMPLES],
+}
+
+impl Samples {
+    /// Creates a chunk from at most `MAX_SAMPLES` samples.
+    pub fn new(seq: u16, samples: &[i16]) -> Result<Self, Error> {
+        if samples.len() > MAX_SAMPLES {
+            return Err(Error::BufferTooSmall);
+        }
+        let mut data = [0; MAX_SAMPLES];
+        data[..samples.len()].copy_from_slice(samples);
+        Ok(Samples {
+            seq,
+            len: samples.len() as u8,
+            data,
+        })
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.data[..self.len as usize]
+    }
+}
+
+/// Header of a raw sample capture, followed by `length` samples in
+/// `Samples` frames. The chunk with `seq` n carries the samples from
+/// `n * MAX_SAMPLES`, so only the last chunk may be shorter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureHeader {
+    /// Capture number, wraps around.
+    pub id: u16,
+    /// Sample rate in Hz.
+    pub sample_rate: f32,
+    /// Gain of the analog front-end.
+    pub gain: f32,
+    /// ADC resolution in bits.
+    pub resolution: u8,
+    /// Start of the capture in ms since boot.
+    pub timestamp: u32,
+    /// Total number of samples.
+    pub length: u32,
+}
+
+// Frames are kept by value, there is no allocator on the device.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frame {
+    Reading(Reading),
+    Samples(Samples),
+    Capture(CaptureHeader),
+    /// The active ADC profile when sent by the device, a request to switch
+    /// profile when sent by the host.
+    Profile(AdcProfile),
+}
+
+impl Frame {
+    fn kind(&self) -> u8 {
+        match self {
+            Frame::Reading(_) => KIND_READING,
+            Frame::Samples(_) => KIND_SAMPLES,
+            Frame::Capture(_) => KIND_CAPTURE,
+            Frame::Profile(_) => KIND_PROFILE,
+        }
+    }
+
+    /// Encodes the frame into `buf`, returning the number of bytes written.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let payload_len = match self {
+            Frame::Reading(_) => READING_LEN,
+            Frame::Samples(s) => 2 + 2 * s.len as usize,
+            Frame::Capture(_) => CAPTURE_LEN,
+            Frame::Profile(_) => PROFILE_LEN,
+        };
+        let total = HEADER_LEN + payload_len + CRC_LEN;
+        if buf.len() < total {
+            return Err(Error::BufferTooSmall);
+        }
+
+        buf[0] = SYNC;
+        buf[1] = PROTOCOL_VERSION;
+        buf[2] = self.kind();
+        buf[3] = payload_len as u8;
+
+        let payload = &mut buf[HEADER_LEN..HEADER_LEN + payload_len];
+        match self {
+            Frame::Reading(r) => {
+                payload[0..4].copy_from_slice(&r.pitch.to_le_bytes());
+                payload[4..6].copy_from_slice(&quantize_i16(r.cents, 100.0).to_le_bytes());
+                payload[6] = r.string.unwrap_or(u8::MAX);
+                payload[7] = libm::roundf(r.confidence.clamp(0.0, 1.0) * 255.0) as u8;
+                payload[8..10].copy_from_slice(&quantize_i16(r.level, 100.0).to_le_bytes());
+                payload[10] = r.state as u8;
+            }
+            Frame::Samples(s) => {
+                payload[0..2].copy_from_slice(&s.seq.to_le_bytes());
+                for (chunk, v) in payload[2..].chunks_exact_mut(2).zip(s.samples()) {
+                    chunk.copy_from_slice(&v.to_le_bytes());
+                }
+            }
+            Frame::Capture(c) => {
+                payload[0..2].copy_from_slice(&c.id.to_le_bytes());
+                payload[2..6].copy_from_slice(&c.sample_rate.to_le_bytes());
+                payload[6..10].copy_from_slice(&c.gain.to_le_bytes());
+                payload[10] = c.resolution;
+                payload[11..15].copy_from_slice(&c.timestamp.to_le_bytes());
+                payload[15..19].copy_from_slice(&c.length.to_le_bytes());
+            }
+            Frame::Profile(p) => {
+                payload[0] = p.gain as u8;
+                payload[1] = p.resolution as u8;
+                payload[2] = p.oversample as u8;
+                payload[3] = p.reference as u8;
+                payload[4] = p.acquisition as u8;
+                // the negative input is 0xFF for single ended
+                let (pos, neg) = match p.input {
+                    Input::Single(pos) => (pos as u8, u8::MAX),
+                    Input::Differential(pos, neg) => (pos as u8, neg as u8),
+                };
+                payload[5] = pos;
+                payload[6] = neg;
+            }
+        }
+
+        let crc = crc16(&buf[1..HEADER_LEN + payload_len]);
+        buf[HEADER_LEN + payload_len..total].copy_from_slice(&crc.to_le_bytes());
+        Ok(total)
+    }
+
+    /// Decodes a complete frame (starting with `SYNC`) from `buf`.
+    pub fn decode(buf: &[u8]) -> Result<Frame, Error> {
+        if buf.len() < HEADER_LEN + CRC_LEN {
+            return Err(Error::BufferTooSmall);
+        }
+        if buf[0] != SYNC {
+            return Err(Error::BadValue);
+        }
+        let payload_len = buf[3] as usize;
+        let total = HEADER_LEN + payload_len + CRC_LEN;
+        if buf.len() < total {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let expected = crc16(&buf[1..HEADER_LEN + payload_len]);
+        let found = u16::from_le_bytes([buf[total - 2], buf[total - 1]]);
+        if expected != found {
+            return Err(Error::Crc { expected, found });
+        }
+
+        let version = buf[1];
+        if version == 0 || version > PROTOCOL_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+
+        let payload = &buf[HEADER_LEN..HEADER_LEN + payload_len];
+        match buf[2] {
+            KIND_READING => {
+                if payload_len != READING_LEN {
+                    return Err(Error::BadLength(buf[3]));
+                }
+                let string = match payload[6] {
+                    u8::MAX => None,
+                    s => Some(s),
+                };
+                Ok(Frame::Reading(Reading {
+                    pitch: f32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]),
+                    cents: i16::from_le_bytes([payload[4], payload[5]]) as f32 / 100.0,
+                    string,
+                    confidence: payload[7] as f32 / 255.0,
+                    level: i16::from_le_bytes([payload[8], payload[9]]) as f32 / 100.0,
+                    state: State::try_from(payload[10])?,
+                }))
+            }
+            KIND_SAMPLES => {
+                if payload_len < 2 || !payload_len.is_multiple_of(2) {
+                    return Err(Error::BadLength(buf[3]));
+                }
+                let mut data = [0; MAX_SAMPLES];
+                for (v, chunk) in data.iter_mut().zip(payload[2..].chunks_exact(2)) {
+                    *v = i16::from_le_bytes([chunk[0], chunk[1]]);
+                }
+                Ok(Frame::Samples(Samples {
+                    seq: u16::from_le_bytes([payload[0], payload[1]]),
+                    len: ((payload_len - 2) / 2) as u8,
+                    data,
+                }))
+            }
+            KIND_CAPTURE if version >= 2 => {
+                if payload_len != CAPTURE_LEN {
+                    return Err(Error::BadLength(buf[3]));
+                }
+                let u32_at = |i: usize| {
+                    u32::from_le_bytes([payload[i], payload[i + 1], payload[i + 2], payload[i + 3]])
+                };
+                Ok(Frame::Capture(CaptureHeader {
+                    id: u16::from_le_bytes([payload[0], payload[1]]),
+                    sample_rate: f32::from_bits(u32_at(2)),
+                    gain: f32::from_bits(u32_at(6)),
+                    resolution: payload[10],
+                    timestamp: u32_at(11),
+                    length: u32_at(15),
+                }))
+            }
+            KIND_PROFILE if version >= 3 => {
+                if payload_len != PROFILE_LEN {
+                    return Err(Error::BadLength(buf[3]));
+                }
+                let pos = payload[5].try_into()?;
+                let input = match payload[6] {
+                    u8::MAX => Input::Single(pos),
+                    neg => Input::Differential(pos, neg.try_into()?),
+                };
+                Ok(Frame::Profile(AdcProfile {
+                    gain: payload[0].try_into()?,
+                    resolution: payload[1].try_into()?,
+                    oversample: payload[2].try_into()?,
+                    reference: payload[3].try_into()?,
+                    acquisition: payload[4].try_into()?,
+                    input,
+                }))
+            }
+            kind => Err(Error::UnknownKind(kind)),
+        }
+    }
+}
+
+fn quantize_i16(v: f32, scale: f32) -> i16 {
+    libm::roundf(v * scale).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF).
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Streaming decoder, recovering frame boundaries from an arbitrary byte
+/// stream. On a corrupt frame the decoder re-synchronizes on the next
+/// `SYNC` byte following the start of the bad frame.
+pub struct Decoder {
+    buf: [u8; MAX_FRAME],
+    len: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Decoder {
+            buf: [0; MAX_FRAME],
+            len: 0,
+        }
+    }
+
+    /// Feeds `bytes` to the decoder, calling `f` for every decoded frame or
+    /// error. Partial frames are kept until the next call.
+    pub fn feed(&mut self, bytes: &[u8], mut f: impl FnMut(Result<Frame, Error>)) {
+        for &b in bytes {
+            if self.len == 0 && b != SYNC {
+                continue;
+            }
+            self.buf[self.len] = b;
+            self.len += 1;
+            while let Some(r) = self.poll() {
+                f(r);
+            }
+        }
+    }
+
+    fn poll(&mut self) -> Option<Result<Frame, Error>> {
+        if self.len < HEADER_LEN {
+            return None;
+        }
+        let total = HEADER_LEN + self.buf[3] as usize + CRC_LEN;
+        if self.len < total {
+            return None;
+        }
+        let r = Frame::decode(&self.buf[..total]);
+        match r {
+            // A valid frame, remaining bytes belong to the next frame.
+            Ok(_) => self.discard(total),
+            // The start might be a false sync, rescan from the next byte.
+            Err(Error::Crc { .. }) => self.discard(1),
+            Err(_) => self.discard(total),
+        }
+        Some(r)
+    }
+
+    fn discard(&mut self, n: usize) {
+        let skip = self.buf[n..self.len]
+            .iter()
+            .position(|&b| b == SYNC)
+            .map_or(self.len, |p| n + p);
+        self.buf.copy_within(skip..self.len, 0);
+        self.len -= skip;
+    }
+}
+
+#[test]
+fn crc16_test() {
+    assert_eq!(crc16(b"123456789"), 0x29B1);
+}
+
+#[test]
+fn reading_test() {
+    let r = Reading {
+        pitch: 82.41,
+        cents: -12.34,
+        string: Some(0),
+        confidence: 1.0,
+        level: -18.5,
+        state: State::Track,
+    };
+    let mut buf = [0; MAX_FRAME];
+    let n = Frame::Reading(r).encode(&mut buf).unwrap();
+    assert_eq!(n, HEADER_LEN + READING_LEN + CRC_LEN);
+    assert_eq!(Frame::decode(&buf[..n]), Ok(Frame::Reading(r)));
+}
+
+#[test]
+fn bad_version_test() {
+    let mut buf = [0; MAX_FRAME];
+    let n = Frame::Samples(Samples::new(0, &[1, 2, 3]).unwrap())
+        .encode(&mut buf)
+        .unwrap();
+    buf[1] = PROTOCOL_VERSION + 1;
+    let crc = crc16(&buf[1..n - CRC_LEN]);
+    buf[n - CRC_LEN..n].copy_from_slice(&crc.to_le_bytes());
+    assert_eq!(
+        Frame::decode(&buf[..n]),
+        Err(Error::UnsupportedVersion(PROTOCOL_VERSION + 1))
+    );
+}
+
+// xorshift32, deterministic pseudo random numbers for the fuzz tests
+#[cfg(test)]
+struct Rng(u32);
+
+#[cfg(test)]
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn frame(&mut self) -> Frame {
+        let kind = self.next() % 4;
+        if kind == 3 {
+            let (_, mut profile) =
+                AdcProfile::NAMED[self.next() as usize % AdcProfile::NAMED.len()];
+            profile.input = match self.next() % 3 {
+                0 => Input::Single(((self.next() % 9) as u8).try_into().unwrap()),
+                _ => Input::Differential(
+                    ((self.next() % 9) as u8).try_into().unwrap(),
+                    ((self.next() % 9) as u8).try_into().unwrap(),
+                ),
+            };
+            Frame::Profile(profile)
+        } else if kind == 2 {
+            Frame::Capture(CaptureHeader {
+                id: self.next() as u16,
+                sample_rate: (self.next() % 100_000) as f32,
+                gain: (self.next() % 8) as f32 / 2.0,
+                resolution: (self.next() % 15) as u8,
+                timestamp: self.next(),
+                length: self.next() % 100_000,
+            })
+        } else if kind == 0 {
+            Frame::Reading(Reading {
+                pitch: (self.next() % 100_000) as f32 / 100.0,
+                cents: (self.next() % 10_001) as f32 / 100.0 - 50.0,
+                string: match self.next() % 7 {
+                    6 => None,
+                    s => Some(s as u8),
+                },
+                confidence: (self.next() % 256) as f32 / 255.0,
+                level: -((self.next() % 9_000) as f32) / 100.0,
+                state: State::try_from((self.next() % 3) as u8).unwrap(),
+            })
+        } else {
+            let mut data = [0; MAX_SAMPLES];
+            let len = self.next() as usize % (MAX_SAMPLES + 1);
+            for v in data[..len].iter_mut() {
+                *v = self.next() as i16;
+            }
+            Frame::Samples(Samples::new(self.next() as u16, &data[..len]).unwrap())
+        }
+    }
+}
+
+#[test]
+fn fuzz_round_trip_test() {
+    const FRAMES: usize = 32;
+    const GARBAGE: usize = 16;
+    let mut rng = Rng(0x1234_5678);
+
+    for _ in 0..200 {
+        let mut sent = [None; FRAMES];
+        let mut stream = [0u8; FRAMES * (MAX_FRAME + GARBAGE) + MAX_FRAME];
+        let mut len = 0;
+
+        for s in sent.iter_mut() {
+            // random garbage (possibly containing SYNC) in front of each frame
+            for _ in 0..rng.next() as usize % GARBAGE {
+                stream[len] = rng.next() as u8;
+                len += 1;
+            }
+            let frame = rng.frame();
+            len += frame.encode(&mut stream[len..]).unwrap();
+
+            // re-encoding the decoded frame gives identical bytes
+            let decoded = Frame::decode(&stream[len - frame_len(&frame)..len]).unwrap();
+            let mut buf = [0; MAX_FRAME];
+            let n = decoded.encode(&mut buf).unwrap();
+            assert_eq!(stream[len - n..len], buf[..n]);
+            *s = Some(decoded);
+        }
+        // trailing zeros flush a pending false sync
+        len += MAX_FRAME;
+
+        // feed the stream in randomly sized chunks
+        let mut decoder = Decoder::new();
+        let mut received = [None; FRAMES];
+        let mut nr_received = 0;
+        let mut pos = 0;
+        while pos < len {
+            let end = (pos + 1 + rng.next() as usize % 64).min(len);
+            decoder.feed(&stream[pos..end], |r| {
+                if let Ok(f) = r {
+                    received[nr_received] = Some(f);
+                    nr_received += 1;
+                }
+            });
+            pos = end;
+        }
+        assert_eq!(nr_received, FRAMES);
+        assert_eq!(received, sent);
+    }
+}
+
+#[cfg(test)]
+fn frame_len(frame: &Frame) -> usize {
+    frame.encode(&mut [0; MAX_FRAME]).unwrap()
+}
+
+#[test]
+fn fuzz_corrupt_test() {
+    let mut rng = Rng(0xdead_beef);
+    let mut buf = [0; MAX_FRAME];
+
+    for _ in 0..10_000 {
+        let n = rng.frame().encode(&mut buf).unwrap();
+        // flipping a single bit is always detected by the crc
+        let bit = rng.next() as usize % ((n - 1) * 8);
+        buf[1 + bit / 8] ^= 1 << (bit % 8);
+        assert!(Frame::decode(&buf[..n]).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+// resample, sample rate conversion of a stream
+//
+// Linear interpolation between consecutive input samples, at any ratio.
+// Fine for upsampling and for small rate differences (e.g., 44.1 kHz and
+// 48 kHz), while downsampling by larger factors aliases whatever is above
+// the new Nyquist frequency.
+
+pub struct Resampler {
+    // input samples per output sample
+    step: f64,
+    // position of the next output sample after `last`, in input samples
+    position: f64,
+    last: f32,
+}
+
+impl Resampler {
+    /// From `from` Hz to `to` Hz.
+    pub fn new(from: f32, to: f32) -> Self {
+        Resampler {
+            step: from as f64 / to as f64,
+            position: 0.0,
+            last: 0.0,
+        }
+    }
+
+    /// Resamples the next `input` samples, calling `out` for each output
+    /// sample.
+    pub fn process(&mut self, input: &[f32], mut out: impl FnMut(f32)) {
+        for x in input {
+            while self.position < 1.0 {
+                out(self.last + (x - self.last) * self.position as f32);
+                self.position += self.step;
+            }
+            self.position -= 1.0;
+            self.last = *x;
+        }
+    }
+}
+
+#[test]
+fn resample_test() {
+    // a second of 100 Hz from 44.1 kHz to 48 kHz, in blocks
+    let x: [f32; 44100] = core::array::from_fn(|i| {
+        libm::sinf(2.0 * core::f32::consts::PI * 100.0 * i as f32 / 44100.0)
+    });
+    let mut y = [0.0; 48100];
+    let mut n = 0;
+    let mut r = Resampler::new(44100.0, 48000.0);
+    for block in x.chunks(256) {
+        r.process(block, |s| {
+            y[n] = s;
+            n += 1;
+        });
+    }
+    assert!(n.abs_diff(48000) <= 1, "{}", n);
+    // the same sine at the new rate, an input sample late
+    for (i, y) in y[..n].iter().enumerate().skip(2) {
+        let t = i as f32 / 48000.0 - 1.0 / 44100.0;
+        let expected = libm::sinf(2.0 * core::f32::consts::PI * 100.0 * t);
+        assert!((y - expected).abs() < 0.001, "{} {} {}", i, y, expected);
+    }
+}
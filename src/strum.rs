@@ -0,0 +1,261 @@
+// strum, polyphonic tuning of all strings from a single strum
+//
+// Each string is searched for within `range` cents of its target. The
+// spectrum is evaluated at candidate frequencies by the (Hann windowed)
+// Goertzel algorithm, summing the fundamental and the second harmonic, and
+// the peak is refined by parabolic interpolation.
+//
+// Strings whose partials coincide with those of lower strings (e.g., B3 and
+// the third harmonic of E2) cannot be told apart while both are in tune, so
+// a muted string might be reported as sounding.
+
+use core::f64::consts::PI;
+
+use crate::{
+    note::cents,
+    tuning::{Tuning, MAX_STRINGS},
+};
+
+/// Number of partials summed for each candidate frequency.
+const HARMONICS: usize = 2;
+/// Candidate spacing of the coarse and fine search in cents.
+const COARSE: f32 = 5.0;
+const FINE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Not sounding (or too weak to be measured).
+    Missing,
+    Flat,
+    InTune,
+    Sharp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StringReading {
+    /// Target frequency in Hz.
+    pub target: f32,
+    /// Detected pitch in Hz.
+    pub pitch: f32,
+    /// Deviation from the target in cents.
+    pub cents: f32,
+    /// Level of the fundamental in dBFS.
+    pub level: f32,
+    pub status: Status,
+}
+
+/// Per string result of `Strum::analyze`, lowest string first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Report {
+    strings: [StringReading; MAX_STRINGS],
+    len: usize,
+}
+
+impl Report {
+    pub fn strings(&self) -> &[StringReading] {
+        &self.strings[..self.len]
+    }
+
+    /// True if all strings are sounding and in tune.
+    pub fn in_tune(&self) -> bool {
+        self.strings().iter().all(|s| s.status == Status::InTune)
+    }
+}
+
+/// Polyphonic tuner, estimating all strings of `tuning` at once.
+#[derive(Debug, Clone, Copy)]
+pub struct Strum {
+    pub tuning: Tuning,
+    pub a4: f32,
+    /// Search range around each target in cents.
+    pub range: f32,
+    /// Maximum deviation in cents considered in tune.
+    pub tolerance: f32,
+    /// Strings below this level (dBFS) are missing.
+    pub floor: f32,
+    /// Strings this many dB below the loudest string are missing.
+    pub relative: f32,
+}
+
+impl Strum {
+    pub const fn new(tuning: Tuning) -> Self {
+        Strum {
+            tuning,
+            a4: crate::note::A4,
+            range: 100.0,
+            tolerance: 5.0,
+            floor: -60.0,
+            relative: -30.0,
+        }
+    }
+
+    /// Analyzes a strum sampled at `fs`, one second or more of signal
+    /// gives sub-cent resolution.
+    pub fn analyze(&self, samples: &[f32], fs: f32) -> Report {
+        let none = StringReading {
+            target: 0.0,
+            pitch: 0.0,
+            cents: 0.0,
+            level: f32::NEG_INFINITY,
+            status: Status::Missing,
+        };
+        let mut report = Report {
+            strings: [none; MAX_STRINGS],
+            len: self.tuning.strings().len(),
+        };
+
+        let mut loudest = f32::NEG_INFINITY;
+        for (i, r) in report.strings[..report.len].iter_mut().enumerate() {
+            let target = self.tuning.target(i, self.a4);
+            let score = |c: f32| partials(samples, fs, target * libm::exp2f(c / 1200.0));
+
+            let (coarse, _) = search(-self.range, self.range, COARSE, score);
+            let (c, _) = search(coarse - COARSE, coarse + COARSE, FINE, score);
+
+            r.target = target;
+            r.pitch = target * libm::exp2f(c / 1200.0);
+            r.cents = cents(r.pitch, target);
+            // the fundamental must be present, the second harmonic alone
+            // might belong to a lower string
+            r.level = 20.0 * libm::log10f(amplitude(samples, fs, r.pitch));
+            loudest = loudest.max(r.level);
+        }
+
+        for r in report.strings[..report.len].iter_mut() {
+            r.status = if r.level < self.floor || r.level < loudest + self.relative {
+                Status::Missing
+            } else if r.cents < -self.tolerance {
+                Status::Flat
+            } else if r.cents > self.tolerance {
+                Status::Sharp
+            } else {
+                Status::InTune
+            };
+        }
+        report
+    }
+}
+
+// Evaluates `score` from `low` to `high` (in cents) in `step`s, returning
+// the (interpolated) position and value of the maximum.
+pub(crate) fn search(low: f32, high: f32, step: f32, score: impl Fn(f32) -> f32) -> (f32, f32) {
+    let n = libm::roundf((high - low) / step) as usize;
+    let (mut best, mut best_score) = (0, f32::NEG_INFINITY);
+    let (mut prev, mut next) = (0.0, 0.0);
+    let mut before = 0.0;
+    for i in 0..=n {
+        let s = score(low + i as f32 * step);
+        if s > best_score {
+            best = i;
+            best_score = s;
+            prev = before;
+            next = 0.0;
+        } else if i == best + 1 {
+            next = s;
+        }
+        before = s;
+    }
+
+    // parabolic interpolation, not at the ends of the range
+    let mut offset = 0.0;
+    if best > 0 && best < n {
+        let d = prev - 2.0 * best_score + next;
+        if d < 0.0 {
+            offset = (0.5 * (prev - next) / d).clamp(-0.5, 0.5);
+        }
+    }
+    (low + (best as f32 + offset) * step, best_score)
+}
+
+// Summed amplitude of the first `HARMONICS` partials of `f`.
+fn partials(samples: &[f32], fs: f32, f: f32) -> f32 {
+    (1..=HARMONICS)
+        .map(|k| amplitude(samples, fs, k as f32 * f))
+        .sum()
+}
+
+/// Amplitude of the frequency component `f` in `samples` (Goertzel, Hann
+/// windowed). Components above the Nyquist frequency are zero.
+pub fn amplitude(samples: &[f32], fs: f32, f: f32) -> f32 {
+    let n = samples.len();
+    if n < 2 || f >= fs / 2.0 {
+        return 0.0;
+    }
+    let coeff = 2.0 * libm::cos(2.0 * PI * f as f64 / fs as f64);
+
+    // the window is computed by rotating (c, s) a step per sample
+    let step = 2.0 * PI / (n - 1) as f64;
+    let (dc, ds) = (libm::cos(step), libm::sin(step));
+    let (mut c, mut s) = (1.0f64, 0.0f64);
+
+    let (mut s1, mut s2) = (0.0f64, 0.0f64);
+    for &x in samples {
+        let w = 0.5 - 0.5 * c;
+        let s0 = x as f64 * w + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+        (c, s) = (c * dc - s * ds, s * dc + c * ds);
+    }
+    let power = (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0);
+    // the Hann window sums to n/2 and a sine splits in two halves
+    (4.0 * libm::sqrt(power) / n as f64) as f32
+}
+
+#[cfg(test)]
+use crate::testing::{sine, FS};
+
+// Plucked string, partials falling off as 1/k.
+#[cfg(test)]
+fn pluck(out: &mut [f32], f: f32, amplitude: f32) {
+    for (i, y) in out.iter_mut().enumerate() {
+        let t = i as f32 / FS;
+        for k in 1..=6 {
+            let phase = 2.0 * core::f32::consts::PI * k as f32 * f * t;
+            *y += amplitude / k as f32 * libm::sinf(phase);
+        }
+    }
+}
+
+#[test]
+fn amplitude_test() {
+    let mut x = [0.0; 4000];
+    for (i, y) in x.iter_mut().enumerate() {
+        *y = 0.5 * sine(100.0, FS, i);
+    }
+    assert!((amplitude(&x, FS, 100.0) - 0.5).abs() < 0.01);
+    assert!(amplitude(&x, FS, 150.0) < 0.001);
+    assert_eq!(amplitude(&x, FS, 5000.0), 0.0);
+}
+
+#[test]
+fn strum_test() {
+    let t = Tuning::STANDARD;
+    // detuning per string, G3 muted
+    let detune = [-12.0, 0.0, 8.0, f32::NAN, 20.0, -3.0];
+    let mut x = [0.0; FS as usize];
+    for (s, d) in detune.iter().enumerate() {
+        if !d.is_nan() {
+            pluck(&mut x, t.target(s, 440.0) * libm::exp2f(d / 1200.0), 0.1);
+        }
+    }
+
+    let report = Strum::new(t).analyze(&x, FS);
+    let status: [Status; 6] = core::array::from_fn(|s| report.strings()[s].status);
+    assert_eq!(
+        status,
+        [
+            Status::Flat,
+            Status::InTune,
+            Status::Sharp,
+            Status::Missing,
+            Status::Sharp,
+            Status::InTune
+        ]
+    );
+    for (r, d) in report.strings().iter().zip(detune) {
+        if !d.is_nan() {
+            assert!((r.cents - d).abs() < 1.0, "{:?} expected {}", r, d);
+        }
+    }
+    assert!(!report.in_tune());
+}
@@ -0,0 +1,167 @@
+// temperament, deviations from equal temperament
+//
+// The note model assumes 12-TET, other temperaments are given by the
+// deviation of each note from its equal tempered pitch, depending on the
+// interval from the key. The key itself is kept at its equal tempered pitch
+// (so A4 is at the reference pitch only in equal temperament or when in the
+// key of A).
+
+use core::{fmt, str::FromStr};
+
+use crate::note::{pitch_class, Note, ParseNoteError};
+
+// Deviations in cents from 12-TET by semitones above the key.
+//
+// 5-limit just intonation: 1, 16/15, 9/8, 6/5, 5/4, 4/3, 45/32, 3/2, 8/5,
+// 5/3, 9/5, 15/8.
+const JUST: [f32; 12] = [
+    0.0, 11.73, 3.91, 15.64, -13.69, -1.96, -9.78, 1.96, 13.69, -15.64, 17.6, -11.73,
+];
+// Pure fifths, the tritone as an augmented fourth (729/512).
+const PYTHAGOREAN: [f32; 12] = [
+    0.0, -9.78, 3.91, -5.87, 7.82, -1.96, 11.73, 1.96, -7.82, 5.87, -3.91, 9.78,
+];
+// Quarter-comma meantone, pure major thirds, the wolf fifth between the
+// augmented fifth and the minor third above the key.
+const MEANTONE: [f32; 12] = [
+    0.0, -23.95, -6.84, 10.26, -13.69, 3.42, -20.53, -3.42, -27.37, -10.26, 6.84, -17.11,
+];
+// Werckmeister III, the fifths C-G-D-A and B-F# narrowed by a quarter of
+// the Pythagorean comma.
+const WERCKMEISTER: [f32; 12] = [
+    0.0, -9.78, -7.82, -5.87, -9.78, -1.96, -11.73, -3.91, -7.82, -11.73, -3.91, -7.82,
+];
+
+/// A temperament, all but `Equal` relative to a key given as semitones
+/// above C (0..12).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Temperament {
+    /// Twelve tone equal temperament (12-TET).
+    #[default]
+    Equal,
+    /// 5-limit just intonation.
+    Just(u8),
+    Pythagorean(u8),
+    /// Quarter-comma meantone.
+    Meantone(u8),
+    /// Werckmeister III.
+    Werckmeister(u8),
+}
+
+impl Temperament {
+    /// Deviation of `note` from its equal tempered pitch in cents.
+    pub fn offset(self, note: Note) -> f32 {
+        let (table, key) = match self {
+            Temperament::Equal => return 0.0,
+            Temperament::Just(key) => (&JUST, key),
+            Temperament::Pythagorean(key) => (&PYTHAGOREAN, key),
+            Temperament::Meantone(key) => (&MEANTONE, key),
+            Temperament::Werckmeister(key) => (&WERCKMEISTER, key),
+        };
+        table[(note.0 as usize + 12 - key as usize % 12) % 12]
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Temperament::Equal => "equal",
+            Temperament::Just(_) => "just",
+            Temperament::Pythagorean(_) => "pythagorean",
+            Temperament::Meantone(_) => "meantone",
+            Temperament::Werckmeister(_) => "werckmeister",
+        }
+    }
+
+    fn key(self) -> Option<u8> {
+        match self {
+            Temperament::Equal => None,
+            Temperament::Just(key)
+            | Temperament::Pythagorean(key)
+            | Temperament::Meantone(key)
+            | Temperament::Werckmeister(key) => Some(key),
+        }
+    }
+}
+
+impl fmt::Display for Temperament {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())?;
+        if let Some(key) = self.key() {
+            // the name of the key, without octave
+            write!(f, ":{}", Note(key).name())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseTemperamentError;
+
+impl fmt::Display for ParseTemperamentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid temperament, expected \"equal\", \"just\", \"pythagorean\", \"meantone\" or \"werckmeister\", optionally with a key, e.g. \"just:G\""
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseTemperamentError {}
+
+impl From<ParseNoteError> for ParseTemperamentError {
+    fn from(_: ParseNoteError) -> Self {
+        ParseTemperamentError
+    }
+}
+
+impl FromStr for Temperament {
+    type Err = ParseTemperamentError;
+
+    /// Parses a name, optionally followed by `:` and the key (C if
+    /// omitted), e.g., "meantone:Eb".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, key) = match s.split_once(':') {
+            Some((name, key)) => {
+                let (semitone, rest) = pitch_class(key)?;
+                if !rest.is_empty() {
+                    return Err(ParseTemperamentError);
+                }
+                (name, semitone.rem_euclid(12) as u8)
+            }
+            None => (s, 0),
+        };
+        match name {
+            "equal" if key == 0 => Ok(Temperament::Equal),
+            "just" => Ok(Temperament::Just(key)),
+            "pythagorean" => Ok(Temperament::Pythagorean(key)),
+            "meantone" => Ok(Temperament::Meantone(key)),
+            "werckmeister" => Ok(Temperament::Werckmeister(key)),
+            _ => Err(ParseTemperamentError),
+        }
+    }
+}
+
+#[test]
+fn temperament_test() {
+    assert_eq!("equal".parse(), Ok(Temperament::Equal));
+    assert_eq!("just:G".parse(), Ok(Temperament::Just(7)));
+    assert_eq!("meantone:Eb".parse(), Ok(Temperament::Meantone(3)));
+    assert_eq!("werckmeister".parse(), Ok(Temperament::Werckmeister(0)));
+    assert_eq!("just:X".parse::<Temperament>(), Err(ParseTemperamentError));
+    assert_eq!("just:G4".parse::<Temperament>(), Err(ParseTemperamentError));
+    assert_eq!(Temperament::Pythagorean(1).to_string(), "pythagorean:C#");
+
+    // the key is not moved, the major third above it is pure in just
+    // intonation and meantone
+    let e2 = Note(40);
+    assert_eq!(Temperament::Just(4).offset(e2), 0.0);
+    let third = 1200.0 * libm::log2f(5.0 / 4.0) - 400.0;
+    assert!((Temperament::Just(0).offset(e2) - third).abs() < 0.01);
+    assert!((Temperament::Meantone(0).offset(e2) - third).abs() < 0.01);
+
+    // the Pythagorean fifth is pure
+    let fifth = 1200.0 * libm::log2f(3.0 / 2.0) - 700.0;
+    let g = Note(43);
+    assert!((Temperament::Pythagorean(0).offset(g) - fifth).abs() < 0.01);
+    assert_eq!(Temperament::Equal.offset(g), 0.0);
+}
@@ -0,0 +1,28 @@
+// testing, signals shared by the unit tests
+//
+// The tests run the trackers and detectors at `FS`, the stages ahead of
+// them (filters, decimation, the pass-through) at the rate of a sound card,
+// `DEVICE_FS`.
+
+use core::f32::consts::PI;
+
+/// Sample rate of the trackers in Hz.
+pub(crate) const FS: f32 = 8000.0;
+
+/// Sample rate of a sound card in Hz.
+pub(crate) const DEVICE_FS: f32 = 48000.0;
+
+/// A string like tone at `phase` radians of its fundamental, the second
+/// harmonic the strongest.
+pub(crate) fn string(phase: f32) -> f32 {
+    [0.5, 1.0, 0.5, 0.25]
+        .iter()
+        .enumerate()
+        .map(|(k, a)| 0.1 * a * libm::sinf((k + 1) as f32 * phase))
+        .sum()
+}
+
+/// Sample `i` of a sine of `freq` Hz at `fs`.
+pub(crate) fn sine(freq: f32, fs: f32, i: usize) -> f32 {
+    libm::sinf(2.0 * PI * freq * i as f32 / fs)
+}
@@ -0,0 +1,216 @@
+// tone, reference tones for tuning by ear
+//
+// A sine, faded in and out to avoid clicks, or a plucked string by the
+// Karplus-Strong algorithm: a delay line of one period, initially noise,
+// fed back through a two point average (a lowpass damping the higher
+// partials first). The average delays by half a sample, and the delay line
+// is a whole number of samples, so the rest of the period is made up by a
+// first order allpass (Jaffe and Smith), keeping the pitch exact.
+
+/// Fade time of the sine in seconds.
+const FADE: f32 = 0.01;
+/// Smallest fractional delay of the allpass, larger is more stable.
+const MIN_DELAY: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timbre {
+    #[default]
+    Sine,
+    /// Plucked string.
+    Pluck,
+}
+
+/// Tone generator, plucked tones down to `fs / N` Hz.
+pub struct Tone<const N: usize> {
+    /// Sample rate in Hz.
+    pub fs: f32,
+    /// Peak amplitude.
+    pub amplitude: f32,
+    /// Gain of the plucked string per period, less decays faster.
+    pub sustain: f32,
+    /// Time between plucks in seconds, 0.0 plucks once.
+    pub repeat: f32,
+    freq: f32,
+    timbre: Timbre,
+    // sine
+    phase: f32,
+    gain: f32,
+    target: f32,
+    // plucked string
+    delay: [f32; N],
+    len: usize,
+    index: usize,
+    coefficient: f32,
+    last: f32,
+    state: (f32, f32),
+    elapsed: usize,
+    seed: u32,
+}
+
+impl<const N: usize> Tone<N> {
+    /// Silent generator at sample rate `fs`.
+    pub const fn new(fs: f32) -> Self {
+        Tone {
+            fs,
+            amplitude: 0.5,
+            sustain: 0.996,
+            repeat: 2.0,
+            freq: 0.0,
+            timbre: Timbre::Sine,
+            phase: 0.0,
+            gain: 0.0,
+            target: 0.0,
+            delay: [0.0; N],
+            len: 0,
+            index: 0,
+            coefficient: 0.0,
+            last: 0.0,
+            state: (0.0, 0.0),
+            elapsed: 0,
+            seed: 0x2545_f491,
+        }
+    }
+
+    pub fn freq(&self) -> f32 {
+        self.freq
+    }
+
+    /// Starts a tone at `freq` Hz, false if too low for the delay line.
+    pub fn play(&mut self, freq: f32, timbre: Timbre) -> bool {
+        let period = self.fs / freq;
+        if timbre == Timbre::Pluck && period - 0.5 - MIN_DELAY >= N as f32 {
+            return false;
+        }
+        self.freq = freq;
+        self.timbre = timbre;
+        match timbre {
+            Timbre::Sine => self.target = 1.0,
+            Timbre::Pluck => {
+                // the loop delay is len + 0.5 (average) + delta (allpass)
+                self.len = libm::floorf(period - 0.5 - MIN_DELAY) as usize;
+                let delta = period - 0.5 - self.len as f32;
+                self.coefficient = (1.0 - delta) / (1.0 + delta);
+                self.pluck();
+            }
+        }
+        true
+    }
+
+    /// Fades out the sine, silences the string.
+    pub fn stop(&mut self) {
+        self.target = 0.0;
+        self.len = 0;
+    }
+
+    /// Plucks the string again.
+    pub fn pluck(&mut self) {
+        let mut sum = 0.0;
+        for x in &mut self.delay[..self.len] {
+            // xorshift
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            *x = self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0;
+            sum += *x;
+        }
+        // no DC, it would not decay
+        let mean = sum / self.len.max(1) as f32;
+        for x in &mut self.delay[..self.len] {
+            *x = (*x - mean) * self.amplitude;
+        }
+        self.index = 0;
+        self.last = 0.0;
+        self.state = (0.0, 0.0);
+        self.elapsed = 0;
+    }
+
+    /// The next sample.
+    pub fn next_sample(&mut self) -> f32 {
+        match self.timbre {
+            Timbre::Sine => {
+                let step = 1.0 / (FADE * self.fs);
+                self.gain = match self.gain < self.target {
+                    true => (self.gain + step).min(self.target),
+                    false => (self.gain - step).max(self.target),
+                };
+                self.phase += self.freq / self.fs;
+                self.phase -= libm::floorf(self.phase);
+                self.amplitude * self.gain * libm::sinf(2.0 * core::f32::consts::PI * self.phase)
+            }
+            Timbre::Pluck => {
+                if self.len == 0 {
+                    return 0.0;
+                }
+                self.elapsed += 1;
+                if self.repeat > 0.0 && self.elapsed as f32 >= self.repeat * self.fs {
+                    self.pluck();
+                }
+                let out = self.delay[self.index];
+                let average = 0.5 * (out + self.last);
+                self.last = out;
+                // first order allpass
+                let (x1, y1) = self.state;
+                let y = self.coefficient * (average - y1) + x1;
+                self.state = (average, y);
+                self.delay[self.index] = self.sustain * y;
+                self.index = (self.index + 1) % self.len;
+                out
+            }
+        }
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for x in out {
+            *x = self.next_sample();
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::testing::FS;
+
+#[test]
+fn sine_test() {
+    let mut tone = Tone::<16>::new(FS);
+    assert!(tone.play(440.0, Timbre::Sine));
+    let mut x = [0.0; 8000];
+    tone.fill(&mut x);
+    // faded in
+    assert!(x[..8].iter().all(|x| x.abs() < 0.05));
+    assert!(x[100..].iter().any(|x| *x > 0.49));
+
+    let (mut first, mut last, mut n) = (None, 0.0, 0);
+    for (t, _) in crate::lerp::zero_crossings(&x[100..]).filter(|(_, rising)| *rising) {
+        first.get_or_insert(t);
+        last = t;
+        n += 1;
+    }
+    let f = (n - 1) as f32 / (last - first.unwrap()) * FS;
+    assert!(crate::note::cents(f, 440.0).abs() < 0.1, "{}", f);
+
+    // faded out
+    tone.stop();
+    tone.fill(&mut x[..100]);
+    assert!(x[90..100].iter().all(|x| *x == 0.0));
+}
+
+#[test]
+fn pluck_test() {
+    // G3, 40.8 samples per period
+    let mut tone = Tone::<64>::new(FS);
+    assert!(!tone.play(100.0, Timbre::Pluck));
+    assert!(tone.play(196.0, Timbre::Pluck));
+
+    let mut t = crate::track::Tracker::<1024>::new(FS);
+    let mut x = [0.0; 64];
+    for _ in 0..50 {
+        tone.fill(&mut x);
+        t.extend(&x);
+        t.update();
+    }
+    assert!(
+        crate::note::cents(t.pitch().unwrap(), 196.0).abs() < 1.0,
+        "{:?}",
+        t.pitch()
+    );
+}
@@ -0,0 +1,423 @@
+// track, time-domain pitch tracker
+//
+// The scheme of `examples/simple_tuner_works.rs` on a history of the last
+// `N` samples. In `Search` the period is found by the cumulative mean
+// normalized difference (as in YIN) over the whole lag range, unless the
+// tracker is `seed`ed (e.g., by the FFT detector). In `Track` only lags next
+// to the current period are evaluated, following the pitch as it drifts
+// during the decay, the period refined at its longest multiple in the window
+// (for the few samples per period at low sample rates). A sudden rise in
+// energy (a new pluck) or a loss of periodicity restarts the search, a level
+// below the noise gate is `Idle`.
+
+use core::fmt;
+
+use crate::{
+    note::Note,
+    protocol::{Reading, State},
+    tuning::Tuning,
+};
+
+/// Pitch range of `Tracker::new` in Hz (guitar and bass).
+pub const MIN_FREQ: f32 = 60.0;
+pub const MAX_FREQ: f32 = 1200.0;
+
+/// Time-domain pitch tracker, `N` must hold two periods of `min_freq` (the
+/// lowest pitches are not tracked otherwise).
+pub struct Tracker<const N: usize> {
+    /// Sample rate in Hz.
+    pub fs: f32,
+    /// Pitch range in Hz.
+    pub min_freq: f32,
+    pub max_freq: f32,
+    /// Noise gate in dBFS.
+    pub gate: f32,
+    /// Largest normalized difference accepted as periodic.
+    pub threshold: f32,
+    history: [f32; N],
+    index: usize,
+    filled: usize,
+    state: State,
+    period: f32,
+    confidence: f32,
+    level: f32,
+    energy: f32,
+}
+
+impl<const N: usize> Tracker<N> {
+    /// Tracker for `MIN_FREQ..MAX_FREQ` at sample rate `fs`.
+    pub const fn new(fs: f32) -> Self {
+        Tracker {
+            fs,
+            min_freq: MIN_FREQ,
+            max_freq: MAX_FREQ,
+            gate: -50.0,
+            threshold: 0.15,
+            history: [0.0; N],
+            index: 0,
+            filled: 0,
+            state: State::Idle,
+            period: 0.0,
+            confidence: 0.0,
+            level: f32::NEG_INFINITY,
+            energy: 0.0,
+        }
+    }
+
+    /// Tracker for `min_freq..max_freq` Hz at sample rate `fs`, an error
+    /// if `fs` is above `max_fs(min_freq)`.
+    pub fn with_range(fs: f32, min_freq: f32, max_freq: f32) -> Result<Self, RangeError> {
+        if fs > Self::max_fs(min_freq) {
+            return Err(RangeError {
+                fs,
+                min_freq,
+                max_fs: Self::max_fs(min_freq),
+            });
+        }
+        let mut tracker = Tracker::new(fs);
+        tracker.min_freq = min_freq;
+        tracker.max_freq = max_freq;
+        Ok(tracker)
+    }
+
+    /// Highest sample rate at which `N` holds two periods of `min_freq`.
+    pub fn max_fs(min_freq: f32) -> f32 {
+        ((N - 1) / 2) as f32 * min_freq
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        self.history[self.index] = sample;
+        self.index = (self.index + 1) % N;
+        self.filled = (self.filled + 1).min(N);
+    }
+
+    pub fn extend(&mut self, samples: &[f32]) {
+        for &s in samples {
+            self.push(s);
+        }
+    }
+
+    /// Starts tracking at `period` (in samples), skipping the search, or
+    /// searches if `period` is outside the pitch range.
+    pub fn seed(&mut self, period: f32) {
+        let (min_lag, max_lag) = self.lags();
+        let window = max_lag;
+        // the current signal is not a new pluck
+        self.energy = (0..window.min(self.filled))
+            .map(|i| self.x(i) * self.x(i))
+            .sum();
+        if !(min_lag as f32..=max_lag as f32).contains(&period) {
+            self.state = State::Search;
+            return;
+        }
+        self.period = period;
+        self.state = State::Track;
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The tracked pitch in Hz.
+    pub fn pitch(&self) -> Option<f32> {
+        (self.state == State::Track).then(|| self.fs / self.period)
+    }
+
+    /// The tracked period in samples.
+    pub fn period(&self) -> Option<f32> {
+        (self.state == State::Track).then_some(self.period)
+    }
+
+    /// Periodicity of the signal, 0.0..=1.0.
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    /// RMS level of the analysis window in dBFS.
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    /// Runs the detector on the latest samples, typically once per block
+    /// of samples pushed.
+    pub fn update(&mut self) -> State {
+        let (min_lag, max_lag) = self.lags();
+        let window = max_lag;
+        if self.filled < window + max_lag + 1 {
+            return self.state;
+        }
+
+        let energy: f32 = (0..window).map(|i| self.x(i) * self.x(i)).sum();
+        self.level = 10.0 * libm::log10f(energy / window as f32);
+        if self.level < self.gate {
+            self.state = State::Idle;
+            self.confidence = 0.0;
+            self.energy = energy;
+            return self.state;
+        }
+        // a new pluck
+        if energy > 4.0 * self.energy || self.state == State::Idle {
+            self.state = State::Search;
+        }
+        self.energy = 0.5 * (self.energy + energy);
+
+        if self.state == State::Search {
+            self.search(min_lag, max_lag, window);
+        } else {
+            self.track(min_lag, max_lag, window, energy);
+        }
+        self.state
+    }
+
+    fn search(&mut self, min_lag: usize, max_lag: usize, window: usize) {
+        // cumulative mean normalized difference of lags tau - 1, tau, tau + 1
+        let mut sum = 0.0;
+        let mut cmnd = [1.0f32; 3];
+        let mut best = (0, f32::INFINITY);
+        for tau in 1..=max_lag {
+            let d = self.diff(tau, window);
+            sum += d;
+            cmnd = [
+                cmnd[1],
+                cmnd[2],
+                if sum > 0.0 { d * tau as f32 / sum } else { 1.0 },
+            ];
+            // cmnd[1] is a local minimum below the threshold
+            let lag = tau - 1;
+            if lag >= min_lag && cmnd[1] < self.threshold && cmnd[1] <= cmnd[2] {
+                self.period = lag as f32 + parabola(cmnd[0], cmnd[1], cmnd[2]);
+                self.confidence = 1.0 - cmnd[1];
+                self.state = State::Track;
+                return;
+            }
+            if lag >= min_lag && cmnd[1] < best.1 {
+                best = (lag, cmnd[1]);
+            }
+        }
+        self.confidence = (1.0 - best.1).max(0.0);
+    }
+
+    fn track(&mut self, min_lag: usize, max_lag: usize, window: usize, energy: f32) {
+        let lock = self.period;
+        let low = (libm::floorf(lock * 0.8) as usize).max(min_lag + 1);
+        let high = (libm::ceilf(lock * 1.2) as usize).min(max_lag - 1);
+        let (p, below, mid, above) = self.descend(libm::roundf(lock) as usize, low, high, window);
+
+        // for a periodic signal the difference vanishes, for noise it is
+        // twice the energy
+        let normalized = if energy > 0.0 {
+            mid / (2.0 * energy)
+        } else {
+            1.0
+        };
+        self.confidence = (1.0 - normalized).max(0.0);
+        if normalized > 2.0 * self.threshold {
+            self.state = State::Search;
+            return;
+        }
+        let period = p as f32 + parabola(below, mid, above);
+
+        // again at the longest multiple of the period, dividing the error of
+        // the interpolation (large at low sample rates, the minimum sharpened
+        // by the upper partials) by the multiple
+        let k = libm::floorf((max_lag - 2) as f32 / period);
+        self.period = period;
+        if k >= 2.0 {
+            let lag = libm::roundf(k * period) as usize;
+            let (q, below, mid, above) = self.descend(lag, lag - 1, lag + 1, window);
+            self.period = (q as f32 + parabola(below, mid, above)) / k;
+        }
+    }
+
+    // Follows the slope of the difference function downhill from lag `p`,
+    // within `low..=high`, returning the lag of the minimum and the
+    // differences at the lags before, at and after it.
+    fn descend(&self, p: usize, low: usize, high: usize, window: usize) -> (usize, f32, f32, f32) {
+        let mut p = p.clamp(low, high);
+        let mut mid = self.diff(p, window);
+        let mut below = self.diff(p - 1, window);
+        let mut above = self.diff(p + 1, window);
+        while below < mid && p > low {
+            p -= 1;
+            (above, mid, below) = (mid, below, self.diff(p - 1, window));
+        }
+        while above < mid && p < high {
+            p += 1;
+            (below, mid, above) = (mid, above, self.diff(p + 1, window));
+        }
+        (p, below, mid, above)
+    }
+
+    // Lag range in samples, the longest lag is also the window length (two
+    // of them within the history).
+    fn lags(&self) -> (usize, usize) {
+        let min_lag = libm::floorf(self.fs / self.max_freq).max(2.0) as usize;
+        let max_lag = (libm::ceilf(self.fs / self.min_freq) as usize).min((N - 1) / 2);
+        (min_lag, max_lag)
+    }
+
+    // The sample `i` samples back from the latest.
+    fn x(&self, i: usize) -> f32 {
+        self.history[(self.index + N - 1 - i) % N]
+    }
+
+    // Squared difference between the window and the window `lag` back.
+    fn diff(&self, lag: usize, window: usize) -> f32 {
+        (0..window)
+            .map(|i| {
+                let d = self.x(i) - self.x(i + lag);
+                d * d
+            })
+            .sum()
+    }
+
+    /// The current state as a `Reading`, the deviation is given to the
+    /// closest string of `tuning`, or to the closest note (in the
+    /// temperament of `tuning`) if `tuning` has no strings.
+    pub fn reading(&self, tuning: &Tuning, a4: f32) -> Reading {
+        let mut reading = Reading {
+            pitch: 0.0,
+            cents: 0.0,
+            string: None,
+            confidence: self.confidence,
+            level: self.level.max(-327.0),
+            state: self.state,
+        };
+        if let Some(pitch) = self.pitch() {
+            reading.pitch = pitch;
+            match tuning.classify(pitch, a4) {
+                Some((string, c)) => {
+                    reading.string = Some(string as u8);
+                    reading.cents = c;
+                }
+                None => {
+                    let (note, c) = Note::nearest(pitch, a4);
+                    reading.cents = c - tuning.temperament().offset(note);
+                }
+            }
+        }
+        reading
+    }
+}
+
+/// The history of a `Tracker` is too short for its range at its sample rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeError {
+    pub fs: f32,
+    pub min_freq: f32,
+    /// Highest sample rate for `min_freq`.
+    pub max_fs: f32,
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sample rate of {} Hz too high to track down to {} Hz, at most {} Hz",
+            self.fs, self.min_freq, self.max_fs
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RangeError {}
+
+// Offset of the vertex of the parabola through (-1, a), (0, b), (1, c).
+fn parabola(a: f32, b: f32, c: f32) -> f32 {
+    let d = a - 2.0 * b + c;
+    if d <= 0.0 {
+        return 0.0;
+    }
+    (0.5 * (a - c) / d).clamp(-0.5, 0.5)
+}
+
+#[cfg(test)]
+use crate::testing::{sine, string, FS};
+
+// Feeds `n` samples of a string like tone at `f` Hz (continuing at sample
+// `start`), updating every 64 samples.
+#[cfg(test)]
+fn feed<const N: usize>(t: &mut Tracker<N>, start: usize, n: usize, f: impl Fn(usize) -> f32) {
+    let mut phase = 0.0f32;
+    for i in start..start + n {
+        phase += 2.0 * core::f32::consts::PI * f(i) / FS;
+        t.push(string(phase));
+        if i % 64 == 0 {
+            t.update();
+        }
+    }
+}
+
+#[test]
+fn track_test() {
+    let mut t = Tracker::<512>::new(FS);
+    assert_eq!(t.update(), State::Idle);
+    assert!(Tracker::<512>::with_range(FS, 60.0, 1200.0).is_ok());
+    assert!(Tracker::<512>::with_range(FS, 30.0, 1200.0).is_err());
+
+    feed(&mut t, 0, 2000, |_| 82.41);
+    assert_eq!(t.state(), State::Track);
+    assert!(
+        crate::note::cents(t.pitch().unwrap(), 82.41).abs() < 1.0,
+        "{:?}",
+        t.pitch()
+    );
+    let r = t.reading(&Tuning::STANDARD, 440.0);
+    assert_eq!(r.string, Some(0));
+    assert!(r.cents.abs() < 1.0);
+
+    // bend up a quarter tone and follow
+    feed(&mut t, 2000, 4000, |i| {
+        82.41 * libm::exp2f(50.0 * i as f32 / 6000.0 / 1200.0)
+    });
+    assert_eq!(t.state(), State::Track);
+    assert!(crate::note::cents(t.pitch().unwrap(), 82.41).abs() > 30.0);
+
+    // silence
+    for _ in 0..512 {
+        t.push(0.0);
+    }
+    assert_eq!(t.update(), State::Idle);
+    assert_eq!(t.pitch(), None);
+}
+
+#[test]
+fn seed_test() {
+    // an E4 seeded from the FFT detector
+    let mut t = Tracker::<512>::new(FS);
+    let mut x = [0.0; 512];
+    for (i, v) in x.iter_mut().enumerate() {
+        *v = 0.3 * sine(329.63, FS, i);
+    }
+    let e = crate::fft::Detector::<1024>::new().detect(&x, FS).unwrap();
+    t.extend(&x);
+    t.seed(e.period(FS));
+    t.update();
+    assert_eq!(t.state(), State::Track);
+    assert!(crate::note::cents(t.pitch().unwrap(), 329.63).abs() < 1.0);
+}
+
+#[test]
+fn seed_range_test() {
+    // seeded from FFT estimates above the range, or nonsense, the tracker
+    // searches instead
+    for f in [2000.0, 3000.0] {
+        let mut t = Tracker::<512>::new(FS);
+        let mut x = [0.0; 512];
+        for (i, v) in x.iter_mut().enumerate() {
+            *v = 0.3 * sine(f, FS, i);
+        }
+        let e = crate::fft::Detector::<1024>::new().detect(&x, FS);
+        t.extend(&x);
+        t.seed(e.map_or(FS / f, |e| e.period(FS)));
+        t.update();
+        assert_ne!(t.pitch(), Some(f));
+    }
+    for period in [0.0, 1.0, 1000.0, f32::NAN, f32::INFINITY] {
+        let mut t = Tracker::<512>::new(FS);
+        feed(&mut t, 0, 512, |_| 110.0);
+        t.seed(period);
+        t.update();
+        assert!(crate::note::cents(t.pitch().unwrap(), 110.0).abs() < 5.0);
+    }
+}
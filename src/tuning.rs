@@ -0,0 +1,301 @@
+// tuning, the target notes of the open strings
+
+use core::{fmt, str::FromStr};
+
+use crate::{
+    note::{cents, Note},
+    temperament::Temperament,
+};
+
+/// Maximum number of strings of a tuning.
+pub const MAX_STRINGS: usize = 8;
+
+/// Target notes of the open strings, lowest string first (string 1 in the
+/// README table), in a temperament and optionally offset by some cents per
+/// string (e.g., "sweetened", or stretched for inharmonicity, see
+/// `harmonics::stretch_tuning`). A capo and a transposition shift all
+/// targets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    strings: [Note; MAX_STRINGS],
+    offsets: [f32; MAX_STRINGS],
+    temperament: Temperament,
+    capo: u8,
+    semitones: i8,
+    cents: f32,
+    len: usize,
+}
+
+impl Tuning {
+    /// Standard guitar tuning, E2 A2 D3 G3 B3 E4.
+    pub const STANDARD: Tuning =
+        Tuning::new(&[Note(40), Note(45), Note(50), Note(55), Note(59), Note(64)]);
+
+    /// Drop D guitar tuning, D2 A2 D3 G3 B3 E4.
+    pub const DROP_D: Tuning =
+        Tuning::new(&[Note(38), Note(45), Note(50), Note(55), Note(59), Note(64)]);
+
+    /// Standard 4 string bass tuning, E1 A1 D2 G2.
+    pub const BASS: Tuning = Tuning::new(&[Note(28), Note(33), Note(38), Note(43)]);
+
+    /// Named tunings, accepted by `from_str`.
+    pub const NAMED: [(&'static str, Tuning); 3] = [
+        ("standard", Tuning::STANDARD),
+        ("drop-d", Tuning::DROP_D),
+        ("bass", Tuning::BASS),
+    ];
+
+    /// Panics if more than `MAX_STRINGS` strings are given.
+    pub const fn new(notes: &[Note]) -> Tuning {
+        assert!(notes.len() <= MAX_STRINGS);
+        let mut strings = [Note(0); MAX_STRINGS];
+        let mut i = 0;
+        while i < notes.len() {
+            strings[i] = notes[i];
+            i += 1;
+        }
+        Tuning {
+            strings,
+            offsets: [0.0; MAX_STRINGS],
+            temperament: Temperament::Equal,
+            capo: 0,
+            semitones: 0,
+            cents: 0.0,
+            len: notes.len(),
+        }
+    }
+
+    pub fn strings(&self) -> &[Note] {
+        &self.strings[..self.len]
+    }
+
+    /// Offsets of the targets in cents, per string.
+    pub fn offsets(&self) -> &[f32] {
+        &self.offsets[..self.len]
+    }
+
+    /// The tuning with the targets offset by `cents` per string, strings
+    /// beyond `cents` are kept.
+    pub fn with_offsets(mut self, cents: &[f32]) -> Tuning {
+        for (o, c) in self.offsets[..self.len].iter_mut().zip(cents) {
+            *o = *c;
+        }
+        self
+    }
+
+    /// The tuning with the targets offset by `cents` per string, added to
+    /// the current offsets.
+    pub fn add_offsets(mut self, cents: &[f32]) -> Tuning {
+        for (o, c) in self.offsets[..self.len].iter_mut().zip(cents) {
+            *o += *c;
+        }
+        self
+    }
+
+    pub fn temperament(&self) -> Temperament {
+        self.temperament
+    }
+
+    pub fn with_temperament(mut self, temperament: Temperament) -> Tuning {
+        self.temperament = temperament;
+        self
+    }
+
+    /// Fret of the capo, 0 if none.
+    pub fn capo(&self) -> u8 {
+        self.capo
+    }
+
+    pub fn with_capo(mut self, fret: u8) -> Tuning {
+        self.capo = fret;
+        self
+    }
+
+    /// Transposition in semitones and cents.
+    pub fn transposition(&self) -> (i8, f32) {
+        (self.semitones, self.cents)
+    }
+
+    /// The tuning transposed by `semitones` and `cents`, e.g., (0, -50.0)
+    /// for a quarter tone flat.
+    pub fn transposed(mut self, semitones: i8, cents: f32) -> Tuning {
+        self.semitones = semitones;
+        self.cents = cents;
+        self
+    }
+
+    /// The note `string` sounds when open (or at the capo), transposed.
+    pub fn note(&self, string: usize) -> Note {
+        let shift = self.capo as i16 + self.semitones as i16;
+        Note((self.strings()[string].0 as i16 + shift).clamp(0, 127) as u8)
+    }
+
+    /// Target frequency of `string` given the reference pitch `a4`.
+    pub fn target(&self, string: usize, a4: f32) -> f32 {
+        let note = self.note(string);
+        let offset = self.temperament.offset(note) + self.offsets[string] + self.cents;
+        note.freq(a4) * libm::exp2f(offset / 1200.0)
+    }
+
+    /// The lowest and the highest target given `a4`, None without strings.
+    pub fn range(&self, a4: f32) -> Option<(f32, f32)> {
+        (0..self.len)
+            .map(|s| self.target(s, a4))
+            .fold(None, |range, f| {
+                let (low, high) = range.unwrap_or((f, f));
+                Some((low.min(f), high.max(f)))
+            })
+    }
+
+    /// The string closest to `f` and the deviation from its target in cents.
+    pub fn classify(&self, f: f32, a4: f32) -> Option<(usize, f32)> {
+        (0..self.len)
+            .map(|s| (s, cents(f, self.target(s, a4))))
+            .min_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseTuningError;
+
+impl fmt::Display for ParseTuningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid tuning, expected \"standard\", \"drop-d\", \"bass\" or notes, e.g. \"D2,A2,D3,G3,A3,D4\", optionally offset in cents, e.g. \"B3-2c\""
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseTuningError {}
+
+impl FromStr for Tuning {
+    type Err = ParseTuningError;
+
+    /// Parses a named tuning or up to `MAX_STRINGS` notes separated by
+    /// commas or whitespace, lowest string first. A note may be followed by
+    /// an offset in cents, e.g., "B3-2c" or "E4+1.5c".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, t)) = Self::NAMED.iter().find(|(name, _)| *name == s) {
+            return Ok(*t);
+        }
+        let mut strings = [Note(0); MAX_STRINGS];
+        let mut offsets = [0.0; MAX_STRINGS];
+        let mut len = 0;
+        for n in s.split(|c: char| c == ',' || c.is_whitespace()) {
+            if n.is_empty() {
+                continue;
+            }
+            if len == MAX_STRINGS {
+                return Err(ParseTuningError);
+            }
+            let (note, offset) = match n.strip_suffix('c') {
+                // the sign of the offset follows the octave, which might
+                // be negative itself
+                Some(n) => n
+                    .rfind(['+', '-'])
+                    .map(|i| n.split_at(i))
+                    .and_then(|(note, cents)| Some((note, cents.parse().ok()?)))
+                    .ok_or(ParseTuningError)?,
+                None => (n, 0.0),
+            };
+            strings[len] = note.parse().map_err(|_| ParseTuningError)?;
+            offsets[len] = offset;
+            len += 1;
+        }
+        if len == 0 {
+            return Err(ParseTuningError);
+        }
+        Ok(Tuning {
+            strings,
+            offsets,
+            temperament: Temperament::Equal,
+            capo: 0,
+            semitones: 0,
+            cents: 0.0,
+            len,
+        })
+    }
+}
+
+impl fmt::Display for Tuning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, n) in self.strings().iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", n)?;
+            if self.offsets[i] != 0.0 {
+                write!(f, "{:+}c", self.offsets[i])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_test() {
+    assert_eq!("drop-d".parse(), Ok(Tuning::DROP_D));
+    assert_eq!("E2,A2,D3,G3,B3,E4".parse(), Ok(Tuning::STANDARD));
+    assert_eq!("E1 A1  D2 G2".parse(), Ok(Tuning::BASS));
+    assert_eq!("".parse::<Tuning>(), Err(ParseTuningError));
+    assert_eq!("E2,X2".parse::<Tuning>(), Err(ParseTuningError));
+    assert_eq!(Tuning::STANDARD.to_string(), "E2 A2 D3 G3 B3 E4");
+
+    let t: Tuning = "E2,A2,D3,G3,B3-2c,E4+1.5c".parse().unwrap();
+    assert_eq!(t.offsets(), [0.0, 0.0, 0.0, 0.0, -2.0, 1.5]);
+    assert_eq!(t.to_string(), "E2 A2 D3 G3 B3-2c E4+1.5c");
+    assert_eq!("C-1-2c".parse::<Tuning>().unwrap().offsets(), [-2.0]);
+    assert_eq!("E2-c".parse::<Tuning>(), Err(ParseTuningError));
+}
+
+#[test]
+fn classify_test() {
+    let t = Tuning::STANDARD;
+    assert_eq!(t.strings().len(), 6);
+    assert_eq!(t.classify(82.0, 440.0).map(|(s, _)| s), Some(0));
+    assert_eq!(t.classify(112.0, 440.0).map(|(s, _)| s), Some(1));
+    assert_eq!(t.classify(340.0, 440.0).map(|(s, _)| s), Some(5));
+    let (_, c) = t.classify(110.0, 440.0).unwrap();
+    assert!(c.abs() < 0.01);
+    assert_eq!(Tuning::new(&[]).classify(110.0, 440.0), None);
+    assert_eq!(Tuning::new(&[]).range(440.0), None);
+    let (low, high) = Tuning::BASS.range(440.0).unwrap();
+    assert!((low - 41.20).abs() < 0.01 && (high - 98.00).abs() < 0.01);
+
+    let t = t.with_offsets(&[-2.0, -1.0]);
+    assert_eq!(t.offsets(), [-2.0, -1.0, 0.0, 0.0, 0.0, 0.0]);
+    assert!((cents(t.target(0, 440.0), 82.407) + 2.0).abs() < 0.01);
+    let (_, c) = t.classify(110.0, 440.0).unwrap();
+    assert!((c - 1.0).abs() < 0.01);
+
+    // the major third G3-B3 is pure in just intonation in G
+    let t = Tuning::STANDARD.with_temperament(Temperament::Just(7));
+    let third = cents(t.target(4, 440.0), t.target(3, 440.0));
+    assert!((third - 1200.0 * libm::log2f(1.25)).abs() < 0.01);
+    assert_eq!(t.add_offsets(&[1.0]).add_offsets(&[1.0]).offsets()[0], 2.0);
+}
+
+#[test]
+fn capo_test() {
+    // capo on the 2nd fret, E2 sounds as F#2
+    let t = Tuning::STANDARD.with_capo(2);
+    assert_eq!(t.note(0), "F#2".parse().unwrap());
+    assert!((t.target(0, 440.0) - 92.50).abs() < 0.01);
+    assert_eq!(t.classify(93.0, 440.0).map(|(s, _)| s), Some(0));
+    assert_eq!(t.classify(123.47, 440.0).map(|(s, _)| s), Some(1));
+
+    // a quarter tone flat
+    let t = Tuning::STANDARD.transposed(0, -50.0);
+    let (s, c) = t.classify(110.0, 440.0).unwrap();
+    assert_eq!(s, 1);
+    assert!((c - 50.0).abs() < 0.01);
+
+    // a semitone down (Eb standard) with the capo on the 1st fret is
+    // standard again
+    let t = Tuning::STANDARD.transposed(-1, 0.0).with_capo(1);
+    for s in 0..6 {
+        assert_eq!(t.target(s, 440.0), Tuning::STANDARD.target(s, 440.0));
+    }
+}